@@ -0,0 +1,162 @@
+/// How the samples of a multichannel chunk are ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelLayout {
+    /// One frame after another, e.g. L R L R for stereo. The layout of WAV files and sound cards.
+    Interleaved,
+    /// One channel after another, e.g. L L R R for stereo.
+    Planar,
+}
+
+/// What an `AudioStreamer` does with the channels of its source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelMode {
+    /// Average all channels into one, the default.
+    Downmix,
+    /// Stream only the channel with this index, e.g. 0 for the left channel.
+    Select(u16),
+    /// Stream all channels in the given layout.
+    All(ChannelLayout),
+}
+
+impl ChannelMode {
+    /// The number of channels in the chunks this mode produces from a source with `channels` channels.
+    pub fn output_channels(&self, channels: u16) -> u16 {
+        match self {
+            ChannelMode::Downmix | ChannelMode::Select(_) => 1,
+            ChannelMode::All(_) => channels,
+        }
+    }
+
+    /// Converts a chunk from a source.
+    /// # Arguments
+    /// * `chunk` - The samples of all channels.
+    /// * `channels` - The number of channels of the source.
+    /// * `layout` - The layout of the source.
+    pub fn apply(&self, chunk: &[f32], channels: u16, layout: ChannelLayout) -> Vec<f32> {
+        match *self {
+            ChannelMode::Downmix => downmix(chunk, channels, layout),
+            ChannelMode::Select(channel) => split_channels(chunk, channels, layout).swap_remove(channel as usize),
+            ChannelMode::All(output_layout) if output_layout == layout => chunk.to_vec(),
+            ChannelMode::All(output_layout) => join_channels(&split_channels(chunk, channels, layout), output_layout),
+        }
+    }
+}
+
+/// Splits a chunk into one vector of samples per channel.
+/// # Panics
+/// If the chunk does not hold the same number of samples for every channel.
+pub fn split_channels(chunk: &[f32], channels: u16, layout: ChannelLayout) -> Vec<Vec<f32>> {
+    let channels = channels as usize;
+    assert!(chunk.len().is_multiple_of(channels), "Chunk length must be a multiple of the channel count.");
+    let frames = chunk.len() / channels;
+    match layout {
+        ChannelLayout::Interleaved => (0..channels)
+            .map(|channel| chunk.iter().skip(channel).step_by(channels).copied().collect())
+            .collect(),
+        ChannelLayout::Planar => (0..channels).map(|channel| chunk[channel * frames..(channel + 1) * frames].to_vec()).collect(),
+    }
+}
+
+/// Joins one vector of samples per channel into a single chunk, the inverse of `split_channels`.
+/// Channels longer than the shortest one are cut to its length.
+pub fn join_channels(channels: &[Vec<f32>], layout: ChannelLayout) -> Vec<f32> {
+    let frames = channels.iter().map(|channel| channel.len()).min().unwrap_or(0);
+    match layout {
+        ChannelLayout::Interleaved => (0..frames)
+            .flat_map(|i| channels.iter().map(move |channel| channel[i]))
+            .collect(),
+        ChannelLayout::Planar => channels.iter().flat_map(|channel| channel[..frames].iter().copied()).collect(),
+    }
+}
+
+/// Averages the channels of a chunk into one.
+pub fn downmix(chunk: &[f32], channels: u16, layout: ChannelLayout) -> Vec<f32> {
+    if channels == 1 {
+        return chunk.to_vec();
+    }
+    let split = split_channels(chunk, channels, layout);
+    (0..split[0].len())
+        .map(|i| split.iter().map(|channel| channel[i]).sum::<f32>() / channels as f32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resample::ResampledSource;
+    use crate::tests::write_wav;
+    use crate::{AudioSource, AudioStreamer, WavFileSource};
+
+    #[test]
+    fn layouts_convert_both_ways() {
+        let interleaved = [1.0, -1.0, 2.0, -2.0, 3.0, -3.0];
+        let split = split_channels(&interleaved, 2, ChannelLayout::Interleaved);
+        assert_eq!(split, vec![vec![1.0, 2.0, 3.0], vec![-1.0, -2.0, -3.0]]);
+
+        let planar = join_channels(&split, ChannelLayout::Planar);
+        assert_eq!(planar, vec![1.0, 2.0, 3.0, -1.0, -2.0, -3.0]);
+        assert_eq!(split_channels(&planar, 2, ChannelLayout::Planar), split);
+        assert_eq!(join_channels(&split, ChannelLayout::Interleaved), interleaved);
+    }
+
+    #[test]
+    fn modes_reduce_or_reorder_channels() {
+        let chunk = [0.5, 0.25, 0.75, 1.0];
+        let layout = ChannelLayout::Interleaved;
+        assert_eq!(ChannelMode::Downmix.apply(&chunk, 2, layout), vec![0.375, 0.875]);
+        assert_eq!(ChannelMode::Select(1).apply(&chunk, 2, layout), vec![0.25, 1.0]);
+        assert_eq!(ChannelMode::All(ChannelLayout::Planar).apply(&chunk, 2, layout), vec![0.5, 0.75, 0.25, 1.0]);
+        assert_eq!(ChannelMode::All(layout).apply(&chunk, 2, layout), chunk.to_vec());
+        assert_eq!(ChannelMode::Downmix.output_channels(2), 1);
+        assert_eq!(ChannelMode::All(layout).output_channels(2), 2);
+    }
+
+    #[test]
+    fn stereo_wav_file_streams_in_every_mode() {
+        let path = std::env::temp_dir().join("channels_test_stereo.wav");
+        // A rising ramp on the left, silence on the right, 0.1 seconds
+        let ramp: Vec<f64> = (0..800).map(|i| (i * 40) as f64 / 32768.0).collect();
+        write_wav(&path, 8000, &[ramp, vec![0.0; 800]]);
+
+        let open = || WavFileSource::new(path.to_str().unwrap()).unwrap();
+        let source = open();
+        assert_eq!(source.get_channels(), 2);
+        assert_eq!(source.get_length(), 800);
+        assert_eq!(source.get_duration(), std::time::Duration::from_secs_f32(0.1));
+        let left: Vec<f32> = (0..800).map(|i| (i * 40) as f32 / 32768.0).collect();
+        assert_eq!(open().read_mono().unwrap(), left.iter().map(|s| s / 2.0).collect::<Vec<f32>>());
+
+        let stream = |mode: ChannelMode| {
+            let mut streamer = AudioStreamer::new(open(), 256);
+            streamer.set_channel_mode(mode);
+            let (tx, rx) = std::sync::mpsc::channel();
+            streamer.run(tx).unwrap();
+            rx.iter().collect::<Vec<Vec<f32>>>()
+        };
+
+        let downmixed = stream(ChannelMode::Downmix);
+        assert_eq!(downmixed.iter().map(|c| c.len()).collect::<Vec<_>>(), vec![256, 256, 256, 32]);
+        assert_eq!(downmixed.concat(), left.iter().map(|s| s / 2.0).collect::<Vec<f32>>());
+        assert_eq!(stream(ChannelMode::Select(0)).concat(), left);
+        let planar = stream(ChannelMode::All(ChannelLayout::Planar));
+        assert_eq!(planar[0][..256], left[..256]);
+        assert!(planar[0][256..].iter().all(|&s| s == 0.0));
+
+        let (left_tx, left_rx) = std::sync::mpsc::channel();
+        let (right_tx, right_rx) = std::sync::mpsc::channel();
+        AudioStreamer::new(open(), 256).run_per_channel(vec![left_tx, right_tx]).unwrap();
+        assert_eq!(left_rx.iter().collect::<Vec<_>>().concat(), left);
+        assert_eq!(right_rx.iter().collect::<Vec<_>>().concat(), vec![0.0; 800]);
+
+        // Wrapping sources keep the channels apart
+        let mut resampled = ResampledSource::new(open(), 16000);
+        assert_eq!(resampled.get_channels(), 2);
+        let (tx, rx) = std::sync::mpsc::channel();
+        resampled.start_streaming(tx, 256).unwrap();
+        let right = split_channels(&rx.iter().collect::<Vec<_>>().concat(), 2, ChannelLayout::Interleaved).swap_remove(1);
+        assert_eq!(right.len(), 1600);
+        assert!(right.iter().all(|&s| s == 0.0));
+
+        let _ = std::fs::remove_file(path);
+    }
+}
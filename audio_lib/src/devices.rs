@@ -0,0 +1,149 @@
+use crate::describe_config_range;
+use cpal::traits::{DeviceTrait, HostTrait};
+use std::fmt;
+
+/// Which input device a `MicrophoneSource` records from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum InputDevice {
+    /// The default input device of the default host.
+    #[default]
+    Default,
+    /// The first device with this name, as listed by `list_input_devices`.
+    Name(String),
+    /// The device at this index of `list_input_devices`.
+    Index(usize),
+}
+
+impl InputDevice {
+    /// Parses a command-line argument, a number selects by index and anything else by name.
+    pub fn parse(argument: &str) -> Self {
+        match argument.parse() {
+            Ok(index) => InputDevice::Index(index),
+            Err(_) => InputDevice::Name(argument.to_string()),
+        }
+    }
+}
+
+/// An input device and the stream configs it supports.
+#[derive(Debug, Clone)]
+pub struct InputDeviceInfo {
+    /// The position in `list_input_devices`, which `InputDevice::Index` refers to.
+    pub index: usize,
+    /// The name of the audio host, e.g. ALSA or JACK.
+    pub host: String,
+    pub name: String,
+    /// Whether this is the default input device of the default host.
+    pub is_default: bool,
+    pub default_config: Option<cpal::SupportedStreamConfig>,
+    pub supported_configs: Vec<cpal::SupportedStreamConfigRange>,
+}
+
+impl fmt::Display for InputDeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.index, self.name, self.host)?;
+        if self.is_default {
+            write!(f, " [default]")?;
+        }
+        if let Some(config) = &self.default_config {
+            write!(f, "\n    default: {}, {} channels, {} Hz", config.sample_format(), config.channels(), config.sample_rate().0)?;
+        }
+        for range in &self.supported_configs {
+            write!(f, "\n    {}", describe_config_range(range))?;
+        }
+        Ok(())
+    }
+}
+
+/// The names of the audio hosts available on this platform.
+pub fn list_hosts() -> Vec<String> {
+    cpal::available_hosts().iter().map(|id| id.name().to_string()).collect()
+}
+
+/// Lists the input devices of every available host, the default host first.
+/// Hosts that fail to open or to list their devices, e.g. JACK without a running server, are skipped.
+/// # How to use:
+/// ```ignore
+/// for device in list_input_devices() {
+///     println!("{}", device);
+/// }
+/// let mic_source = MicrophoneSource::with_device(&InputDevice::Index(2), MicrophoneConfig::default())?;
+/// ```
+pub fn list_input_devices() -> Vec<InputDeviceInfo> {
+    let default_name = cpal::default_host().default_input_device().and_then(|device| device.name().ok());
+    let default_host = cpal::default_host().id();
+
+    let mut devices = Vec::new();
+    for (index, (host, device)) in input_devices().into_iter().enumerate() {
+        let name = device.name().unwrap_or_else(|_| "Unknown".to_string());
+        devices.push(InputDeviceInfo {
+            index,
+            host: host.name().to_string(),
+            is_default: host == default_host && default_name.as_ref() == Some(&name),
+            name,
+            default_config: device.default_input_config().ok(),
+            supported_configs: device.supported_input_configs().map(|configs| configs.collect()).unwrap_or_default(),
+        });
+    }
+    devices
+}
+
+/// Finds the cpal device a selection refers to.
+pub(crate) fn find_input_device(selection: &InputDevice) -> Result<cpal::Device, anyhow::Error> {
+    match selection {
+        InputDevice::Default => cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("No default input device available")),
+        InputDevice::Name(name) => input_devices()
+            .into_iter()
+            .map(|(_, device)| device)
+            .find(|device| device.name().is_ok_and(|device_name| &device_name == name))
+            .ok_or_else(|| anyhow::anyhow!("No input device named \"{}\". Available devices:\n{}", name, device_names())),
+        InputDevice::Index(index) => input_devices()
+            .into_iter()
+            .nth(*index)
+            .map(|(_, device)| device)
+            .ok_or_else(|| anyhow::anyhow!("No input device with index {}. Available devices:\n{}", index, device_names())),
+    }
+}
+
+/// The input devices of every host that opens, the default host first.
+fn input_devices() -> Vec<(cpal::HostId, cpal::Device)> {
+    let mut hosts = cpal::available_hosts();
+    let default_host = cpal::default_host().id();
+    hosts.sort_by_key(|&host| host != default_host);
+
+    let mut devices = Vec::new();
+    for host_id in hosts {
+        let Ok(host) = cpal::host_from_id(host_id) else {
+            continue;
+        };
+        let Ok(host_devices) = host.input_devices() else {
+            continue;
+        };
+        devices.extend(host_devices.map(|device| (host_id, device)));
+    }
+    devices
+}
+
+/// One line per input device, for error messages.
+fn device_names() -> String {
+    input_devices()
+        .iter()
+        .enumerate()
+        .map(|(index, (host, device))| format!("  {}: {} ({})", index, device.name().unwrap_or_else(|_| "Unknown".to_string()), host.name()))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_arguments_select_by_index_or_name() {
+        assert_eq!(InputDevice::parse("2"), InputDevice::Index(2));
+        assert_eq!(InputDevice::parse("USB Audio"), InputDevice::Name("USB Audio".to_string()));
+        assert_eq!(InputDevice::parse("-1"), InputDevice::Name("-1".to_string()));
+        assert_eq!(InputDevice::default(), InputDevice::Default);
+    }
+}
@@ -0,0 +1,293 @@
+use crate::channels::downmix;
+use crate::resample::Resampler;
+use crate::{stream_source, AudioSource, WavFileSource};
+use fft_lib::fingerprint::{fingerprint, FingerprintIndex, Landmark, SAMPLE_RATE};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+const DATABASE_MAGIC: [u8; 4] = *b"FPDB";
+const DATABASE_VERSION: u8 = 1;
+/// Matches with fewer agreeing landmarks than this are treated as chance.
+const MIN_SCORE: usize = 10;
+
+/// The reference recording a clip was identified as.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryMatch {
+    /// The name of the reference, its file name when built from a directory.
+    pub name: String,
+    /// The time in seconds of the reference where the clip starts.
+    pub offset: f64,
+    /// The number of landmarks that agree on the offset, higher is more certain.
+    pub score: usize,
+}
+
+/// A local database of reference recordings that clips can be identified against.
+/// Everything runs offline, the database is a single file on disk.
+/// # How to use:
+/// ```ignore
+/// // Once, after adding recordings to the directory
+/// let database = FingerprintDatabase::build_from_directory("path/to/references")?;
+/// database.save("references.fpdb")?;
+///
+/// // Identify ten seconds from the microphone
+/// let database = FingerprintDatabase::load("references.fpdb")?;
+/// let mut mic_source = MicrophoneSource::new()?;
+/// if let Some(found) = database.query_source(&mut mic_source, Duration::from_secs(10))? {
+///     println!("{} at {:.1} s (score {})", found.name, found.offset, found.score);
+/// }
+/// ```
+#[derive(Default)]
+pub struct FingerprintDatabase {
+    names: Vec<String>,
+    landmarks: Vec<Vec<Landmark>>,
+    index: FingerprintIndex,
+}
+
+impl FingerprintDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fingerprints every WAV file in a directory, in the order of their file names.
+    /// Other files and subdirectories are skipped.
+    pub fn build_from_directory(directory: &str) -> Result<Self, anyhow::Error> {
+        let mut paths: Vec<_> = std::fs::read_dir(directory)
+            .map_err(|e| anyhow::anyhow!("Failed to read directory {}: {}", directory, e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("wav")))
+            .collect();
+        paths.sort();
+
+        let mut database = Self::new();
+        for path in paths {
+            database.add_wav_file(&path)?;
+        }
+        Ok(database)
+    }
+
+    /// Fingerprints a WAV file and adds it under its file name.
+    pub fn add_wav_file(&mut self, path: &Path) -> Result<(), anyhow::Error> {
+        let path_str = path.to_str().ok_or_else(|| anyhow::anyhow!("Invalid path: {}", path.display()))?;
+        let (samples, sample_rate) = read_mono(path_str)?;
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        self.add(&name, &samples, sample_rate);
+        Ok(())
+    }
+
+    /// Fingerprints a recording and adds it under the given name.
+    /// # Arguments
+    /// * `name` - The name reported when a clip matches the recording.
+    /// * `samples` - The mono samples of the recording.
+    /// * `sample_rate` - The sample rate of the samples, they are resampled as needed.
+    pub fn add(&mut self, name: &str, samples: &[f32], sample_rate: u32) {
+        let landmarks = fingerprint(&prepare(samples, sample_rate));
+        self.insert(name.to_string(), landmarks);
+    }
+
+    /// The names of the references, in the order they were added.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Identifies a clip.
+    /// # Arguments
+    /// * `samples` - The mono samples of the clip, a few seconds are enough.
+    /// * `sample_rate` - The sample rate of the samples.
+    /// # Returns
+    /// The best matching reference, or `None` if no reference matches well enough.
+    pub fn query(&self, samples: &[f32], sample_rate: u32) -> Option<QueryMatch> {
+        let landmarks = fingerprint(&prepare(samples, sample_rate));
+        self.index
+            .query(&landmarks)
+            .first()
+            .filter(|best| best.score >= MIN_SCORE)
+            .map(|best| QueryMatch { name: self.names[best.reference].clone(), offset: best.offset_seconds(), score: best.score })
+    }
+
+    /// Identifies a clip stored in a WAV file.
+    pub fn query_wav_file(&self, path: &str) -> Result<Option<QueryMatch>, anyhow::Error> {
+        let (samples, sample_rate) = read_mono(path)?;
+        Ok(self.query(&samples, sample_rate))
+    }
+
+    /// Records a clip from a source, e.g. a `MicrophoneSource`, and identifies it.
+    /// # Arguments
+    /// * `source` - The source to record from. It is stopped once the clip is long enough.
+    /// * `duration` - How long to record.
+    pub fn query_source<T: AudioSource + Send>(&self, source: &mut T, duration: Duration) -> Result<Option<QueryMatch>, anyhow::Error> {
+        let (sample_rate, channels, layout) = (source.get_sample_rate(), source.get_channels(), source.get_layout());
+        let wanted = (duration.as_secs_f64() * sample_rate as f64) as usize;
+        let mut samples = Vec::with_capacity(wanted);
+        // Ends early if the source runs out first
+        stream_source(source, 1024, |chunk| {
+            samples.extend(downmix(&chunk, channels, layout));
+            samples.len() < wanted
+        })?;
+
+        Ok(self.query(&samples, sample_rate))
+    }
+
+    /// Writes the database to a file.
+    pub fn save(&self, path: &str) -> Result<(), anyhow::Error> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&DATABASE_MAGIC);
+        bytes.push(DATABASE_VERSION);
+        bytes.extend_from_slice(&(self.names.len() as u32).to_le_bytes());
+        for (name, landmarks) in self.names.iter().zip(self.landmarks.iter()) {
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&(landmarks.len() as u32).to_le_bytes());
+            for landmark in landmarks {
+                bytes.extend_from_slice(&landmark.hash.to_le_bytes());
+                bytes.extend_from_slice(&landmark.frame.to_le_bytes());
+            }
+        }
+
+        let mut file = std::fs::File::create(path).map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path, e))?;
+        file.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Reads a database written by `save`.
+    pub fn load(path: &str) -> Result<Self, anyhow::Error> {
+        let bytes = std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+        let mut reader = ByteReader { bytes: &bytes, position: 0 };
+
+        if reader.take(4)? != DATABASE_MAGIC {
+            return Err(anyhow::anyhow!("{} is not a fingerprint database", path));
+        }
+        let version = reader.take(1)?[0];
+        if version != DATABASE_VERSION {
+            return Err(anyhow::anyhow!("Unsupported fingerprint database version {}", version));
+        }
+
+        let mut database = Self::new();
+        for _ in 0..reader.read_u32()? {
+            let name_length = reader.read_u32()? as usize;
+            let name = String::from_utf8(reader.take(name_length)?.to_vec())?;
+            let count = reader.read_u32()? as usize;
+            let mut landmarks = Vec::with_capacity(count.min(bytes.len() / 8));
+            for _ in 0..count {
+                landmarks.push(Landmark { hash: reader.read_u32()?, frame: reader.read_u32()? });
+            }
+            database.insert(name, landmarks);
+        }
+        Ok(database)
+    }
+
+    fn insert(&mut self, name: String, landmarks: Vec<Landmark>) {
+        self.index.add(&landmarks);
+        self.names.push(name);
+        self.landmarks.push(landmarks);
+    }
+}
+
+/// Reads a WAV file and averages its channels.
+fn read_mono(path: &str) -> Result<(Vec<f32>, u32), anyhow::Error> {
+    let mut source = WavFileSource::new(path).map_err(|e| anyhow::anyhow!("Failed to open {}: {}", path, e))?;
+    Ok((source.read_mono()?, source.get_sample_rate()))
+}
+
+/// Resamples to the fingerprint sample rate.
+fn prepare(samples: &[f32], sample_rate: u32) -> Vec<f64> {
+    let resampled = if sample_rate == SAMPLE_RATE {
+        samples.to_vec()
+    } else {
+        let mut resampler = Resampler::new(sample_rate, SAMPLE_RATE);
+        let mut resampled = resampler.process(samples);
+        resampled.extend(resampler.finish());
+        resampled
+    };
+    resampled.iter().map(|&s| s as f64).collect()
+}
+
+/// Reads little-endian values from a byte slice, failing at the end instead of panicking.
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], anyhow::Error> {
+        let end = self.position.checked_add(length).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or_else(|| anyhow::anyhow!("Fingerprint database is truncated"))?;
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, anyhow::Error> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::write_wav;
+    use std::f64::consts::PI;
+
+    /// Random chords of three notes, a new chord every quarter second.
+    fn music(seed: u64, seconds: usize, sample_rate: u32) -> Vec<f64> {
+        let mut state = seed;
+        let mut random = move || {
+            state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let chord_length = sample_rate as usize / 4;
+        let mut samples = Vec::with_capacity(seconds * sample_rate as usize);
+        for _ in 0..seconds * 4 {
+            let frequencies: Vec<f64> = (0..3).map(|_| 200.0 + 2800.0 * random()).collect();
+            for i in 0..chord_length {
+                let t = i as f64 / sample_rate as f64;
+                samples.push(frequencies.iter().map(|f| 0.2 * (-4.0 * t).exp() * (2.0 * PI * f * t).sin()).sum());
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn directory_database_identifies_clip() {
+        let directory = std::env::temp_dir().join("fingerprint_test_references");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        write_wav(&directory.join("a.wav"), 16000, &[music(1, 12, 16000)]);
+        let stereo = music(2, 12, 22050);
+        write_wav(&directory.join("b.wav"), 22050, &[stereo.clone(), stereo]);
+        std::fs::write(directory.join("notes.txt"), "not audio").unwrap();
+
+        let database = FingerprintDatabase::build_from_directory(directory.to_str().unwrap()).unwrap();
+        assert_eq!(database.names(), ["a.wav", "b.wav"]);
+
+        let database_path = directory.join("references.fpdb");
+        database.save(database_path.to_str().unwrap()).unwrap();
+        let database = FingerprintDatabase::load(database_path.to_str().unwrap()).unwrap();
+        assert_eq!(database.names(), ["a.wav", "b.wav"]);
+
+        // Four seconds of the second reference starting at 5 seconds, recorded at another rate
+        let clip_path = directory.join("clip.wav");
+        let reference = music(2, 12, 44100);
+        write_wav(&clip_path, 44100, &[reference[5 * 44100..9 * 44100].to_vec()]);
+        let found = database.query_wav_file(clip_path.to_str().unwrap()).unwrap().unwrap();
+        assert_eq!(found.name, "b.wav");
+        assert!((found.offset - 5.0).abs() < 0.05, "offset {}", found.offset);
+
+        // Something that is not in the database
+        let other: Vec<f32> = music(3, 4, 16000).iter().map(|&s| s as f32).collect();
+        assert_eq!(database.query(&other, 16000), None);
+
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[test]
+    fn load_rejects_other_files() {
+        let path = std::env::temp_dir().join("fingerprint_test_invalid.fpdb");
+        std::fs::write(&path, b"FPDB\x01\x05\x00\x00\x00").unwrap();
+        assert!(FingerprintDatabase::load(path.to_str().unwrap()).is_err());
+        std::fs::write(&path, b"RIFF").unwrap();
+        assert!(FingerprintDatabase::load(path.to_str().unwrap()).is_err());
+        let _ = std::fs::remove_file(path);
+    }
+}
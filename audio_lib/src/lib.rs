@@ -1,18 +1,68 @@
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use hound::{WavReader, WavSpec};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use hound::{SampleFormat, WavReader, WavSpec};
 use std::fs::File;
 use std::io::BufReader;
 use std::sync::mpsc::Sender;
 use std::time::{Duration, Instant}; // Modified this line
 // std::thread is not directly used in this file anymore after the change, but keep if other parts use it.
 
+pub mod channels;
+pub mod devices;
+pub mod fingerprint;
+pub mod hpss;
+pub mod lpc;
+pub mod resample;
+pub mod transcription;
+pub mod vocoder;
+
+pub use channels::{ChannelLayout, ChannelMode};
+pub use devices::{list_hosts, list_input_devices, InputDevice, InputDeviceInfo};
+pub use cpal::SampleFormat as MicrophoneSampleFormat;
+pub use fingerprint::{FingerprintDatabase, QueryMatch};
+pub use hpss::{separate_wav_file, separate_wav_source};
+pub use lpc::{analyze_lpc_source, analyze_lpc_wav_file};
+pub use resample::{ResampledSource, Resampler};
+pub use transcription::transcribe_wav_file;
+pub use vocoder::{vocode_wav_file, VocodedSource};
+
 pub trait AudioSource {
     fn get_sample_rate(&self) -> u32;
     fn get_duration(&self) -> Duration;
+    /// The length in frames, i.e. samples per channel.
     fn get_length(&self) -> u64;
+    /// The number of channels in each chunk.
+    fn get_channels(&self) -> u16 {
+        1
+    }
+    /// How the channels of each chunk are ordered.
+    fn get_layout(&self) -> ChannelLayout {
+        ChannelLayout::Interleaved
+    }
+    /// Streams chunks of `chunk_size` frames, i.e. `chunk_size * get_channels()` samples,
+    /// until the source runs out or the receiver is dropped. The last chunk may be shorter.
     fn start_streaming(&mut self, sender: Sender<Vec<f32>>, chunk_size: usize) -> Result<(), anyhow::Error>;
 }
 
+/// Streams a source on a separate thread and hands each chunk to `forward`, until the
+/// source runs out or `forward` returns false. The source then stops at its next chunk.
+/// # Returns
+/// true if the source ran out, false if `forward` stopped it.
+pub(crate) fn stream_source<T: AudioSource + Send>(source: &mut T, chunk_size: usize, mut forward: impl FnMut(Vec<f32>) -> bool) -> Result<bool, anyhow::Error> {
+    let (source_tx, source_rx) = std::sync::mpsc::channel::<Vec<f32>>();
+    std::thread::scope(|scope| {
+        let source_thread = scope.spawn(move || source.start_streaming(source_tx, chunk_size));
+        // Ends when the source finishes and drops its sender
+        let finished = source_rx.iter().all(&mut forward);
+        // Dropping the receiver here makes the source stop at its next chunk
+        drop(source_rx);
+
+        source_thread
+            .join()
+            .map_err(|_| anyhow::anyhow!("Audio source thread panicked"))??;
+        Ok(finished)
+    })
+}
+
 /// Wav file source for audio streaming.
 /// # How to use:
 /// ```rust
@@ -69,12 +119,79 @@ impl WavFileSource {
         let file = File::open(file_path).map_err(|e| e.to_string())?;
         let reader = WavReader::new(BufReader::new(file)).map_err(|e| e.to_string())?;
         let spec = reader.spec();
+        match (spec.sample_format, spec.bits_per_sample) {
+            (SampleFormat::Int, 1..=32) | (SampleFormat::Float, 32) => {}
+            (format, bits) => return Err(format!("Unsupported WAV sample format: {} bit {:?}", bits, format)),
+        }
         let sample_rate = spec.sample_rate;
-        let length = reader.len() as u64;
+        let length = reader.duration() as u64;
         let duration = Duration::from_secs_f32(length as f32 / sample_rate as f32);
 
         Ok(Self { reader, spec, sample_rate, duration, length })
     }
+
+    /// Reads the next sample in the format of the file.
+    /// Integer samples are divided by 2^(bits - 1), so every bit depth covers -1.0 to 1.0,
+    /// float samples are passed through.
+    fn next_sample(&mut self) -> Option<Result<f32, anyhow::Error>> {
+        let sample = match self.spec.sample_format {
+            SampleFormat::Float => self.reader.samples::<f32>().next()?,
+            SampleFormat::Int => {
+                let scale = (1_u64 << (self.spec.bits_per_sample - 1)) as f32;
+                self.reader.samples::<i32>().next()?.map(|sample| sample as f32 / scale)
+            }
+        };
+        Some(sample.map_err(|e| anyhow::anyhow!("Error reading sample: {}", e)))
+    }
+
+    /// Reads all remaining samples at once, without pacing them in real time.
+    /// Useful for offline analysis of a whole file, e.g. beat tracking.
+    /// # Returns
+    /// The interleaved samples of all channels, normalized to the range -1.0 to 1.0.
+    pub fn read_all_samples(&mut self) -> Result<Vec<f32>, anyhow::Error> {
+        std::iter::from_fn(|| self.next_sample()).collect()
+    }
+
+    /// Reads all remaining samples at once and averages the channels into one.
+    /// # Returns
+    /// The mono samples, normalized to the range -1.0 to 1.0.
+    pub fn read_mono(&mut self) -> Result<Vec<f32>, anyhow::Error> {
+        let samples = self.read_all_samples()?;
+        Ok(channels::downmix(&samples, self.spec.channels, ChannelLayout::Interleaved))
+    }
+
+    /// Reads all remaining samples at once and splits the interleaved channels.
+    /// # Returns
+    /// One vector of samples per channel, normalized to the range -1.0 to 1.0.
+    pub fn read_all_channels(&mut self) -> Result<Vec<Vec<f32>>, anyhow::Error> {
+        let channels = self.spec.channels as usize;
+        let samples = self.read_all_samples()?;
+        Ok((0..channels)
+            .map(|channel| samples.iter().skip(channel).step_by(channels).copied().collect())
+            .collect())
+    }
+}
+
+/// Writes one vector of samples per channel as an interleaved 16-bit WAV file.
+/// Samples outside the range -1.0 to 1.0 are clipped.
+pub(crate) fn write_wav_channels(path: &str, sample_rate: u32, channels: &[Vec<f64>]) -> Result<(), anyhow::Error> {
+    let spec = WavSpec {
+        channels: channels.len() as u16,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)
+        .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path, e))?;
+    let length = channels.iter().map(|channel| channel.len()).min().unwrap_or(0);
+    for i in 0..length {
+        for channel in channels {
+            let sample = (channel[i].clamp(-1.0, 1.0) * i16::MAX as f64).round() as i16;
+            writer.write_sample(sample)?;
+        }
+    }
+    writer.finalize()?;
+    Ok(())
 }
 
 impl AudioSource for WavFileSource {
@@ -90,25 +207,31 @@ impl AudioSource for WavFileSource {
         self.length
     }
 
+    fn get_channels(&self) -> u16 {
+        self.spec.channels
+    }
+
     fn start_streaming(&mut self, sender: Sender<Vec<f32>>, chunk_size: usize) -> Result<(), anyhow::Error> {
         let sample_rate = self.spec.sample_rate;
-        let mut buffer = vec![0.0; chunk_size];
+        let channels = self.spec.channels as usize;
+        let mut buffer = vec![0.0; chunk_size * channels];
         let mut next_chunk_target_time = Instant::now();
 
         loop {
             let mut written = 0;
-            for i in 0..chunk_size {
-                if let Some(sample_result) = self.reader.samples::<i16>().next() {
-                    let sample = sample_result.map_err(|e| anyhow::anyhow!("Error reading sample: {}", e))?;
-                    buffer[i] = sample as f32 / i16::MAX as f32;
+            for slot in buffer.iter_mut() {
+                if let Some(sample) = self.next_sample() {
+                    *slot = sample?;
                     written += 1;
                 } else {
                     break; // End of file
                 }
             }
+            // A truncated file can end in the middle of a frame
+            written -= written % channels;
 
             if written > 0 {
-                let actual_data_duration = Duration::from_secs_f32(written as f32 / sample_rate as f32);
+                let actual_data_duration = Duration::from_secs_f32((written / channels) as f32 / sample_rate as f32);
 
                 if sender.send(buffer[0..written].to_vec()).is_err() {
                     eprintln!("WAV stream: Receiver dropped. Stopping.");
@@ -173,27 +296,138 @@ impl AudioSource for WavFileSource {
 pub struct MicrophoneSource {
     device: cpal::Device,
     config: cpal::StreamConfig,
+    sample_format: MicrophoneSampleFormat,
+}
+
+/// The stream settings to request from the microphone. Settings left as `None` use the
+/// device's default where possible.
+/// # How to use:
+/// ```ignore
+/// let mic_source = MicrophoneSource::with_config(MicrophoneConfig {
+///     sample_format: Some(MicrophoneSampleFormat::I16),
+///     sample_rate: Some(48000),
+///     buffer_size: Some(256),
+/// })?;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MicrophoneConfig {
+    /// The format the device delivers samples in. They are converted to f32 either way.
+    pub sample_format: Option<MicrophoneSampleFormat>,
+    /// The sample rate in Hz.
+    pub sample_rate: Option<u32>,
+    /// The number of frames the device delivers per callback.
+    pub buffer_size: Option<u32>,
 }
 
 impl MicrophoneSource {
+    /// Opens the default input device with its default config.
     pub fn new() -> Result<Self, anyhow::Error> {
-        let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .ok_or_else(|| anyhow::anyhow!("No default input device available"))?;
+        Self::with_config(MicrophoneConfig::default())
+    }
+
+    /// Opens the default input device with the requested config.
+    pub fn with_config(requested: MicrophoneConfig) -> Result<Self, anyhow::Error> {
+        Self::with_device(&InputDevice::Default, requested)
+    }
+
+    /// Opens an input device with the requested config.
+    /// # Arguments
+    /// * `selection` - The device to open, by name or by index of `list_input_devices`.
+    /// * `requested` - The format, sample rate and buffer size to use.
+    /// # Returns
+    /// A new `MicrophoneSource`, or an error listing the available devices if none matches
+    /// the selection, or the configs the device supports if none matches the request.
+    pub fn with_device(selection: &InputDevice, requested: MicrophoneConfig) -> Result<Self, anyhow::Error> {
+        let device = devices::find_input_device(selection)?;
         println!("Using input device: {}", device.name().unwrap_or_else(|_| "Unknown".to_string()));
-        let config = device
+        let default_config = device
             .default_input_config()
-            .map_err(|e| anyhow::anyhow!("Failed to get default input config: {}", e))?
-            .config();
+            .map_err(|e| anyhow::anyhow!("Failed to get default input config: {}", e))?;
+
+        let (mut config, sample_format) = if requested.sample_format.is_none() && requested.sample_rate.is_none() && requested.buffer_size.is_none() {
+            (default_config.config(), default_config.sample_format())
+        } else {
+            let ranges: Vec<cpal::SupportedStreamConfigRange> = device
+                .supported_input_configs()
+                .map_err(|e| anyhow::anyhow!("Failed to get supported input configs: {}", e))?
+                .collect();
+            let supported = negotiate_config(&ranges, &default_config, &requested)?;
+            (supported.config(), supported.sample_format())
+        };
+        if let Some(frames) = requested.buffer_size {
+            config.buffer_size = cpal::BufferSize::Fixed(frames);
+        }
 
-        println!("Microphone Default input config: Sample Rate: {:?}, Channels: {:?}, Format: {:?}", config.sample_rate, config.channels, device.default_input_config().unwrap().sample_format());
+        println!("Microphone input config: Sample Rate: {:?}, Channels: {:?}, Format: {:?}, Buffer Size: {:?}", config.sample_rate, config.channels, sample_format, config.buffer_size);
 
         Ok(MicrophoneSource {
             device,
             config,
+            sample_format,
         })
     }
+
+    pub fn get_sample_format(&self) -> MicrophoneSampleFormat {
+        self.sample_format
+    }
+}
+
+/// Finds a supported config that matches every requested setting. Among the matches the
+/// default format and channel count are preferred, then the default sample rate.
+fn negotiate_config(ranges: &[cpal::SupportedStreamConfigRange], default_config: &cpal::SupportedStreamConfig, requested: &MicrophoneConfig) -> Result<cpal::SupportedStreamConfig, anyhow::Error> {
+    let default_rate = default_config.sample_rate().0;
+    let best = ranges
+        .iter()
+        .filter(|range| requested.sample_format.is_none_or(|format| range.sample_format() == format))
+        .filter(|range| requested.sample_rate.is_none_or(|rate| (range.min_sample_rate().0..=range.max_sample_rate().0).contains(&rate)))
+        .filter(|range| match (requested.buffer_size, range.buffer_size()) {
+            (Some(frames), cpal::SupportedBufferSize::Range { min, max }) => (*min..=*max).contains(&frames),
+            _ => true,
+        })
+        .max_by_key(|range| {
+            (
+                range.sample_format() == default_config.sample_format(),
+                range.channels() == default_config.channels(),
+                (range.min_sample_rate().0..=range.max_sample_rate().0).contains(&default_rate),
+            )
+        });
+
+    let Some(range) = best else {
+        let supported: Vec<String> = ranges.iter().map(describe_config_range).collect();
+        return Err(anyhow::anyhow!("The input device does not support {:?}. Supported configs:\n  {}", requested, supported.join("\n  ")));
+    };
+    let rate = requested
+        .sample_rate
+        .unwrap_or_else(|| default_rate.clamp(range.min_sample_rate().0, range.max_sample_rate().0));
+    Ok(range.with_sample_rate(cpal::SampleRate(rate)))
+}
+
+fn describe_config_range(range: &cpal::SupportedStreamConfigRange) -> String {
+    let buffer_size = match range.buffer_size() {
+        cpal::SupportedBufferSize::Range { min, max } => format!("{}-{} frames", min, max),
+        cpal::SupportedBufferSize::Unknown => "unknown buffer size".to_string(),
+    };
+    format!("{}, {} channels, {}-{} Hz, {}", range.sample_format(), range.channels(), range.min_sample_rate().0, range.max_sample_rate().0, buffer_size)
+}
+
+/// Builds an input stream that delivers samples of type `T`, converted to f32.
+fn build_converting_stream<T>(device: &cpal::Device, config: &cpal::StreamConfig, mut on_data: impl FnMut(&[f32]) + Send + 'static) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    let err_fn = |err| eprintln!("An error occurred on the audio stream: {}", err);
+    let mut converted: Vec<f32> = Vec::new();
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            converted.clear();
+            converted.extend(data.iter().map(|&sample| sample.to_sample::<f32>()));
+            on_data(&converted);
+        },
+        err_fn,
+        None // Timeout
+    )
 }
 
 impl AudioSource for MicrophoneSource {
@@ -209,8 +443,13 @@ impl AudioSource for MicrophoneSource {
         u64::MAX // Indefinite for microphone
     }
 
+    fn get_channels(&self) -> u16 {
+        self.config.channels
+    }
+
     fn start_streaming(&mut self, sender: Sender<Vec<f32>>, chunk_size: usize) -> Result<(), anyhow::Error> {
-        let err_fn = |err| eprintln!("An error occurred on the audio stream: {}", err);
+        // cpal delivers interleaved frames
+        let chunk_size = chunk_size * self.config.channels as usize;
 
         // Channel to signal this function to stop from the audio callback
         let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
@@ -219,26 +458,38 @@ impl AudioSource for MicrophoneSource {
         
         let mut internal_buffer: Vec<f32> = Vec::with_capacity(chunk_size * 2); // Pre-allocate some space
 
-        let stream = self.device.build_input_stream(
-            &self.config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                internal_buffer.extend_from_slice(data);
-
-                while internal_buffer.len() >= chunk_size {
-                    // Drain the first desired_chunk_size elements from the buffer
-                    let chunk_to_send: Vec<f32> = internal_buffer.drain(0..chunk_size).collect();
-                    
-                    if callback_sender.send(chunk_to_send).is_err() {
-                        // Receiver has been dropped, signal the main streaming loop to stop.
-                        // Ignore error if stop_rx has already been dropped (main function exited)
-                        let _ = stop_tx.send(());
-                        return; // Stop processing in this callback invocation
-                    }
+        let on_data = move |data: &[f32]| {
+            internal_buffer.extend_from_slice(data);
+
+            while internal_buffer.len() >= chunk_size {
+                // Drain the first desired_chunk_size elements from the buffer
+                let chunk_to_send: Vec<f32> = internal_buffer.drain(0..chunk_size).collect();
+                
+                if callback_sender.send(chunk_to_send).is_err() {
+                    // Receiver has been dropped, signal the main streaming loop to stop.
+                    // Ignore error if stop_rx has already been dropped (main function exited)
+                    let _ = stop_tx.send(());
+                    return; // Stop processing in this callback invocation
                 }
-            },
-            err_fn,
-            None // Timeout
-        ).map_err(|e| anyhow::anyhow!("Failed to build input stream: {}", e))?;
+            }
+        };
+
+        // The callback receives the device's own sample type
+        let (device, config) = (&self.device, &self.config);
+        let stream = match self.sample_format {
+            MicrophoneSampleFormat::I8 => build_converting_stream::<i8>(device, config, on_data),
+            MicrophoneSampleFormat::I16 => build_converting_stream::<i16>(device, config, on_data),
+            MicrophoneSampleFormat::I32 => build_converting_stream::<i32>(device, config, on_data),
+            MicrophoneSampleFormat::I64 => build_converting_stream::<i64>(device, config, on_data),
+            MicrophoneSampleFormat::U8 => build_converting_stream::<u8>(device, config, on_data),
+            MicrophoneSampleFormat::U16 => build_converting_stream::<u16>(device, config, on_data),
+            MicrophoneSampleFormat::U32 => build_converting_stream::<u32>(device, config, on_data),
+            MicrophoneSampleFormat::U64 => build_converting_stream::<u64>(device, config, on_data),
+            MicrophoneSampleFormat::F32 => build_converting_stream::<f32>(device, config, on_data),
+            MicrophoneSampleFormat::F64 => build_converting_stream::<f64>(device, config, on_data),
+            format => return Err(anyhow::anyhow!("Unsupported sample format: {}", format)),
+        }
+        .map_err(|e| anyhow::anyhow!("Failed to build input stream: {}", e))?;
 
         stream.play().map_err(|e| anyhow::anyhow!("Failed to play stream: {}", e))?;
         println!("Microphone stream started. Waiting for stop signal...");
@@ -264,32 +515,65 @@ impl AudioSource for MicrophoneSource {
 }
 
 /// AudioStreamer struct for streaming audio data from a source.
+/// Multichannel sources are downmixed to mono unless another `ChannelMode` is set.
+/// # How to use:
+/// ```ignore
+/// let wav_source = WavFileSource::new("path/to/stereo.wav")
+///    .map_err(|e| anyhow::anyhow!("Failed to create WavFileSource: {}", e))?;
+/// let mut streamer = AudioStreamer::new(wav_source, 1024_usize);
+/// // Only the left channel
+/// streamer.set_channel_mode(ChannelMode::Select(0));
+///
+/// // Or every channel to its own receiver
+/// let (left_tx, left_rx) = std::sync::mpsc::channel::<Vec<f32>>();
+/// let (right_tx, right_rx) = std::sync::mpsc::channel::<Vec<f32>>();
+/// streamer.run_per_channel(vec![left_tx, right_tx])?;
+/// ```
 pub struct AudioStreamer<T: AudioSource + Send + 'static> {
     source: T,
     sample_rate: u32,
     chunk_size: usize,
-    #[allow(dead_code)] // channels is not used yet
     channels: u16,
+    channel_mode: ChannelMode,
 }
 
 impl<T: AudioSource + Send + 'static> AudioStreamer<T> {
     /// Creates a new AudioStreamer instance.
     /// # Arguments
     /// * `source` - The audio source to stream from.
-    /// * `chunk_size` - The size of the audio chunks to stream.
+    /// * `chunk_size` - The number of frames in each audio chunk.
     /// # Returns
     /// A new `AudioStreamer` instance.
     /// # Panics
     /// If the chunk size is not a power of 2.
     pub fn new(source: T, chunk_size: usize) -> Self {
         let sample_rate = source.get_sample_rate();
-        let channels = 1; // Assuming mono for now, or get from source if available/needed
+        let channels = source.get_channels();
 
         if chunk_size != chunk_size.next_power_of_two() {
             panic!("Chunk size must be a power of 2.");
         }
 
-        AudioStreamer { source, sample_rate, channels, chunk_size }
+        AudioStreamer { source, sample_rate, channels, chunk_size, channel_mode: ChannelMode::Downmix }
+    }
+
+    /// Sets what `run` does with the channels of the source.
+    /// # Panics
+    /// If `ChannelMode::Select` names a channel the source does not have.
+    pub fn set_channel_mode(&mut self, channel_mode: ChannelMode) {
+        if let ChannelMode::Select(channel) = channel_mode {
+            assert!(channel < self.channels, "Channel {} is out of range for a source with {} channels.", channel, self.channels);
+        }
+        self.channel_mode = channel_mode;
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The number of channels in the chunks `run` sends.
+    pub fn get_channels(&self) -> u16 {
+        self.channel_mode.output_channels(self.channels)
     }
 
     /// Starts streaming audio data from the source.
@@ -299,8 +583,162 @@ impl<T: AudioSource + Send + 'static> AudioStreamer<T> {
     /// A result indicating success or failure.
     pub fn run(&mut self, sender: Sender<Vec<f32>>) -> Result<(), anyhow::Error> {
         println!("AudioStreamer: Starting source streaming...");
-        self.source.start_streaming(sender, self.chunk_size)?;
+        let (channels, layout, channel_mode) = (self.channels, self.source.get_layout(), self.channel_mode);
+        if channels == 1 || channel_mode == ChannelMode::All(layout) {
+            // Nothing to convert
+            self.source.start_streaming(sender, self.chunk_size)?;
+        } else if !stream_source(&mut self.source, self.chunk_size, |chunk| sender.send(channel_mode.apply(&chunk, channels, layout)).is_ok())? {
+            eprintln!("AudioStreamer: Receiver dropped. Stopping.");
+        }
         println!("AudioStreamer: Source streaming finished.");
         Ok(())
     }
+
+    /// Starts streaming each channel of the source to its own receiver.
+    /// Streaming stops once every receiver has been dropped.
+    /// # Arguments
+    /// * `senders` - One channel sender per channel of the source.
+    /// # Returns
+    /// A result indicating success or failure.
+    pub fn run_per_channel(&mut self, senders: Vec<Sender<Vec<f32>>>) -> Result<(), anyhow::Error> {
+        if senders.len() != self.channels as usize {
+            return Err(anyhow::anyhow!("Expected {} senders, one per channel, got {}", self.channels, senders.len()));
+        }
+        let (channels, layout) = (self.channels, self.source.get_layout());
+        let mut connected = vec![true; senders.len()];
+        stream_source(&mut self.source, self.chunk_size, |chunk| {
+            for ((sender, connected), samples) in senders.iter().zip(connected.iter_mut()).zip(channels::split_channels(&chunk, channels, layout)) {
+                *connected = *connected && sender.send(samples).is_ok();
+            }
+            connected.contains(&true)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use hound::WavWriter;
+    use std::path::Path;
+
+    /// A sine at half of full scale.
+    pub(crate) fn sine(frequency: f64, sample_rate: u32, length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|i| (0.5 * (2.0 * std::f64::consts::PI * frequency * i as f64 / sample_rate as f64).sin()) as f32)
+            .collect()
+    }
+
+    /// Writes one vector of samples per channel to a 16-bit WAV file.
+    pub(crate) fn write_wav(path: &Path, sample_rate: u32, channels: &[Vec<f64>]) {
+        write_wav_as(path, sample_rate, channels, 16, SampleFormat::Int);
+    }
+
+    /// Writes one vector of samples per channel to a WAV file in any format hound can write.
+    /// Integers are scaled by 2^(bits - 1), so full scale up is clamped to the largest value.
+    pub(crate) fn write_wav_as(path: &Path, sample_rate: u32, channels: &[Vec<f64>], bits_per_sample: u16, sample_format: SampleFormat) {
+        let spec = WavSpec { channels: channels.len() as u16, sample_rate, bits_per_sample, sample_format };
+        let mut writer = WavWriter::create(path, spec).unwrap();
+        let scale = (1_i64 << (bits_per_sample - 1)) as f64;
+        for i in 0..channels[0].len() {
+            for channel in channels {
+                match sample_format {
+                    SampleFormat::Float => writer.write_sample(channel[i] as f32).unwrap(),
+                    SampleFormat::Int => writer.write_sample((channel[i] * scale).clamp(-scale, scale - 1.0) as i32).unwrap(),
+                }
+            }
+        }
+        writer.finalize().unwrap();
+    }
+
+    /// Full scale down, half scale down, silence, half scale up and the largest value.
+    const LEVELS: [f64; 5] = [-1.0, -0.5, 0.0, 0.5, 1.0];
+
+    /// Writes `LEVELS` as a stereo file, the right channel inverted.
+    fn write_fixture(name: &str, bits_per_sample: u16, sample_format: SampleFormat) -> String {
+        let path = std::env::temp_dir().join(name);
+        let inverted = LEVELS.iter().map(|level| -level).collect();
+        write_wav_as(&path, 8000, &[LEVELS.to_vec(), inverted], bits_per_sample, sample_format);
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn every_sample_format_is_normalized() {
+        for (bits, format) in [(8, SampleFormat::Int), (16, SampleFormat::Int), (24, SampleFormat::Int), (32, SampleFormat::Int), (32, SampleFormat::Float)] {
+            let path = write_fixture(&format!("wav_format_test_{}_{:?}.wav", bits, format), bits, format);
+            // The largest integer is one step below full scale
+            let step = match format {
+                SampleFormat::Int => 1.0 / (1_u64 << (bits - 1)) as f64,
+                SampleFormat::Float => 0.0,
+            };
+            let expected: Vec<f32> = LEVELS
+                .iter()
+                .flat_map(|&level| [level, -level])
+                .map(|sample| if sample == 1.0 { 1.0 - step } else { sample } as f32)
+                .collect();
+
+            let mut source = WavFileSource::new(&path).unwrap();
+            assert_eq!(source.get_length(), 5);
+            assert_eq!(source.read_all_samples().unwrap(), expected, "{} bit {:?}", bits, format);
+
+            // The inverted right channel cancels the left one
+            let mut source = WavFileSource::new(&path).unwrap();
+            let mono = source.read_mono().unwrap();
+            assert_eq!(mono.len(), 5);
+            assert!(mono.iter().all(|&s| s.abs() <= step as f32), "{} bit {:?}", bits, format);
+
+            // Streaming reads the same samples
+            let mut source = WavFileSource::new(&path).unwrap();
+            let (tx, rx) = std::sync::mpsc::channel();
+            source.start_streaming(tx, 4).unwrap();
+            assert_eq!(rx.iter().collect::<Vec<_>>().concat(), expected, "{} bit {:?}", bits, format);
+
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn microphone_config_matches_every_requested_setting() {
+        let buffer_size = cpal::SupportedBufferSize::Range { min: 64, max: 4096 };
+        let range = |channels, max_rate, format| cpal::SupportedStreamConfigRange::new(channels, cpal::SampleRate(8000), cpal::SampleRate(max_rate), buffer_size, format);
+        let ranges = [
+            range(2, 48000, MicrophoneSampleFormat::I16),
+            range(1, 48000, MicrophoneSampleFormat::I16),
+            range(2, 96000, MicrophoneSampleFormat::F32),
+        ];
+        let default_config = cpal::SupportedStreamConfig::new(1, cpal::SampleRate(44100), buffer_size, MicrophoneSampleFormat::I16);
+
+        // Nothing specific requested, so the defaults win
+        let config = negotiate_config(&ranges, &default_config, &MicrophoneConfig { buffer_size: Some(256), ..MicrophoneConfig::default() }).unwrap();
+        assert_eq!((config.channels(), config.sample_rate().0, config.sample_format()), (1, 44100, MicrophoneSampleFormat::I16));
+
+        // Only the float range reaches 96 kHz
+        let requested = MicrophoneConfig { sample_rate: Some(96000), ..MicrophoneConfig::default() };
+        let config = negotiate_config(&ranges, &default_config, &requested).unwrap();
+        assert_eq!((config.channels(), config.sample_rate().0, config.sample_format()), (2, 96000, MicrophoneSampleFormat::F32));
+
+        // The error lists what the device supports
+        let requested = MicrophoneConfig { sample_format: Some(MicrophoneSampleFormat::U16), ..MicrophoneConfig::default() };
+        let error = negotiate_config(&ranges, &default_config, &requested).unwrap_err().to_string();
+        assert!(error.contains("f32, 2 channels, 8000-96000 Hz, 64-4096 frames"), "{}", error);
+        let requested = MicrophoneConfig { buffer_size: Some(8192), ..MicrophoneConfig::default() };
+        assert!(negotiate_config(&ranges, &default_config, &requested).is_err());
+
+        // Of two otherwise equal ranges, the one that covers the default rate wins
+        let ranges = [range(1, 96000, MicrophoneSampleFormat::I16), range(1, 48000, MicrophoneSampleFormat::I16)];
+        let default_config = cpal::SupportedStreamConfig::new(1, cpal::SampleRate(96000), buffer_size, MicrophoneSampleFormat::I16);
+        let config = negotiate_config(&ranges, &default_config, &MicrophoneConfig::default()).unwrap();
+        assert_eq!(config.sample_rate().0, 96000);
+    }
+
+    #[test]
+    fn unsupported_formats_are_rejected() {
+        let path = std::env::temp_dir().join("wav_format_test_64_float.wav");
+        // hound cannot write 64-bit floats, so patch the header of a 32-bit file
+        let mut bytes = std::fs::read(write_fixture("wav_format_test_64_float.wav", 32, SampleFormat::Float)).unwrap();
+        bytes[34] = 64;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(WavFileSource::new(path.to_str().unwrap()).is_err());
+        let _ = std::fs::remove_file(path);
+    }
 }
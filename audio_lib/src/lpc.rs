@@ -0,0 +1,68 @@
+use crate::channels::downmix;
+use crate::{stream_source, AudioSource, WavFileSource};
+use fft_lib::lpc::{LpcAnalyzer, LpcFrame};
+
+/// Number of frames read from the source at a time.
+const CHUNK_SIZE: usize = 1024;
+
+/// Runs linear prediction and formant estimation on a source as it streams, with the speech
+/// settings of `LpcAnalyzer::new`: 25 ms frames every 10 ms. The channels are mixed to mono.
+/// # How to use:
+/// ```ignore
+/// // Print the formants heard by the microphone
+/// let mut microphone = MicrophoneSource::new()?;
+/// analyze_lpc_source(&mut microphone, |frame| {
+///     println!("{:.2} s: {:?}", frame.time, frame.formants);
+///     true
+/// })?;
+/// ```
+/// # Arguments
+/// * `source` - The audio source to analyze.
+/// * `on_frame` - Called with every analyzed frame, returns false to stop the analysis.
+pub fn analyze_lpc_source<T: AudioSource + Send>(source: &mut T, mut on_frame: impl FnMut(LpcFrame) -> bool) -> Result<(), anyhow::Error> {
+    let (channels, layout) = (source.get_channels(), source.get_layout());
+    let mut analyzer = LpcAnalyzer::new(source.get_sample_rate());
+    stream_source(source, CHUNK_SIZE, |chunk| {
+        let input: Vec<f64> = downmix(&chunk, channels, layout).iter().map(|&s| s as f64).collect();
+        analyzer.process(&input).into_iter().all(&mut on_frame)
+    })?;
+    Ok(())
+}
+
+/// Runs linear prediction and formant estimation on a whole WAV file at once, with the
+/// speech settings of `LpcAnalyzer::new`. The channels are mixed to mono first.
+/// # Arguments
+/// * `input_path` - The WAV file to read.
+/// # Returns
+/// The analysis of every complete frame, 10 ms apart.
+pub fn analyze_lpc_wav_file(input_path: &str) -> Result<Vec<LpcFrame>, anyhow::Error> {
+    let mut source = WavFileSource::new(input_path)
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", input_path, e))?;
+    let mono: Vec<f64> = source.read_mono()?.iter().map(|&s| s as f64).collect();
+
+    Ok(LpcAnalyzer::new(source.get_sample_rate()).process(&mono))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{sine, write_wav};
+
+    #[test]
+    fn wav_file_frames_every_ten_milliseconds() {
+        let path = std::env::temp_dir().join("lpc_test_input.wav");
+        // The same tone on both channels, so the mix is the tone
+        let tone: Vec<f64> = sine(600.0, 8000, 8000).iter().map(|&s| s as f64).collect();
+        write_wav(&path, 8000, &[tone.clone(), tone]);
+
+        let frames = analyze_lpc_wav_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(frames.len(), (8000 - 200) / 80 + 1);
+        assert!((frames[0].time - 0.0125).abs() < 1e-9);
+        for frame in &frames {
+            assert_eq!(frame.lpc.coefficients.len(), 10 + 1);
+            assert!(frame.formants.iter().any(|formant| (formant.frequency - 600.0).abs() < 30.0));
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,498 @@
+use crate::channels::{join_channels, split_channels, ChannelLayout};
+use crate::{stream_source, AudioSource};
+use std::f64::consts::PI;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+/// Number of zero crossings of the sinc on each side of the center, at unity ratio.
+/// Together with the Kaiser window this gives a transition band of about 5% of the sample rate.
+const ZERO_CROSSINGS: usize = 64;
+/// Number of fractional positions the filter table is computed for.
+/// Positions in between are linearly interpolated.
+const PHASES: usize = 256;
+/// Cutoff relative to the lower of the two Nyquist frequencies, so the transition band
+/// ends right at Nyquist and nothing above it aliases back.
+const CUTOFF: f64 = 0.95;
+/// Kaiser window shape, gives about 100 dB of stopband attenuation.
+const KAISER_BETA: f64 = 10.0;
+
+/// Polyphase windowed-sinc resampler for converting between any two sample rates.
+///
+/// The ratio is kept as an exact fraction of the two rates, so the output never drifts
+/// relative to the input, no matter how long the stream is. The output is aligned with the
+/// input (output sample 0 is at the same time as input sample 0), which means `process` holds
+/// back the last few samples of each chunk until enough input has arrived after them.
+/// # How to use:
+/// ```ignore
+/// let mut resampler = Resampler::new(44100, 48000);
+/// let mut output = Vec::new();
+/// for chunk in input.chunks(1024) {
+///     output.extend(resampler.process(chunk));
+/// }
+/// output.extend(resampler.finish());
+/// ```
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    /// Input step per output sample is `input_step / output_step` input samples.
+    input_step: u64,
+    output_step: u64,
+    /// Half the filter length, in input samples.
+    half_length: usize,
+    /// `PHASES + 1` rows of `2 * half_length` taps each.
+    table: Vec<f32>,
+    buffer: Vec<f32>,
+    /// Input index of `buffer[0]`, negative while the initial zero padding is in the buffer.
+    buffer_start: i64,
+    /// Integer and fractional (in units of 1 / `output_step`) input position of the next output.
+    position: i64,
+    remainder: u64,
+    input_count: i64,
+}
+
+impl Resampler {
+    /// Creates a new resampler.
+    /// # Arguments
+    /// * `input_rate` - The sample rate of the samples passed to `process`.
+    /// * `output_rate` - The sample rate of the returned samples.
+    /// # Returns
+    /// A new `Resampler` instance.
+    /// # Panics
+    /// If one of the rates is 0.
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        if input_rate == 0 || output_rate == 0 {
+            panic!("Sample rates must be greater than 0.");
+        }
+
+        let divisor = gcd(input_rate as u64, output_rate as u64);
+        // When downsampling the filter is stretched to cut off at the output Nyquist frequency
+        let scale = (output_rate as f64 / input_rate as f64).min(1.0);
+        let half_length = (ZERO_CROSSINGS as f64 / scale).ceil() as usize;
+
+        let mut resampler = Self {
+            input_rate,
+            output_rate,
+            input_step: input_rate as u64 / divisor,
+            output_step: output_rate as u64 / divisor,
+            half_length,
+            table: build_table(half_length, scale * CUTOFF),
+            buffer: Vec::new(),
+            buffer_start: 0,
+            position: 0,
+            remainder: 0,
+            input_count: 0,
+        };
+        resampler.reset();
+        resampler
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    pub fn output_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    /// Resamples a chunk of a stream.
+    /// # Arguments
+    /// * `input` - The next input samples.
+    /// # Returns
+    /// Every output sample that can be calculated from the input so far.
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.input_rate == self.output_rate {
+            return input.to_vec();
+        }
+
+        self.buffer.extend_from_slice(input);
+        self.input_count += input.len() as i64;
+
+        let available = self.buffer_start + self.buffer.len() as i64;
+        let mut output = Vec::new();
+        while self.position + (self.half_length as i64) < available {
+            output.push(self.next_sample());
+        }
+
+        // Drop the input that no future output sample needs anymore
+        let first_needed = self.position - self.half_length as i64 + 1;
+        let drop = (first_needed - self.buffer_start).clamp(0, self.buffer.len() as i64);
+        self.buffer.drain(..drop as usize);
+        self.buffer_start += drop;
+
+        output
+    }
+
+    /// Flushes the samples held back at the end of the stream and resets the resampler.
+    /// # Returns
+    /// The remaining output samples, so the total output covers the same duration as the input.
+    pub fn finish(&mut self) -> Vec<f32> {
+        if self.input_rate == self.output_rate {
+            return Vec::new();
+        }
+
+        self.buffer
+            .extend(std::iter::repeat_n(0.0, self.half_length + 1));
+
+        let mut output = Vec::new();
+        while self.position < self.input_count {
+            output.push(self.next_sample());
+        }
+
+        self.reset();
+        output
+    }
+
+    /// Clears the stream state, as if no samples had been processed.
+    pub fn reset(&mut self) {
+        // Pretend the signal was silent before the first sample
+        self.buffer = vec![0.0; self.half_length];
+        self.buffer_start = -(self.half_length as i64);
+        self.position = 0;
+        self.remainder = 0;
+        self.input_count = 0;
+    }
+
+    /// The number of output samples a complete stream of `input_length` samples results in.
+    pub fn output_length(&self, input_length: u64) -> u64 {
+        resampled_length(input_length, self.input_rate, self.output_rate)
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        let taps = 2 * self.half_length;
+        let fraction = self.remainder as f64 / self.output_step as f64 * PHASES as f64;
+        let phase = fraction as usize;
+        let weight = (fraction - phase as f64) as f32;
+
+        let first = (self.position - self.half_length as i64 + 1 - self.buffer_start) as usize;
+        let samples = &self.buffer[first..first + taps];
+        let row = &self.table[phase * taps..(phase + 1) * taps];
+        let next_row = &self.table[(phase + 1) * taps..(phase + 2) * taps];
+
+        let mut sum = 0.0;
+        for ((sample, a), b) in samples.iter().zip(row).zip(next_row) {
+            sum += sample * (a + weight * (b - a));
+        }
+
+        self.remainder += self.input_step;
+        self.position += (self.remainder / self.output_step) as i64;
+        self.remainder %= self.output_step;
+        sum
+    }
+}
+
+/// Wraps an `AudioSource` so it streams at a different sample rate.
+/// # How to use:
+/// ```ignore
+/// let mic_source = MicrophoneSource::new()?;
+/// // Always analyze at 48 kHz, whatever rate the device runs at
+/// let source = ResampledSource::new(mic_source, 48000);
+/// let mut streamer = AudioStreamer::new(source, 1024_usize);
+/// ```
+pub struct ResampledSource<T: AudioSource + Send> {
+    source: T,
+    output_rate: u32,
+}
+
+impl<T: AudioSource + Send> ResampledSource<T> {
+    /// Creates a new ResampledSource instance.
+    /// # Arguments
+    /// * `source` - The audio source to resample.
+    /// * `output_rate` - The sample rate to stream at.
+    /// # Returns
+    /// A new `ResampledSource` instance.
+    pub fn new(source: T, output_rate: u32) -> Self {
+        ResampledSource { source, output_rate }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.source
+    }
+}
+
+impl<T: AudioSource + Send> AudioSource for ResampledSource<T> {
+    fn get_sample_rate(&self) -> u32 {
+        self.output_rate
+    }
+
+    fn get_duration(&self) -> Duration {
+        self.source.get_duration()
+    }
+
+    fn get_length(&self) -> u64 {
+        let length = self.source.get_length();
+        if length == u64::MAX {
+            return u64::MAX; // Indefinite stays indefinite
+        }
+        resampled_length(length, self.source.get_sample_rate(), self.output_rate)
+    }
+
+    fn get_channels(&self) -> u16 {
+        self.source.get_channels()
+    }
+
+    fn get_layout(&self) -> ChannelLayout {
+        self.source.get_layout()
+    }
+
+    fn start_streaming(&mut self, sender: Sender<Vec<f32>>, chunk_size: usize) -> Result<(), anyhow::Error> {
+        let (channels, layout) = (self.source.get_channels(), self.source.get_layout());
+        // Each channel is resampled on its own
+        let mut resamplers: Vec<Resampler> = (0..channels)
+            .map(|_| Resampler::new(self.source.get_sample_rate(), self.output_rate))
+            .collect();
+        let mut pending: Vec<Vec<f32>> = vec![Vec::with_capacity(chunk_size * 2); channels as usize];
+        let finished = stream_source(&mut self.source, chunk_size, |chunk| {
+            for ((resampler, pending), samples) in resamplers.iter_mut().zip(pending.iter_mut()).zip(split_channels(&chunk, channels, layout)) {
+                pending.extend(resampler.process(&samples));
+            }
+            send_chunks(&sender, &mut pending, chunk_size, layout)
+        })?;
+
+        if finished {
+            for (resampler, pending) in resamplers.iter_mut().zip(pending.iter_mut()) {
+                pending.extend(resampler.finish());
+            }
+            if send_chunks(&sender, &mut pending, chunk_size, layout) {
+                send_remainder(&sender, &pending, layout);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sends every complete chunk in `pending`, which holds the samples of each channel.
+/// # Returns
+/// false if the receiver has been dropped.
+pub(crate) fn send_chunks(sender: &Sender<Vec<f32>>, pending: &mut [Vec<f32>], chunk_size: usize, layout: ChannelLayout) -> bool {
+    while pending.iter().all(|channel| channel.len() >= chunk_size) {
+        let channels: Vec<Vec<f32>> = pending.iter_mut().map(|channel| channel.drain(0..chunk_size).collect()).collect();
+        if sender.send(join_channels(&channels, layout)).is_err() {
+            eprintln!("Resampled stream: Receiver dropped. Stopping.");
+            return false;
+        }
+    }
+    true
+}
+
+/// Sends what is left in `pending` after the last complete chunk, if anything.
+pub(crate) fn send_remainder(sender: &Sender<Vec<f32>>, pending: &[Vec<f32>], layout: ChannelLayout) {
+    if pending.iter().any(|channel| !channel.is_empty()) {
+        let _ = sender.send(join_channels(pending, layout));
+    }
+}
+
+fn build_table(half_length: usize, cutoff: f64) -> Vec<f32> {
+    let taps = 2 * half_length;
+    let mut table = Vec::with_capacity((PHASES + 1) * taps);
+    for phase in 0..=PHASES {
+        for tap in 0..taps {
+            // Distance from the output position to the input sample this tap multiplies
+            let distance = phase as f64 / PHASES as f64 + half_length as f64 - 1.0 - tap as f64;
+            table.push(kernel(distance, half_length as f64, cutoff) as f32);
+        }
+    }
+    table
+}
+
+/// Kaiser-windowed sinc lowpass, `cutoff` relative to the input Nyquist frequency.
+fn kernel(distance: f64, half_length: f64, cutoff: f64) -> f64 {
+    let ratio = distance / half_length;
+    if ratio.abs() >= 1.0 {
+        return 0.0;
+    }
+    let x = PI * cutoff * distance;
+    let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+    let window = bessel_i0(KAISER_BETA * (1.0 - ratio * ratio).sqrt()) / bessel_i0(KAISER_BETA);
+    cutoff * sinc * window
+}
+
+/// Modified Bessel function of the first kind, order 0, by its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..50 {
+        term *= (half / k as f64) * (half / k as f64);
+        sum += term;
+        if term < sum * 1e-17 {
+            break;
+        }
+    }
+    sum
+}
+
+/// The number of samples `length` input samples become at another rate, rounded up.
+fn resampled_length(length: u64, input_rate: u32, output_rate: u32) -> u64 {
+    (length as u128 * output_rate as u128).div_ceil(input_rate as u128) as u64
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::sine;
+
+    /// THD+N in dB: fits the fundamental by least squares and compares what is left to it.
+    fn thd_n(samples: &[f32], frequency: f64, sample_rate: u32) -> f64 {
+        let omega = 2.0 * PI * frequency / sample_rate as f64;
+        let (mut ss, mut cc, mut sc, mut ys, mut yc) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (i, y) in samples.iter().enumerate() {
+            let (s, c) = (omega * i as f64).sin_cos();
+            ss += s * s;
+            cc += c * c;
+            sc += s * c;
+            ys += *y as f64 * s;
+            yc += *y as f64 * c;
+        }
+        let determinant = ss * cc - sc * sc;
+        let a = (ys * cc - yc * sc) / determinant;
+        let b = (yc * ss - ys * sc) / determinant;
+
+        let mut fundamental = 0.0;
+        let mut residual = 0.0;
+        for (i, y) in samples.iter().enumerate() {
+            let (s, c) = (omega * i as f64).sin_cos();
+            let fit = a * s + b * c;
+            fundamental += fit * fit;
+            residual += (*y as f64 - fit).powi(2);
+        }
+        10.0 * (residual / fundamental).log10()
+    }
+
+    fn rms_db(samples: &[f32]) -> f64 {
+        let power = samples.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / samples.len() as f64;
+        10.0 * power.log10()
+    }
+
+    #[test]
+    fn low_distortion_for_common_ratios() {
+        for (input_rate, output_rate) in [(44100, 48000), (48000, 44100), (48000, 16000), (8000, 44100)] {
+            let input = sine(1000.0, input_rate, input_rate as usize);
+            let mut resampler = Resampler::new(input_rate, output_rate);
+            let output = resampler.process(&input);
+
+            // Skip the edges, where the signal starts and stops abruptly
+            let margin = output_rate as usize / 10;
+            let steady = &output[margin..output.len() - margin];
+            let distortion = thd_n(steady, 1000.0, output_rate);
+            assert!(
+                distortion < -90.0,
+                "THD+N {:.1} dB for {} -> {}",
+                distortion,
+                input_rate,
+                output_rate
+            );
+        }
+    }
+
+    #[test]
+    fn downsampling_rejects_aliases() {
+        // 13 kHz would alias to 3 kHz at 16 kHz
+        let input = sine(13000.0, 48000, 48000);
+        let output = Resampler::new(48000, 16000).process(&input);
+        let steady = &output[1600..output.len() - 1600];
+        assert!(rms_db(steady) - rms_db(&input) < -90.0);
+
+        // while 7 kHz is still in the passband
+        let input = sine(7000.0, 48000, 48000);
+        let output = Resampler::new(48000, 16000).process(&input);
+        let steady = &output[1600..output.len() - 1600];
+        assert!((rms_db(steady) - rms_db(&input)).abs() < 0.01);
+    }
+
+    #[test]
+    fn upsampling_leaves_no_images() {
+        // Upsampling 1 kHz from 8 kHz would leave images at 7 kHz and 9 kHz without filtering
+        let input = sine(1000.0, 8000, 8000);
+        let output = Resampler::new(8000, 48000).process(&input);
+        let steady = &output[4800..output.len() - 4800];
+        assert!(thd_n(steady, 1000.0, 48000) < -90.0);
+    }
+
+    #[test]
+    fn chunked_matches_single_pass_and_keeps_length() {
+        let input = sine(440.0, 44100, 10000);
+        let mut whole = Resampler::new(44100, 48000);
+        let mut expected = whole.process(&input);
+        expected.extend(whole.finish());
+        assert_eq!(expected.len() as u64, whole.output_length(10000));
+
+        let mut chunked = Resampler::new(44100, 48000);
+        let mut output: Vec<f32> = input.chunks(300).flat_map(|c| chunked.process(c)).collect();
+        output.extend(chunked.finish());
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn output_is_aligned_with_input() {
+        // An impulse at input sample 441 (10 ms) peaks at output sample 480
+        let mut input = vec![0.0; 2000];
+        input[441] = 1.0;
+        let mut resampler = Resampler::new(44100, 48000);
+        let mut output = resampler.process(&input);
+        output.extend(resampler.finish());
+
+        let peak = output
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap()
+            .0;
+        assert_eq!(peak, 480);
+    }
+
+    struct TestSource {
+        samples: Vec<f32>,
+        sample_rate: u32,
+    }
+
+    impl AudioSource for TestSource {
+        fn get_sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn get_duration(&self) -> Duration {
+            Duration::from_secs_f64(self.samples.len() as f64 / self.sample_rate as f64)
+        }
+
+        fn get_length(&self) -> u64 {
+            self.samples.len() as u64
+        }
+
+        fn start_streaming(&mut self, sender: Sender<Vec<f32>>, chunk_size: usize) -> Result<(), anyhow::Error> {
+            for chunk in self.samples.chunks(chunk_size) {
+                if sender.send(chunk.to_vec()).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn resampled_source_streams_at_output_rate() {
+        let samples = sine(1000.0, 44100, 44100);
+        let source = TestSource { samples: samples.clone(), sample_rate: 44100 };
+        let mut resampled = ResampledSource::new(source, 48000);
+        assert_eq!(resampled.get_sample_rate(), 48000);
+        assert_eq!(resampled.get_length(), 48000);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        resampled.start_streaming(tx, 256).unwrap();
+        let chunks: Vec<Vec<f32>> = rx.iter().collect();
+        assert!(chunks[..chunks.len() - 1].iter().all(|c| c.len() == 256));
+
+        let streamed: Vec<f32> = chunks.concat();
+        let mut resampler = Resampler::new(44100, 48000);
+        let mut expected = resampler.process(&samples);
+        expected.extend(resampler.finish());
+        assert_eq!(streamed, expected);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{AudioSource, WavFileSource};
+use fft_lib::midi::{encode_midi, Note};
+use fft_lib::transcription::transcribe;
+
+/// Transcribes the notes of a WAV file and writes them to a Standard MIDI File.
+/// The channels are mixed to mono before the analysis.
+/// # Arguments
+/// * `input_path` - The WAV file to transcribe.
+/// * `output_path` - Where to write the `.mid` file.
+/// # Returns
+/// The transcribed notes, in the order they are written.
+/// # How to use:
+/// ```ignore
+/// let notes = transcribe_wav_file("path/to/recording.wav", "path/to/recording.mid")?;
+/// println!("Found {} notes", notes.len());
+/// ```
+pub fn transcribe_wav_file(input_path: &str, output_path: &str) -> Result<Vec<Note>, anyhow::Error> {
+    let mut source = WavFileSource::new(input_path)
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", input_path, e))?;
+    let sample_rate = source.get_sample_rate();
+
+    let mono: Vec<f64> = source.read_mono()?.iter().map(|&s| s as f64).collect();
+
+    let notes = transcribe(&mono, sample_rate);
+    std::fs::write(output_path, encode_midi(&notes))
+        .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", output_path, e))?;
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::write_wav;
+    use fft_lib::midi::decode_midi;
+    use fft_lib::transcription::synthesize;
+
+    #[test]
+    fn synthesized_midi_round_trips_through_a_wav_file() {
+        let directory = std::env::temp_dir();
+        let input_path = directory.join("transcription_test_input.wav");
+        let output_path = directory.join("transcription_test_output.mid");
+
+        // A melody over a held bass note, rendered from MIDI to a stereo WAV file
+        let note = |pitch, onset, offset, velocity| Note { pitch, onset, offset, velocity };
+        let expected = decode_midi(&encode_midi(&[
+            note(48, 0.1, 1.3, 80),
+            note(67, 0.1, 0.5, 95),
+            note(69, 0.5, 0.9, 95),
+            note(71, 0.9, 1.3, 95),
+        ]))
+        .unwrap();
+        let samples = synthesize(&expected, 22050);
+        write_wav(&input_path, 22050, &[samples.clone(), samples]);
+
+        let notes = transcribe_wav_file(input_path.to_str().unwrap(), output_path.to_str().unwrap()).unwrap();
+        let written = decode_midi(&std::fs::read(&output_path).unwrap()).unwrap();
+        assert_eq!(written.len(), notes.len());
+
+        assert_eq!(written.len(), expected.len(), "found {:?}", written);
+        for expected in &expected {
+            let found = written
+                .iter()
+                .find(|n| n.pitch == expected.pitch && (n.onset - expected.onset).abs() < 0.03)
+                .unwrap_or_else(|| panic!("{:?} not found in {:?}", expected, written));
+            assert!((found.offset - expected.offset).abs() < 0.08, "{:?}", found);
+            assert!(found.velocity.abs_diff(expected.velocity) <= 8, "{:?}", found);
+        }
+
+        let _ = std::fs::remove_file(input_path);
+        let _ = std::fs::remove_file(output_path);
+    }
+}
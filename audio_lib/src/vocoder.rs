@@ -0,0 +1,236 @@
+use crate::channels::{split_channels, ChannelLayout};
+use crate::resample::{send_chunks, send_remainder};
+use crate::{stream_source, write_wav_channels, AudioSource, WavFileSource};
+use fft_lib::vocoder::{PhaseLocking, PhaseVocoder};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+/// Length of the vocoder frames, about 2048 samples at 44.1 kHz.
+const FRAME_DURATION: f64 = 0.046;
+
+/// Audio source that changes the speed and the pitch of another source independently,
+/// using a phase vocoder.
+/// # How to use:
+/// ```ignore
+/// // Half speed for transcribing, same pitch
+/// let wav_source = WavFileSource::new("path/to/audio.wav")
+///    .map_err(|e| anyhow::anyhow!("Failed to create WavFileSource: {}", e))?;
+/// let slowed = VocodedSource::new(wav_source, 2.0, 1.0);
+/// let mut streamer = AudioStreamer::new(slowed, 128_usize);
+/// ```
+pub struct VocodedSource<T: AudioSource + Send> {
+    source: T,
+    stretch: f64,
+    pitch: f64,
+}
+
+impl<T: AudioSource + Send> VocodedSource<T> {
+    /// Creates a new VocodedSource instance.
+    /// # Arguments
+    /// * `source` - The audio source to process.
+    /// * `stretch` - The factor the duration is multiplied by, e.g. 2.0 for half speed.
+    /// * `pitch` - The factor the frequencies are multiplied by, e.g. 2.0 for an octave up.
+    /// # Returns
+    /// A new `VocodedSource` instance.
+    pub fn new(source: T, stretch: f64, pitch: f64) -> Self {
+        assert!(stretch > 0.0 && pitch > 0.0, "Stretch and pitch factors must be positive.");
+        VocodedSource { source, stretch, pitch }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.source
+    }
+
+    fn vocoder(&self) -> PhaseVocoder {
+        new_vocoder(self.source.get_sample_rate(), self.stretch, self.pitch)
+    }
+}
+
+impl<T: AudioSource + Send> AudioSource for VocodedSource<T> {
+    fn get_sample_rate(&self) -> u32 {
+        self.source.get_sample_rate()
+    }
+
+    fn get_duration(&self) -> Duration {
+        if self.source.get_length() == u64::MAX {
+            return self.source.get_duration(); // Indefinite stays indefinite
+        }
+        self.source.get_duration().mul_f64(self.stretch)
+    }
+
+    fn get_length(&self) -> u64 {
+        let length = self.source.get_length();
+        if length == u64::MAX {
+            return u64::MAX; // Indefinite stays indefinite
+        }
+        self.vocoder().output_length(length as usize) as u64
+    }
+
+    fn get_channels(&self) -> u16 {
+        self.source.get_channels()
+    }
+
+    fn get_layout(&self) -> ChannelLayout {
+        self.source.get_layout()
+    }
+
+    fn start_streaming(&mut self, sender: Sender<Vec<f32>>, chunk_size: usize) -> Result<(), anyhow::Error> {
+        let (channels, layout) = (self.source.get_channels(), self.source.get_layout());
+        // Each channel is processed on its own
+        let mut vocoders: Vec<PhaseVocoder> = (0..channels).map(|_| self.vocoder()).collect();
+        let mut pending: Vec<Vec<f32>> = vec![Vec::with_capacity(chunk_size * 2); channels as usize];
+        let finished = stream_source(&mut self.source, chunk_size, |chunk| {
+            for ((vocoder, pending), samples) in vocoders.iter_mut().zip(pending.iter_mut()).zip(split_channels(&chunk, channels, layout)) {
+                let input: Vec<f64> = samples.iter().map(|&s| s as f64).collect();
+                pending.extend(vocoder.process(&input).iter().map(|&s| s as f32));
+            }
+            send_chunks(&sender, &mut pending, chunk_size, layout)
+        })?;
+
+        if finished {
+            for (vocoder, pending) in vocoders.iter_mut().zip(pending.iter_mut()) {
+                pending.extend(vocoder.finish().iter().map(|&s| s as f32));
+            }
+            if send_chunks(&sender, &mut pending, chunk_size, layout) {
+                send_remainder(&sender, &pending, layout);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Changes the speed and the pitch of a WAV file and writes the result as a new 16-bit WAV
+/// file with the same sample rate and channels. Each channel is processed separately.
+/// # Arguments
+/// * `input_path` - The WAV file to read.
+/// * `output_path` - Where to write the result.
+/// * `stretch` - The factor the duration is multiplied by, e.g. 2.0 for half speed.
+/// * `pitch` - The factor the frequencies are multiplied by, e.g. 2.0 for an octave up.
+pub fn vocode_wav_file(input_path: &str, output_path: &str, stretch: f64, pitch: f64) -> Result<(), anyhow::Error> {
+    let mut source = WavFileSource::new(input_path)
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", input_path, e))?;
+    let sample_rate = source.get_sample_rate();
+
+    let processed: Vec<Vec<f64>> = source
+        .read_all_channels()?
+        .iter()
+        .map(|channel| {
+            let input: Vec<f64> = channel.iter().map(|&s| s as f64).collect();
+            let mut vocoder = new_vocoder(sample_rate, stretch, pitch);
+            let mut output = vocoder.process(&input);
+            output.extend(vocoder.finish());
+            output
+        })
+        .collect();
+
+    write_wav_channels(output_path, sample_rate, &processed)
+}
+
+fn new_vocoder(sample_rate: u32, stretch: f64, pitch: f64) -> PhaseVocoder {
+    let frame_size = ((sample_rate as f64 * FRAME_DURATION) as usize).next_power_of_two();
+    PhaseVocoder::new(frame_size, stretch, pitch, PhaseLocking::Identity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{sine, write_wav};
+
+    struct TestSource {
+        samples: Vec<f32>,
+        sample_rate: u32,
+        indefinite: bool,
+    }
+
+    impl AudioSource for TestSource {
+        fn get_sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        fn get_duration(&self) -> Duration {
+            if self.indefinite {
+                return Duration::from_secs(u64::MAX); // Like a microphone
+            }
+            Duration::from_secs_f64(self.samples.len() as f64 / self.sample_rate as f64)
+        }
+
+        fn get_length(&self) -> u64 {
+            if self.indefinite {
+                return u64::MAX;
+            }
+            self.samples.len() as u64
+        }
+
+        fn start_streaming(&mut self, sender: Sender<Vec<f32>>, chunk_size: usize) -> Result<(), anyhow::Error> {
+            for chunk in self.samples.chunks(chunk_size) {
+                if sender.send(chunk.to_vec()).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// The frequency from the number of upward zero crossings.
+    fn measure_frequency(samples: &[f32], sample_rate: u32) -> f64 {
+        let crossings: Vec<usize> = (1..samples.len()).filter(|&i| samples[i - 1] < 0.0 && samples[i] >= 0.0).collect();
+        let periods = (crossings.len() - 1) as f64;
+        periods * sample_rate as f64 / (crossings[crossings.len() - 1] - crossings[0]) as f64
+    }
+
+    #[test]
+    fn vocoded_source_streams_stretched_audio() {
+        let samples = sine(440.0, 16000, 16000);
+        let source = TestSource { samples, sample_rate: 16000, indefinite: false };
+        let mut slowed = VocodedSource::new(source, 1.5, 1.0);
+        assert_eq!(slowed.get_sample_rate(), 16000);
+        assert_eq!(slowed.get_length(), 24000);
+        assert_eq!(slowed.get_duration(), Duration::from_secs_f64(1.5));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        slowed.start_streaming(tx, 256).unwrap();
+        let chunks: Vec<Vec<f32>> = rx.iter().collect();
+        assert!(chunks[..chunks.len() - 1].iter().all(|c| c.len() == 256));
+
+        let streamed: Vec<f32> = chunks.concat();
+        assert_eq!(streamed.len(), 24000);
+        let frequency = measure_frequency(&streamed[4000..20000], 16000);
+        assert!((frequency - 440.0).abs() < 1.0, "measured {} Hz", frequency);
+    }
+
+    #[test]
+    fn indefinite_source_stays_indefinite() {
+        let source = TestSource { samples: Vec::new(), sample_rate: 16000, indefinite: true };
+        let slowed = VocodedSource::new(source, 2.0, 1.0);
+        assert_eq!(slowed.get_length(), u64::MAX);
+        assert_eq!(slowed.get_duration(), Duration::from_secs(u64::MAX));
+    }
+
+    #[test]
+    fn wav_file_is_pitch_shifted_per_channel() {
+        let directory = std::env::temp_dir();
+        let input_path = directory.join("vocoder_test_input.wav");
+        let output_path = directory.join("vocoder_test_output.wav");
+
+        // 300 Hz on the left, 500 Hz on the right
+        let channels: Vec<Vec<f64>> = [300.0, 500.0]
+            .iter()
+            .map(|&frequency| sine(frequency, 16000, 16000).iter().map(|&s| s as f64).collect())
+            .collect();
+        write_wav(&input_path, 16000, &channels);
+
+        vocode_wav_file(input_path.to_str().unwrap(), output_path.to_str().unwrap(), 1.0, 1.5).unwrap();
+
+        let mut output = WavFileSource::new(output_path.to_str().unwrap()).unwrap();
+        assert_eq!(output.spec.channels, 2);
+        let samples = output.read_all_samples().unwrap();
+        assert_eq!(samples.len(), 32000);
+        let left: Vec<f32> = samples.iter().step_by(2).copied().collect();
+        let right: Vec<f32> = samples.iter().skip(1).step_by(2).copied().collect();
+        assert!((measure_frequency(&left[2000..14000], 16000) - 450.0).abs() < 1.5);
+        assert!((measure_frequency(&right[2000..14000], 16000) - 750.0).abs() < 1.5);
+
+        let _ = std::fs::remove_file(input_path);
+        let _ = std::fs::remove_file(output_path);
+    }
+}
@@ -0,0 +1,263 @@
+//! Averaging of successive spectra, to steady the flickering of single frames.
+
+use alloc::collections::VecDeque;
+use alloc::{vec, vec::Vec};
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::Frequencies;
+
+/// How successive frames are combined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AveragingMode {
+    /// The mean power of the last `frames` frames.
+    Linear { frames: usize },
+    /// Exponentially weighted mean power, where older frames fade out with the time
+    /// constant (in seconds).
+    Exponential { time_constant: f64 },
+    /// The largest amplitude seen in each bin since the last reset.
+    PeakHold,
+    /// The smallest amplitude seen in each bin since the last reset.
+    MinHold,
+}
+
+/// Combines successive `Frequencies` frames into an averaged spectrum.
+///
+/// Linear and exponential averaging work on power (squared amplitude), so the average of
+/// two frames is their RMS amplitude. The result always has the layout of the latest
+/// frame; if the number of bins or the sample rate changes the averager starts over.
+///
+/// # How to use:
+/// ```ignore
+/// let mut averager = SpectrumAverager::new(AveragingMode::Exponential { time_constant: 0.1 });
+/// for chunk in chunks {
+///     let frame = get_frequencies(&fft(&chunk), sample_rate);
+///     let averaged = averager.process(&frame);
+///     draw(&averaged.amplitudes);
+/// }
+/// ```
+pub struct SpectrumAverager {
+    mode: AveragingMode,
+    /// Powers for linear and exponential averaging, amplitudes for the holds.
+    state: Vec<f64>,
+    /// The powers of the frames in the linear window.
+    history: VecDeque<Vec<f64>>,
+    frame_count: usize,
+    last_frame: Option<Frequencies>,
+}
+
+impl SpectrumAverager {
+    /// Creates a new averager.
+    ///
+    /// # Panics
+    ///
+    /// If a linear average covers 0 frames or the time constant is not positive.
+    pub fn new(mode: AveragingMode) -> Self {
+        match mode {
+            AveragingMode::Linear { frames } => {
+                assert!(frames > 0, "A linear average needs at least one frame.")
+            }
+            AveragingMode::Exponential { time_constant } => {
+                assert!(time_constant > 0.0, "The time constant must be positive.")
+            }
+            AveragingMode::PeakHold | AveragingMode::MinHold => {}
+        }
+
+        Self {
+            mode,
+            state: Vec::new(),
+            history: VecDeque::new(),
+            frame_count: 0,
+            last_frame: None,
+        }
+    }
+
+    pub fn mode(&self) -> AveragingMode {
+        self.mode
+    }
+
+    /// The number of frames combined since the last reset.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Adds a frame to the average.
+    ///
+    /// For exponential averaging the time between frames is the difference between their
+    /// start times, or the frame duration (`total_samples / sample_rate`) when the start
+    /// times do not increase, as for consecutive frames from `get_frequencies`.
+    ///
+    /// # Returns
+    ///
+    /// The averaged spectrum, including the new frame.
+    pub fn process(&mut self, frame: &Frequencies) -> Frequencies {
+        let layout_changed = self.last_frame.as_ref().is_some_and(|last| {
+            last.amplitudes.len() != frame.amplitudes.len() || last.sample_rate != frame.sample_rate
+        });
+        if layout_changed {
+            self.reset();
+        }
+
+        let interval = self.frame_interval(frame);
+        let powers = frame.amplitudes.iter().map(|a| a * a);
+
+        if self.frame_count == 0 {
+            self.state = match self.mode {
+                AveragingMode::PeakHold | AveragingMode::MinHold => frame.amplitudes.clone(),
+                _ => powers.clone().collect(),
+            };
+        } else {
+            match self.mode {
+                AveragingMode::Linear { .. } => {}
+                AveragingMode::Exponential { time_constant } => {
+                    let decay = (-interval / time_constant).exp();
+                    for (state, power) in self.state.iter_mut().zip(powers.clone()) {
+                        *state = decay * *state + (1.0 - decay) * power;
+                    }
+                }
+                AveragingMode::PeakHold => {
+                    for (state, amplitude) in self.state.iter_mut().zip(&frame.amplitudes) {
+                        *state = state.max(*amplitude);
+                    }
+                }
+                AveragingMode::MinHold => {
+                    for (state, amplitude) in self.state.iter_mut().zip(&frame.amplitudes) {
+                        *state = state.min(*amplitude);
+                    }
+                }
+            }
+        }
+
+        if let AveragingMode::Linear { frames } = self.mode {
+            self.history.push_back(powers.collect());
+            if self.history.len() > frames {
+                self.history.pop_front();
+            }
+            // Summed again every frame, so no rounding error builds up in a running sum
+            self.state = vec![0.0; frame.amplitudes.len()];
+            for powers in &self.history {
+                for (state, power) in self.state.iter_mut().zip(powers) {
+                    *state += power;
+                }
+            }
+            let count = self.history.len() as f64;
+            self.state.iter_mut().for_each(|s| *s /= count);
+        }
+
+        self.frame_count += 1;
+        self.last_frame = Some(frame.clone());
+        self.current().unwrap()
+    }
+
+    /// The averaged spectrum, or `None` before the first frame.
+    pub fn current(&self) -> Option<Frequencies> {
+        let last = self.last_frame.as_ref()?;
+        let amplitudes = match self.mode {
+            AveragingMode::PeakHold | AveragingMode::MinHold => self.state.clone(),
+            _ => self.state.iter().map(|p| p.sqrt()).collect(),
+        };
+        Some(Frequencies {
+            amplitudes,
+            ..last.clone()
+        })
+    }
+
+    /// Forgets all frames, e.g. to restart a peak hold.
+    pub fn reset(&mut self) {
+        self.state.clear();
+        self.history.clear();
+        self.frame_count = 0;
+        self.last_frame = None;
+    }
+
+    fn frame_interval(&self, frame: &Frequencies) -> f64 {
+        let since_last = self
+            .last_frame
+            .as_ref()
+            .map(|last| frame.start_time - last.start_time)
+            .unwrap_or(0.0);
+        if since_last > 0.0 {
+            since_last
+        } else {
+            frame.total_samples as f64 / frame.sample_rate as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    fn frame(amplitudes: &[f64], start_time: f64) -> Frequencies {
+        Frequencies {
+            frequencies: (0..amplitudes.len()).map(|i| i as f64 * 10.0).collect(),
+            amplitudes: amplitudes.to_vec(),
+            total_samples: amplitudes.len() * 2,
+            sample_rate: 100,
+            start_time,
+        }
+    }
+
+    #[test]
+    fn linear_averages_power_over_window() {
+        let mut averager = SpectrumAverager::new(AveragingMode::Linear { frames: 2 });
+        averager.process(&frame(&[3.0, 0.0], 0.0));
+        let averaged = averager.process(&frame(&[4.0, 2.0], 0.0));
+        assert_float_absolute_eq!(averaged.amplitudes[0], 12.5f64.sqrt(), 1e-12);
+        assert_float_absolute_eq!(averaged.amplitudes[1], 2.0f64.sqrt(), 1e-12);
+
+        // The first frame has left the window
+        let averaged = averager.process(&frame(&[4.0, 2.0], 0.0));
+        assert_float_absolute_eq!(averaged.amplitudes[0], 4.0, 1e-12);
+        assert_eq!(averager.frame_count(), 3);
+    }
+
+    #[test]
+    fn exponential_follows_time_constant() {
+        let mut averager = SpectrumAverager::new(AveragingMode::Exponential { time_constant: 0.5 });
+        averager.process(&frame(&[0.0], 0.0));
+
+        // Frames of 2 samples at 100 Hz are 20 ms apart, so 25 frames make one time constant
+        let mut averaged = averager.current().unwrap();
+        for _ in 0..25 {
+            averaged = averager.process(&frame(&[1.0], 0.0));
+        }
+        let power = averaged.amplitudes[0].powi(2);
+        assert_float_absolute_eq!(power, 1.0 - (-1.0f64).exp(), 1e-9);
+
+        // Start times take precedence over the frame duration
+        let mut averager = SpectrumAverager::new(AveragingMode::Exponential { time_constant: 0.5 });
+        averager.process(&frame(&[0.0], 1.0));
+        let averaged = averager.process(&frame(&[1.0], 1.5));
+        assert_float_absolute_eq!(averaged.amplitudes[0].powi(2), 1.0 - (-1.0f64).exp(), 1e-9);
+    }
+
+    #[test]
+    fn holds_keep_extremes_until_reset() {
+        let mut peak = SpectrumAverager::new(AveragingMode::PeakHold);
+        let mut min = SpectrumAverager::new(AveragingMode::MinHold);
+        for amplitudes in [[1.0, 5.0], [3.0, 2.0], [2.0, 4.0]] {
+            peak.process(&frame(&amplitudes, 0.0));
+            min.process(&frame(&amplitudes, 0.0));
+        }
+        assert_eq!(peak.current().unwrap().amplitudes, vec![3.0, 5.0]);
+        assert_eq!(min.current().unwrap().amplitudes, vec![1.0, 2.0]);
+
+        peak.reset();
+        assert!(peak.current().is_none());
+        assert_eq!(
+            peak.process(&frame(&[0.5, 0.5], 0.0)).amplitudes,
+            vec![0.5, 0.5]
+        );
+    }
+
+    #[test]
+    fn layout_change_starts_over() {
+        let mut averager = SpectrumAverager::new(AveragingMode::PeakHold);
+        averager.process(&frame(&[9.0, 9.0], 0.0));
+        let averaged = averager.process(&frame(&[1.0, 1.0, 1.0], 0.0));
+        assert_eq!(averaged.amplitudes, vec![1.0, 1.0, 1.0]);
+        assert_eq!(averager.frame_count(), 1);
+    }
+}
@@ -0,0 +1,350 @@
+//! Tempo estimation and beat tracking on top of the onset novelty curve.
+//!
+//! The tempo is the autocorrelation peak of the novelty curve, weighted towards common
+//! tempos. Beats are then placed with dynamic programming (Ellis, 2007): every beat should
+//! fall on high novelty while consecutive beats stay close to the estimated period.
+
+use crate::onset::{NoveltyDetector, NoveltyMethod};
+
+/// Slowest tempo considered, in beats per minute.
+pub const MIN_BPM: f64 = 40.0;
+/// Fastest tempo considered, in beats per minute.
+pub const MAX_BPM: f64 = 240.0;
+
+/// Center of the tempo preference applied to the autocorrelation.
+const PREFERRED_BPM: f64 = 120.0;
+/// Width of the tempo preference in octaves.
+const PREFERENCE_WIDTH: f64 = 1.0;
+/// How strongly the beat tracker penalizes intervals that deviate from the period.
+const TIGHTNESS: f64 = 100.0;
+
+/// The tempo and beat times of a signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatTrack {
+    /// The estimated tempo in beats per minute.
+    pub tempo: f64,
+    /// The beat times in seconds, in ascending order.
+    pub beats: Vec<f64>,
+}
+
+/// Estimates the tempo of a novelty curve.
+///
+/// # Arguments
+///
+/// * `novelty` - The novelty curve, e.g. from `onset::novelty_curve`.
+/// * `frame_rate` - The number of novelty values per second.
+///
+/// # Returns
+///
+/// The tempo in beats per minute, or `None` if the curve is too short or flat.
+pub fn estimate_tempo(novelty: &[f64], frame_rate: f64) -> Option<f64> {
+    let min_lag = ((60.0 * frame_rate / MAX_BPM).floor() as usize).max(1);
+    let max_lag = (60.0 * frame_rate / MIN_BPM).ceil() as usize;
+    if novelty.len() <= min_lag + 1 {
+        return None;
+    }
+    let max_lag = max_lag.min(novelty.len() - 2);
+
+    let mean = novelty.iter().sum::<f64>() / novelty.len() as f64;
+    let centered: Vec<f64> = novelty.iter().map(|v| v - mean).collect();
+    let autocorrelation = |lag: usize| -> f64 {
+        centered[..centered.len() - lag]
+            .iter()
+            .zip(centered[lag..].iter())
+            .map(|(a, b)| a * b)
+            .sum::<f64>()
+    };
+
+    let preferred_lag = 60.0 * frame_rate / PREFERRED_BPM;
+    let weighted = |lag: usize| -> f64 {
+        let octaves = (lag as f64 / preferred_lag).log2() / PREFERENCE_WIDTH;
+        autocorrelation(lag) * (-0.5 * octaves * octaves).exp()
+    };
+
+    let scores: Vec<f64> = (min_lag..=max_lag).map(weighted).collect();
+    let (best, &best_score) = scores
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+    if best_score <= 0.0 {
+        return None;
+    }
+
+    // Refine the lag with a parabola through the peak and its neighbours
+    let mut lag = (min_lag + best) as f64;
+    if best > 0 && best + 1 < scores.len() {
+        let (left, right) = (scores[best - 1], scores[best + 1]);
+        let denominator = left - 2.0 * best_score + right;
+        if denominator < 0.0 {
+            lag += 0.5 * (left - right) / denominator;
+        }
+    }
+
+    Some(60.0 * frame_rate / lag)
+}
+
+/// Places beats on a novelty curve with the given tempo.
+///
+/// # Arguments
+///
+/// * `novelty` - The novelty curve.
+/// * `frame_rate` - The number of novelty values per second.
+/// * `tempo` - The tempo in beats per minute.
+///
+/// # Returns
+///
+/// The indices of the beat frames, in ascending order.
+pub fn track_beat_frames(novelty: &[f64], frame_rate: f64, tempo: f64) -> Vec<usize> {
+    if novelty.is_empty() {
+        return Vec::new();
+    }
+
+    // Normalize so the tightness weighs the same regardless of the signal level
+    let mean = novelty.iter().sum::<f64>() / novelty.len() as f64;
+    let deviation =
+        (novelty.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / novelty.len() as f64).sqrt();
+    let scale = if deviation > 0.0 { deviation } else { 1.0 };
+    let local: Vec<f64> = novelty.iter().map(|v| v / scale).collect();
+
+    let period = 60.0 * frame_rate / tempo;
+    let mut score = vec![0.0; local.len()];
+    let mut backlink: Vec<Option<usize>> = vec![None; local.len()];
+
+    for t in 0..local.len() {
+        let earliest = t as f64 - 2.0 * period;
+        let latest = t as f64 - period / 2.0;
+        let mut best: Option<(usize, f64)> = None;
+
+        if latest >= 0.0 {
+            let first = earliest.max(0.0).round() as usize;
+            let last = latest.round() as usize;
+            for (previous, previous_score) in score.iter().enumerate().take(last + 1).skip(first) {
+                let interval = (t - previous) as f64 / period;
+                let candidate = previous_score - TIGHTNESS * interval.ln().powi(2);
+                if best.is_none_or(|(_, s)| candidate > s) {
+                    best = Some((previous, candidate));
+                }
+            }
+        }
+
+        score[t] = local[t] + best.map_or(0.0, |(_, s)| s.max(0.0));
+        backlink[t] = best.filter(|(_, s)| *s > 0.0).map(|(p, _)| p);
+    }
+
+    // The last beat is the best-scoring frame within the final period
+    let tail_start = local.len().saturating_sub(period.round().max(1.0) as usize);
+    let mut beat = (tail_start..local.len()).max_by(|&a, &b| score[a].total_cmp(&score[b]));
+
+    let mut beats = Vec::new();
+    while let Some(frame) = beat {
+        beats.push(frame);
+        beat = backlink[frame];
+    }
+    beats.reverse();
+    beats
+}
+
+/// Estimates the tempo and beat times of a complete signal, e.g. a whole WAV file.
+///
+/// # Arguments
+///
+/// * `samples` - The samples of the signal.
+/// * `sample_rate` - The sample rate of the signal.
+///
+/// # Returns
+///
+/// The beat track, or `None` if no tempo could be found.
+pub fn track_beats(samples: &[f64], sample_rate: u32) -> Option<BeatTrack> {
+    let mut detector = NoveltyDetector::new(sample_rate, NoveltyMethod::SpectralFlux);
+    let novelty = detector.process(samples);
+    let tempo = estimate_tempo(&novelty, detector.frame_rate())?;
+    let beats = track_beat_frames(&novelty, detector.frame_rate(), tempo)
+        .into_iter()
+        .map(|frame| detector.frame_time(frame))
+        .collect();
+
+    Some(BeatTrack { tempo, beats })
+}
+
+/// Tracks beats in a live stream.
+///
+/// The tracker keeps the most recent `HISTORY_SECONDS` of novelty, re-estimates the tempo
+/// and the beat grid every `UPDATE_SECONDS`, and reports beats once they are at least
+/// `LATENCY_SECONDS` old, when later novelty can no longer move them.
+///
+/// # How to use:
+/// ```ignore
+/// let mut tracker = BeatTracker::new(sample_rate);
+///
+/// while let Ok(chunk) = audio_rx.recv() {
+///     let chunk: Vec<f64> = chunk.into_iter().map(|s| s as f64).collect();
+///     for time in tracker.process(&chunk) {
+///         println!("Beat at {:.3} s ({:.1} BPM)", time, tracker.tempo().unwrap_or(0.0));
+///     }
+/// }
+/// ```
+pub struct BeatTracker {
+    novelty: NoveltyDetector,
+    history: Vec<f64>,
+    history_start: usize,
+    frames_since_update: usize,
+    tempo: Option<f64>,
+    last_beat: Option<f64>,
+}
+
+impl BeatTracker {
+    const HISTORY_SECONDS: f64 = 8.0;
+    const UPDATE_SECONDS: f64 = 1.0;
+    const LATENCY_SECONDS: f64 = 2.0;
+
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            novelty: NoveltyDetector::new(sample_rate, NoveltyMethod::SpectralFlux),
+            history: Vec::new(),
+            history_start: 0,
+            frames_since_update: 0,
+            tempo: None,
+            last_beat: None,
+        }
+    }
+
+    /// The most recent tempo estimate in beats per minute.
+    pub fn tempo(&self) -> Option<f64> {
+        self.tempo
+    }
+
+    /// Feeds a chunk of samples and returns the times (in seconds) of newly confirmed beats.
+    pub fn process(&mut self, chunk: &[f64]) -> Vec<f64> {
+        let frame_rate = self.novelty.frame_rate();
+        let history_length = (Self::HISTORY_SECONDS * frame_rate) as usize;
+        let update_interval = (Self::UPDATE_SECONDS * frame_rate) as usize;
+
+        let mut beats = Vec::new();
+        for value in self.novelty.process(chunk) {
+            self.history.push(value);
+            if self.history.len() > history_length {
+                let excess = self.history.len() - history_length;
+                self.history.drain(..excess);
+                self.history_start += excess;
+            }
+
+            self.frames_since_update += 1;
+            if self.frames_since_update >= update_interval {
+                self.frames_since_update = 0;
+                let head = self
+                    .novelty
+                    .frame_time(self.history_start + self.history.len());
+                beats.extend(self.update(head - Self::LATENCY_SECONDS));
+            }
+        }
+        beats
+    }
+
+    /// Ends the stream and returns the beats that were still waiting for confirmation.
+    pub fn finish(&mut self) -> Vec<f64> {
+        self.update(f64::INFINITY)
+    }
+
+    /// Re-estimates the beat grid and returns the unreported beats up to `confirm_until`.
+    fn update(&mut self, confirm_until: f64) -> Vec<f64> {
+        let frame_rate = self.novelty.frame_rate();
+        let Some(tempo) = estimate_tempo(&self.history, frame_rate) else {
+            return Vec::new();
+        };
+        self.tempo = Some(tempo);
+
+        let min_spacing = 0.5 * 60.0 / tempo;
+        let mut beats = Vec::new();
+        for frame in track_beat_frames(&self.history, frame_rate, tempo) {
+            let time = self.novelty.frame_time(self.history_start + frame);
+            if time > confirm_until {
+                break;
+            }
+            if self.last_beat.is_none_or(|last| time - last > min_spacing) {
+                beats.push(time);
+                self.last_beat = Some(time);
+            }
+        }
+        beats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::onset::tests::clicks;
+
+    fn click_track(bpm: f64, offset: f64, duration: f64) -> Vec<f64> {
+        let period = 60.0 / bpm;
+        (0..)
+            .map(|i| offset + i as f64 * period)
+            .take_while(|&t| t < duration - 0.05)
+            .collect()
+    }
+
+    fn assert_beats_on_grid(beats: &[f64], bpm: f64, offset: f64) {
+        let period = 60.0 / bpm;
+        for beat in beats {
+            let phase = ((beat - offset) / period).round();
+            let error = beat - offset - phase * period;
+            assert!(
+                error.abs() < 0.03,
+                "beat at {} is off the grid by {}",
+                beat,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn tempo_of_click_track() {
+        for bpm in [90.0, 120.0, 150.0] {
+            let samples = clicks(&click_track(bpm, 0.1, 8.0), 8.0, 22050);
+            let track = track_beats(&samples, 22050).expect("no tempo found");
+            assert!(
+                (track.tempo - bpm).abs() < 2.0,
+                "estimated {} BPM instead of {}",
+                track.tempo,
+                bpm
+            );
+        }
+    }
+
+    #[test]
+    fn beats_follow_clicks() {
+        let times = click_track(120.0, 0.3, 8.0);
+        let samples = clicks(&times, 8.0, 22050);
+        let track = track_beats(&samples, 22050).unwrap();
+
+        assert!(
+            track.beats.len() + 2 >= times.len(),
+            "beats {:?}",
+            track.beats
+        );
+        assert_beats_on_grid(&track.beats, 120.0, 0.3);
+    }
+
+    #[test]
+    fn streaming_tracker_reports_beats() {
+        let times = click_track(100.0, 0.2, 12.0);
+        let samples = clicks(&times, 12.0, 22050);
+
+        let mut tracker = BeatTracker::new(22050);
+        let mut beats: Vec<f64> = samples
+            .chunks(512)
+            .flat_map(|chunk| tracker.process(chunk))
+            .collect();
+        beats.extend(tracker.finish());
+
+        assert!((tracker.tempo().unwrap() - 100.0).abs() < 2.0);
+        assert!(beats.len() + 3 >= times.len(), "beats {:?}", beats);
+        assert!(beats.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_beats_on_grid(&beats, 100.0, 0.2);
+    }
+
+    #[test]
+    fn flat_curve_has_no_tempo() {
+        assert_eq!(estimate_tempo(&[0.0; 1000], 86.0), None);
+        assert_eq!(estimate_tempo(&[1.0; 3], 86.0), None);
+    }
+}
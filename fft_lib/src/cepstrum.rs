@@ -0,0 +1,376 @@
+//! Cepstral analysis: the spectrum of the log spectrum.
+//!
+//! Echoes and the harmonics of a pitched sound are periodic ripples in the log spectrum, so
+//! they show up as peaks in the cepstrum at the delay or the pitch period. The slowly
+//! varying spectral envelope stays at low quefrencies, where liftering can separate it.
+//!
+//! The definitions follow the textbook ones with an unscaled forward DFT and an inverse
+//! that divides by N, so they do not depend on the scaling used by `fft`.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{fft, get_frequencies, ifft, unwrap_phase, FftResult, Frequencies};
+
+/// Which part of a cepstrum a lifter keeps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lifter {
+    /// Keeps the coefficients below the cutoff (in samples), e.g. the spectral envelope.
+    LowQuefrency(usize),
+    /// Keeps the coefficients from the cutoff (in samples) up, e.g. the pitch harmonics.
+    HighQuefrency(usize),
+}
+
+/// A cepstrum with one coefficient per sample of the analyzed signal.
+///
+/// Coefficient `n` belongs to the quefrency `n / sample_rate` seconds. The upper half holds
+/// the negative quefrencies, like the upper half of a spectrum holds negative frequencies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cepstrum {
+    pub coefficients: Vec<f64>,
+    pub sample_rate: u32,
+}
+
+impl Cepstrum {
+    /// The quefrency of each coefficient in seconds.
+    pub fn quefrencies(&self) -> Vec<f64> {
+        (0..self.coefficients.len())
+            .map(|n| n as f64 / self.sample_rate as f64)
+            .collect()
+    }
+
+    /// Applies a lifter, zeroing the coefficients it does not keep.
+    ///
+    /// The lifter is applied symmetrically to the positive and the negative quefrencies.
+    pub fn lifter(&self, lifter: Lifter) -> Cepstrum {
+        let N = self.coefficients.len();
+        let coefficients = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .map(|(n, &c)| {
+                let quefrency = n.min(N - n);
+                let keep = match lifter {
+                    Lifter::LowQuefrency(cutoff) => quefrency < cutoff,
+                    Lifter::HighQuefrency(cutoff) => quefrency >= cutoff,
+                };
+                if keep {
+                    c
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        Cepstrum {
+            coefficients,
+            sample_rate: self.sample_rate,
+        }
+    }
+
+    /// The natural log magnitude of the spectrum this real cepstrum describes, for every bin.
+    ///
+    /// For a liftered cepstrum this is the smoothed (low quefrency) or flattened (high
+    /// quefrency) log spectrum.
+    pub fn log_spectrum(&self) -> Vec<f64> {
+        // The coefficients are even, so the forward DFT is real
+        let N = self.coefficients.len() as f64;
+        fft(&self.coefficients)
+            .real
+            .iter()
+            .map(|value| value * N)
+            .collect()
+    }
+
+    /// The spectrum this real cepstrum describes, scaled like `get_frequencies`.
+    pub fn spectrum(&self) -> Frequencies {
+        let N = self.coefficients.len() as f64;
+        let magnitudes = FftResult {
+            real: self
+                .log_spectrum()
+                .iter()
+                .map(|log| log.exp() / N)
+                .collect(),
+            imag: vec![0.0; self.coefficients.len()],
+        };
+        get_frequencies(&magnitudes, self.sample_rate)
+    }
+
+    /// Finds the strongest coefficient in a range of quefrencies, e.g. the pitch period or
+    /// the delay of an echo.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_quefrency` - The lowest quefrency to search in seconds.
+    /// * `max_quefrency` - The highest quefrency to search in seconds.
+    ///
+    /// # Returns
+    ///
+    /// The quefrency of the peak in seconds, or `None` if the range holds no coefficients.
+    pub fn peak_quefrency(&self, min_quefrency: f64, max_quefrency: f64) -> Option<f64> {
+        let sample_rate = self.sample_rate as f64;
+        let start = (min_quefrency * sample_rate).ceil().max(1.0) as usize;
+        let end = ((max_quefrency * sample_rate).floor() as usize).min(self.coefficients.len() / 2);
+        (start..=end)
+            .max_by(|&a, &b| self.coefficients[a].total_cmp(&self.coefficients[b]))
+            .map(|n| n as f64 / sample_rate)
+    }
+}
+
+/// A complex cepstrum, which keeps the phase and can be turned back into the signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComplexCepstrum {
+    pub cepstrum: Cepstrum,
+    /// The delay in samples that was removed from the phase after unwrapping it.
+    pub delay: i64,
+}
+
+impl ComplexCepstrum {
+    /// Reconstructs the signal, including the removed delay.
+    pub fn signal(&self) -> Vec<f64> {
+        let coefficients = &self.cepstrum.coefficients;
+        let N = coefficients.len();
+
+        let log_spectrum = fft(coefficients);
+        let mut spectrum = FftResult {
+            real: vec![0.0; N],
+            imag: vec![0.0; N],
+        };
+        for k in 0..N {
+            let magnitude = (log_spectrum.real[k] * N as f64).exp();
+            let phase = log_spectrum.imag[k] * N as f64 - linear_phase(self.delay, k, N);
+            let (sin, cos) = phase.sin_cos();
+            spectrum.real[k] = magnitude * cos;
+            spectrum.imag[k] = magnitude * sin;
+        }
+
+        ifft(&spectrum)
+            .real
+            .iter()
+            .map(|value| value / N as f64)
+            .collect()
+    }
+}
+
+/// Calculates the real cepstrum, the inverse DFT of the log magnitude spectrum.
+///
+/// # Arguments
+///
+/// * `samples` - The signal, its length must be a power of 2.
+/// * `sample_rate` - The sample rate of the signal.
+///
+/// # Returns
+///
+/// A `Cepstrum` with as many coefficients as there are samples.
+pub fn real_cepstrum(samples: &[f64], sample_rate: u32) -> Cepstrum {
+    let log_magnitudes = log_magnitudes(samples);
+    Cepstrum {
+        coefficients: inverse_real(&log_magnitudes),
+        sample_rate,
+    }
+}
+
+/// Calculates the power cepstrum, the squared magnitude of the inverse DFT of the log power
+/// spectrum. It is four times the square of the real cepstrum.
+///
+/// # Arguments
+///
+/// * `samples` - The signal, its length must be a power of 2.
+/// * `sample_rate` - The sample rate of the signal.
+pub fn power_cepstrum(samples: &[f64], sample_rate: u32) -> Cepstrum {
+    let log_powers: Vec<f64> = log_magnitudes(samples).iter().map(|l| 2.0 * l).collect();
+    Cepstrum {
+        coefficients: inverse_real(&log_powers).iter().map(|c| c * c).collect(),
+        sample_rate,
+    }
+}
+
+/// Calculates the complex cepstrum, the inverse DFT of the complex logarithm of the spectrum.
+///
+/// The phase is unwrapped, and then the linear phase of a whole number of samples of delay is
+/// removed so the phase is continuous around the Nyquist frequency.
+///
+/// # Arguments
+///
+/// * `samples` - The signal, its length must be a power of 2.
+/// * `sample_rate` - The sample rate of the signal.
+pub fn complex_cepstrum(samples: &[f64], sample_rate: u32) -> ComplexCepstrum {
+    let N = samples.len();
+    let spectrum = fft(samples);
+    let half = N / 2;
+
+    let wrapped: Vec<f64> = (0..=half)
+        .map(|k| spectrum.imag[k].atan2(spectrum.real[k]))
+        .collect();
+    let mut phase = unwrap_phase(&wrapped);
+    let delay = if N > 1 {
+        -(phase[half] / PI).round() as i64
+    } else {
+        0
+    };
+    for (k, value) in phase.iter_mut().enumerate() {
+        *value += linear_phase(delay, k, N);
+    }
+
+    let magnitudes = log_magnitudes(samples);
+    let mut log_spectrum = FftResult {
+        real: magnitudes,
+        imag: vec![0.0; N],
+    };
+    for k in 0..N {
+        // The upper half mirrors the lower half, conjugated
+        log_spectrum.imag[k] = if k <= half { phase[k] } else { -phase[N - k] };
+    }
+
+    let coefficients = ifft(&log_spectrum)
+        .real
+        .iter()
+        .map(|value| value / N as f64)
+        .collect();
+    ComplexCepstrum {
+        cepstrum: Cepstrum {
+            coefficients,
+            sample_rate,
+        },
+        delay,
+    }
+}
+
+/// The natural log magnitude of the unscaled DFT, for every bin.
+fn log_magnitudes(samples: &[f64]) -> Vec<f64> {
+    let N = samples.len() as f64;
+    let spectrum = fft(samples);
+    spectrum
+        .real
+        .iter()
+        .zip(spectrum.imag.iter())
+        .map(|(re, im)| {
+            // Silent bins would give -inf, which spreads over the whole cepstrum
+            let magnitude = (re * re + im * im).sqrt() * N;
+            magnitude.max(f64::MIN_POSITIVE).ln()
+        })
+        .collect()
+}
+
+/// The inverse DFT, divided by N, of a real and even spectrum.
+fn inverse_real(spectrum: &[f64]) -> Vec<f64> {
+    let N = spectrum.len() as f64;
+    let transformed = ifft(&FftResult {
+        real: spectrum.to_vec(),
+        imag: vec![0.0; spectrum.len()],
+    });
+    transformed.real.iter().map(|value| value / N).collect()
+}
+
+/// The phase that advances bin `k` by `delay` samples, with the upper half of the bins as
+/// negative frequencies.
+fn linear_phase(delay: i64, k: usize, N: usize) -> f64 {
+    let k = if k <= N / 2 {
+        k as f64
+    } else {
+        k as f64 - N as f64
+    };
+    2.0 * PI * delay as f64 * k / N as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::noise;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    const SAMPLE_RATE: u32 = 8000;
+
+    /// 1 - a z^-1, padded to `length`.
+    fn first_order(a: f64, length: usize) -> Vec<f64> {
+        let mut samples = vec![0.0; length];
+        samples[0] = 1.0;
+        samples[1] = -a;
+        samples
+    }
+
+    #[test]
+    fn matches_analytic_cepstra() {
+        // For 1 - a z^-1 with |a| < 1 the complex cepstrum is -a^n / n for n > 0, and the real
+        // cepstrum is its even part
+        let a: f64 = 0.5;
+        let complex = complex_cepstrum(&first_order(a, 256), SAMPLE_RATE);
+        let real = real_cepstrum(&first_order(a, 256), SAMPLE_RATE);
+
+        assert_eq!(complex.delay, 0);
+        assert_float_absolute_eq!(complex.cepstrum.coefficients[0], 0.0, 1e-12);
+        assert_float_absolute_eq!(real.coefficients[0], 0.0, 1e-12);
+        for n in 1..20 {
+            let expected = -a.powi(n as i32) / n as f64;
+            assert_float_absolute_eq!(complex.cepstrum.coefficients[n], expected, 1e-12);
+            assert_float_absolute_eq!(complex.cepstrum.coefficients[256 - n], 0.0, 1e-12);
+            assert_float_absolute_eq!(real.coefficients[n], expected / 2.0, 1e-12);
+            assert_float_absolute_eq!(real.coefficients[256 - n], expected / 2.0, 1e-12);
+        }
+
+        let power = power_cepstrum(&first_order(a, 256), SAMPLE_RATE);
+        for n in 0..256 {
+            assert_float_absolute_eq!(
+                power.coefficients[n],
+                4.0 * real.coefficients[n].powi(2),
+                1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn complex_cepstrum_restores_delayed_signal() {
+        let mut samples = vec![0.0; 128];
+        for (i, sample) in samples[3..40].iter_mut().enumerate() {
+            *sample = (-(i as f64) / 6.0).exp() * (i as f64 * 0.9).cos();
+        }
+        let complex = complex_cepstrum(&samples, SAMPLE_RATE);
+        assert_eq!(complex.delay, 3);
+        let restored = complex.signal();
+
+        for (restored, original) in restored.iter().zip(samples.iter()) {
+            assert_float_absolute_eq!(*restored, *original, 1e-9);
+        }
+        assert_eq!(complex.cepstrum.quefrencies()[8], 0.001);
+    }
+
+    #[test]
+    fn detects_echo_delay() {
+        let direct = noise(7, 4096);
+        let delay = 120;
+        let samples: Vec<f64> = (0..direct.len())
+            .map(|i| {
+                direct[i]
+                    + if i >= delay {
+                        0.6 * direct[i - delay]
+                    } else {
+                        0.0
+                    }
+            })
+            .collect();
+
+        let cepstrum = real_cepstrum(&samples, SAMPLE_RATE);
+        let echo = cepstrum.peak_quefrency(0.002, 0.1).unwrap();
+        assert_float_absolute_eq!(echo, delay as f64 / SAMPLE_RATE as f64, 1e-9);
+    }
+
+    #[test]
+    fn liftering_separates_envelope() {
+        let a = 0.8;
+        let samples = first_order(a, 512);
+        let cepstrum = real_cepstrum(&samples, SAMPLE_RATE);
+
+        // The coefficients of this envelope have decayed below 1e-9 after 80
+        let envelope = cepstrum.lifter(Lifter::LowQuefrency(80)).spectrum();
+        let direct = get_frequencies(&fft(&samples), SAMPLE_RATE);
+        for (smoothed, exact) in envelope.amplitudes.iter().zip(direct.amplitudes.iter()) {
+            assert_float_absolute_eq!(*smoothed, *exact, 1e-9);
+        }
+        assert_eq!(envelope.frequencies, direct.frequencies);
+
+        // What is left above the envelope is flat
+        let fine = cepstrum.lifter(Lifter::HighQuefrency(80)).log_spectrum();
+        assert!(fine.iter().all(|log| log.abs() < 1e-8));
+    }
+}
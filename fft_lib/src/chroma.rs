@@ -0,0 +1,238 @@
+//! Chroma (pitch-class profile) features and tuning estimation.
+//!
+//! A chroma vector folds the spectrum onto the 12 pitch classes of the equal-tempered
+//! scale, index 0 being C. Octave and timbre largely drop out, which is what key and
+//! chord analysis want.
+
+use std::f64::consts::PI;
+
+use crate::log_spectrum::{frequency_to_midi, LogFrequencySpectrum};
+use crate::stft::Stft;
+use crate::window::Window;
+use crate::{get_frequencies, Frequencies};
+
+/// The names of the pitch classes, starting at C.
+pub const PITCH_CLASS_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Lowest frequency included in the chroma, in Hz (just below C2).
+pub const MIN_FREQUENCY: f64 = 60.0;
+/// Highest frequency included in the chroma, in Hz (around C7).
+pub const MAX_FREQUENCY: f64 = 2100.0;
+
+/// Default STFT frame size for chromagrams, long enough to resolve semitones in the bass.
+pub const DEFAULT_FRAME_SIZE: usize = 8192;
+/// Default STFT hop size for chromagrams.
+pub const DEFAULT_HOP_SIZE: usize = 2048;
+
+/// A 12-bin pitch-class profile, index 0 being C.
+pub type Chroma = [f64; 12];
+
+/// Calculates the chroma of a spectrum.
+///
+/// # Arguments
+///
+/// * `frequencies` - The spectrum to fold.
+/// * `tuning` - The tuning offset in semitones relative to A4 = 440 Hz, e.g. from `estimate_tuning`.
+///
+/// # Returns
+///
+/// The power per pitch class, scaled so the strongest pitch class is 1.0
+/// (all zeros for a silent spectrum).
+pub fn chroma(frequencies: &Frequencies, tuning: f64) -> Chroma {
+    let mut result = [0.0; 12];
+    for (f, a) in frequencies
+        .frequencies
+        .iter()
+        .zip(frequencies.amplitudes.iter())
+    {
+        if *f < MIN_FREQUENCY || *f > MAX_FREQUENCY {
+            continue;
+        }
+        result[pitch_class(frequency_to_midi(*f) - tuning)] += a * a;
+    }
+    normalize(result)
+}
+
+/// Calculates the chroma of a log-frequency spectrum.
+///
+/// The spectrum's bins should be centered on (tuned) semitones, i.e. use a
+/// `bins_per_octave` that is a multiple of 12 and a tuned `min_frequency`.
+pub fn chroma_from_log_spectrum(spectrum: &LogFrequencySpectrum) -> Chroma {
+    let mut result = [0.0; 12];
+    for (index, power) in spectrum.powers.iter().enumerate() {
+        result[pitch_class(spectrum.midi_pitch(index))] += power;
+    }
+    normalize(result)
+}
+
+/// Estimates how far the tuning of a recording deviates from A4 = 440 Hz.
+///
+/// The frequencies of the spectral peaks are refined with parabolic interpolation, and their
+/// deviations from the nearest semitone are averaged on a circle, weighted by amplitude.
+///
+/// # Arguments
+///
+/// * `frames` - One or more spectra of the recording.
+///
+/// # Returns
+///
+/// The tuning offset in semitones, between -0.5 and 0.5 (0.0 if no peaks were found).
+pub fn estimate_tuning(frames: &[Frequencies]) -> f64 {
+    // Peaks below this fraction of the frame's maximum are ignored
+    const PEAK_THRESHOLD: f64 = 0.1;
+
+    let (mut sin_sum, mut cos_sum) = (0.0, 0.0);
+    for frame in frames {
+        let amplitudes = &frame.amplitudes;
+        if amplitudes.len() < 3 || frame.total_samples == 0 {
+            continue;
+        }
+        let spacing = frame.sample_rate as f64 / frame.total_samples as f64;
+        let max = amplitudes.iter().fold(0.0_f64, |max, &a| max.max(a));
+
+        for k in 1..amplitudes.len() - 1 {
+            let (left, center, right) = (amplitudes[k - 1], amplitudes[k], amplitudes[k + 1]);
+            let frequency = k as f64 * spacing;
+            if center < max * PEAK_THRESHOLD
+                || center <= left
+                || center < right
+                || !(MIN_FREQUENCY..=MAX_FREQUENCY).contains(&frequency)
+            {
+                continue;
+            }
+
+            // Parabolic interpolation on the log magnitudes of the peak and its neighbours
+            let (l, c, r) = (left.max(1e-20).ln(), center.ln(), right.max(1e-20).ln());
+            let denominator = l - 2.0 * c + r;
+            let offset = if denominator < 0.0 {
+                0.5 * (l - r) / denominator
+            } else {
+                0.0
+            };
+
+            let pitch = frequency_to_midi((k as f64 + offset) * spacing);
+            let angle = 2.0 * PI * (pitch - pitch.round());
+            sin_sum += center * angle.sin();
+            cos_sum += center * angle.cos();
+        }
+    }
+
+    if sin_sum == 0.0 && cos_sum == 0.0 {
+        return 0.0;
+    }
+    sin_sum.atan2(cos_sum) / (2.0 * PI)
+}
+
+/// Calculates the chroma of every STFT frame of a signal.
+///
+/// The tuning is estimated over the whole signal first.
+///
+/// # Arguments
+///
+/// * `samples` - The samples of the signal.
+/// * `sample_rate` - The sample rate of the signal.
+///
+/// # Returns
+///
+/// One chroma vector per frame of `DEFAULT_FRAME_SIZE` samples, `DEFAULT_HOP_SIZE` apart.
+pub fn chromagram(samples: &[f64], sample_rate: u32) -> Vec<Chroma> {
+    let stft = Stft::new(DEFAULT_FRAME_SIZE, DEFAULT_HOP_SIZE, Window::Hann);
+    let frames: Vec<Frequencies> = stft
+        .analyze(samples)
+        .iter()
+        .map(|frame| get_frequencies(frame, sample_rate))
+        .collect();
+
+    let tuning = estimate_tuning(&frames);
+    frames.iter().map(|frame| chroma(frame, tuning)).collect()
+}
+
+fn pitch_class(pitch: f64) -> usize {
+    (pitch.round() as i64).rem_euclid(12) as usize
+}
+
+fn normalize(mut chroma: Chroma) -> Chroma {
+    let max = chroma.iter().fold(0.0_f64, |max, &c| max.max(c));
+    if max > 0.0 {
+        for c in chroma.iter_mut() {
+            *c /= max;
+        }
+    }
+    chroma
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::fft;
+    use crate::log_spectrum::midi_to_frequency;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    pub(crate) fn tones(pitches: &[f64], duration: f64, sample_rate: u32) -> Vec<f64> {
+        (0..(duration * sample_rate as f64) as usize)
+            .map(|i| {
+                let t = i as f64 / sample_rate as f64;
+                pitches
+                    .iter()
+                    .map(|&p| 0.3 * (2.0 * PI * midi_to_frequency(p) * t).sin())
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn spectrum(samples: &[f64], sample_rate: u32) -> Frequencies {
+        let windowed = crate::window::apply_window(samples, &Window::Hann.generate(samples.len()));
+        get_frequencies(&fft(&windowed), sample_rate)
+    }
+
+    fn strongest(chroma: &Chroma, count: usize) -> Vec<usize> {
+        let mut classes: Vec<usize> = (0..12).collect();
+        classes.sort_by(|&a, &b| chroma[b].total_cmp(&chroma[a]));
+        let mut top = classes[..count].to_vec();
+        top.sort();
+        top
+    }
+
+    #[test]
+    fn c_major_triad() {
+        let samples = tones(&[60.0, 64.0, 67.0], 1.0, 16384);
+        let c = chroma(&spectrum(&samples[..8192], 16384), 0.0);
+        assert_eq!(strongest(&c, 3), vec![0, 4, 7]);
+        assert_float_absolute_eq!(c.iter().fold(0.0_f64, |m, &v| m.max(v)), 1.0, 1e-12);
+    }
+
+    #[test]
+    fn triad_from_log_spectrum() {
+        let samples = tones(&[57.0, 60.0, 64.0], 1.0, 16384);
+        let f = spectrum(&samples[..8192], 16384);
+        let log = LogFrequencySpectrum::from_frequencies(&f, midi_to_frequency(36.0), 12, 6);
+        assert_eq!(strongest(&chroma_from_log_spectrum(&log), 3), vec![0, 4, 9]);
+    }
+
+    #[test]
+    fn tuning_of_detuned_tones() {
+        for offset in [-0.3, 0.0, 0.2] {
+            let samples = tones(&[57.0 + offset, 64.0 + offset, 69.0 + offset], 1.0, 16384);
+            let f = spectrum(&samples[..8192], 16384);
+            assert_float_absolute_eq!(estimate_tuning(&[f]), offset, 0.03);
+        }
+    }
+
+    #[test]
+    fn detuned_chroma_is_corrected() {
+        // Half a semitone sharp minus a bit: without correction the energy would leak into C#
+        let samples = tones(&[60.45, 64.45, 67.45], 1.0, 16384);
+        let frames = chromagram(&samples, 16384);
+        assert!(!frames.is_empty());
+        assert_eq!(strongest(&frames[0], 3), vec![0, 4, 7]);
+    }
+
+    #[test]
+    fn silence_has_empty_chroma() {
+        let f = spectrum(&[0.0; 1024], 8000);
+        assert_eq!(chroma(&f, 0.0), [0.0; 12]);
+        assert_eq!(estimate_tuning(&[f]), 0.0);
+    }
+}
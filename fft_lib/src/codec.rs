@@ -0,0 +1,396 @@
+//! Compact binary encoding of `FftResult` and `Frequencies`, for writing spectra to disk or
+//! sending them over a socket.
+//!
+//! Every value is little-endian. A message is a 32 byte header followed by two arrays of
+//! `length` values each:
+//!
+//! | Offset | Size | Field                                                        |
+//! |--------|------|--------------------------------------------------------------|
+//! | 0      | 4    | Magic bytes `FFTS`                                           |
+//! | 4      | 1    | Format version, currently 1                                  |
+//! | 5      | 1    | Kind: 0 = `FftResult`, 1 = `Frequencies`                     |
+//! | 6      | 1    | Precision of the arrays: 0 = f32, 1 = f64                    |
+//! | 7      | 1    | Reserved, always 0                                           |
+//! | 8      | 4    | `sample_rate` as u32 (0 for `FftResult`)                     |
+//! | 12     | 4    | `length`, the number of values in each array, as u32         |
+//! | 16     | 8    | `total_samples` as u64 (the length for `FftResult`)          |
+//! | 24     | 8    | `start_time` in seconds as f64 (0.0 for `FftResult`)         |
+//! | 32     | ...  | `real` then `imag`, or `frequencies` then `amplitudes`       |
+//!
+//! With f64 precision a message decodes to exactly the value that was encoded. f32
+//! precision halves the size at the cost of rounding each value to the nearest f32.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{FftResult, Frequencies};
+
+/// The size of the header in bytes.
+pub const HEADER_SIZE: usize = 32;
+
+const MAGIC: [u8; 4] = *b"FFTS";
+const VERSION: u8 = 1;
+const KIND_FFT_RESULT: u8 = 0;
+const KIND_FREQUENCIES: u8 = 1;
+
+/// The precision the arrays are stored with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    F32,
+    F64,
+}
+
+impl Precision {
+    fn tag(self) -> u8 {
+        match self {
+            Precision::F32 => 0,
+            Precision::F64 => 1,
+        }
+    }
+
+    fn value_size(self) -> usize {
+        match self {
+            Precision::F32 => 4,
+            Precision::F64 => 8,
+        }
+    }
+}
+
+/// The reasons a message can not be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// There are fewer bytes than the header needs.
+    Truncated,
+    /// The message does not start with the magic bytes.
+    BadMagic,
+    UnsupportedVersion(u8),
+    /// The message holds the other type, or an unknown one.
+    WrongKind(u8),
+    UnknownPrecision(u8),
+    /// The number of bytes does not match the length in the header.
+    LengthMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// The length in the header gives a message too large to address on this platform.
+    TooLong(u32),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "message is shorter than the header"),
+            DecodeError::BadMagic => write!(f, "message does not start with the magic bytes"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            DecodeError::WrongKind(kind) => write!(f, "unexpected message kind {}", kind),
+            DecodeError::UnknownPrecision(tag) => write!(f, "unknown precision tag {}", tag),
+            DecodeError::LengthMismatch { expected, actual } => write!(
+                f,
+                "message should be {} bytes but is {} bytes",
+                expected, actual
+            ),
+            DecodeError::TooLong(length) => {
+                write!(f, "arrays of {} values do not fit in memory", length)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// The fields of a decoded header.
+struct Header {
+    kind: u8,
+    precision: Precision,
+    sample_rate: u32,
+    length: usize,
+    total_samples: u64,
+    start_time: f64,
+}
+
+/// Reads the total size of a message from its header, so a stream reader knows how many
+/// bytes to wait for.
+///
+/// # Arguments
+///
+/// * `header` - At least the first `HEADER_SIZE` bytes of a message.
+///
+/// # Returns
+///
+/// The size of the whole message in bytes, including the header, or
+/// `DecodeError::TooLong` if that size overflows `usize`.
+pub fn message_length(header: &[u8]) -> Result<usize, DecodeError> {
+    let header = read_header(header)?;
+    header
+        .length
+        .checked_mul(2 * header.precision.value_size())
+        .and_then(|size| size.checked_add(HEADER_SIZE))
+        .ok_or(DecodeError::TooLong(header.length as u32))
+}
+
+impl FftResult {
+    /// Encodes the result in the binary format described in the `codec` module.
+    ///
+    /// # Panics
+    ///
+    /// If `real` and `imag` have different lengths, or more than `u32::MAX` values.
+    pub fn to_bytes(&self, precision: Precision) -> Vec<u8> {
+        assert_eq!(
+            self.real.len(),
+            self.imag.len(),
+            "Real and imaginary components must have the same length."
+        );
+        let length = self.real.len();
+        let mut bytes = write_header(KIND_FFT_RESULT, precision, 0, length, length as u64, 0.0);
+        write_values(&mut bytes, &self.real, precision);
+        write_values(&mut bytes, &self.imag, precision);
+        bytes
+    }
+
+    /// Decodes a result encoded with `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let header = read_message(bytes, KIND_FFT_RESULT)?;
+        let (real, imag) = read_arrays(bytes, &header);
+        Ok(FftResult { real, imag })
+    }
+}
+
+impl Frequencies {
+    /// Encodes the frequencies in the binary format described in the `codec` module.
+    ///
+    /// # Panics
+    ///
+    /// If `frequencies` and `amplitudes` have different lengths, or more than `u32::MAX`
+    /// values.
+    pub fn to_bytes(&self, precision: Precision) -> Vec<u8> {
+        assert_eq!(
+            self.frequencies.len(),
+            self.amplitudes.len(),
+            "There must be one amplitude per frequency."
+        );
+        let mut bytes = write_header(
+            KIND_FREQUENCIES,
+            precision,
+            self.sample_rate,
+            self.frequencies.len(),
+            self.total_samples as u64,
+            self.start_time,
+        );
+        write_values(&mut bytes, &self.frequencies, precision);
+        write_values(&mut bytes, &self.amplitudes, precision);
+        bytes
+    }
+
+    /// Decodes frequencies encoded with `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let header = read_message(bytes, KIND_FREQUENCIES)?;
+        let (frequencies, amplitudes) = read_arrays(bytes, &header);
+        Ok(Frequencies {
+            frequencies,
+            amplitudes,
+            total_samples: header.total_samples as usize,
+            sample_rate: header.sample_rate,
+            start_time: header.start_time,
+        })
+    }
+}
+
+fn write_header(
+    kind: u8,
+    precision: Precision,
+    sample_rate: u32,
+    length: usize,
+    total_samples: u64,
+    start_time: f64,
+) -> Vec<u8> {
+    let length = u32::try_from(length).expect("The format holds at most u32::MAX values.");
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + 2 * length as usize * precision.value_size());
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&[VERSION, kind, precision.tag(), 0]);
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&length.to_le_bytes());
+    bytes.extend_from_slice(&total_samples.to_le_bytes());
+    bytes.extend_from_slice(&start_time.to_le_bytes());
+    bytes
+}
+
+fn write_values(bytes: &mut Vec<u8>, values: &[f64], precision: Precision) {
+    for value in values {
+        match precision {
+            Precision::F32 => bytes.extend_from_slice(&(*value as f32).to_le_bytes()),
+            Precision::F64 => bytes.extend_from_slice(&value.to_le_bytes()),
+        }
+    }
+}
+
+fn read_header(bytes: &[u8]) -> Result<Header, DecodeError> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(DecodeError::Truncated);
+    }
+    if bytes[0..4] != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    if bytes[4] != VERSION {
+        return Err(DecodeError::UnsupportedVersion(bytes[4]));
+    }
+    let precision = match bytes[6] {
+        0 => Precision::F32,
+        1 => Precision::F64,
+        tag => return Err(DecodeError::UnknownPrecision(tag)),
+    };
+
+    Ok(Header {
+        kind: bytes[5],
+        precision,
+        sample_rate: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+        length: u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize,
+        total_samples: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+        start_time: f64::from_le_bytes(bytes[24..32].try_into().unwrap()),
+    })
+}
+
+/// Reads the header and checks that the message has the expected kind and size.
+fn read_message(bytes: &[u8], kind: u8) -> Result<Header, DecodeError> {
+    let header = read_header(bytes)?;
+    if header.kind != kind {
+        return Err(DecodeError::WrongKind(header.kind));
+    }
+    let expected = message_length(bytes)?;
+    if bytes.len() != expected {
+        return Err(DecodeError::LengthMismatch {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+    Ok(header)
+}
+
+fn read_arrays(bytes: &[u8], header: &Header) -> (Vec<f64>, Vec<f64>) {
+    let size = header.precision.value_size();
+    let values: Vec<f64> = bytes[HEADER_SIZE..]
+        .chunks_exact(size)
+        .map(|chunk| match header.precision {
+            Precision::F32 => f32::from_le_bytes(chunk.try_into().unwrap()) as f64,
+            Precision::F64 => f64::from_le_bytes(chunk.try_into().unwrap()),
+        })
+        .collect();
+    let (first, second) = values.split_at(header.length);
+    (first.to_vec(), second.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fft, get_frequencies};
+    use alloc::vec;
+
+    fn spectrum() -> Frequencies {
+        let samples: Vec<f64> = (0..64).map(|i| (i as f64 * 0.37).sin() / 3.0).collect();
+        let mut frequencies = get_frequencies(&fft(&samples), 44100);
+        frequencies.start_time = 12.345;
+        frequencies
+    }
+
+    #[test]
+    fn f64_round_trip_is_exact() {
+        let frequencies = spectrum();
+        let bytes = frequencies.to_bytes(Precision::F64);
+        assert_eq!(bytes.len(), HEADER_SIZE + 2 * 32 * 8);
+        assert_eq!(Frequencies::from_bytes(&bytes).unwrap(), frequencies);
+
+        let result = fft(&[0.1, -0.7, 0.3, 0.9]);
+        let bytes = result.to_bytes(Precision::F64);
+        assert_eq!(FftResult::from_bytes(&bytes).unwrap(), result);
+    }
+
+    #[test]
+    fn f32_round_trip_rounds_values() {
+        let frequencies = spectrum();
+        let bytes = frequencies.to_bytes(Precision::F32);
+        assert_eq!(bytes.len(), HEADER_SIZE + 2 * 32 * 4);
+
+        let decoded = Frequencies::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.start_time, 12.345);
+        for (a, b) in decoded.amplitudes.iter().zip(frequencies.amplitudes.iter()) {
+            assert_eq!(*a, *b as f32 as f64);
+        }
+    }
+
+    #[test]
+    fn header_layout() {
+        let result = FftResult {
+            real: vec![1.0],
+            imag: vec![-2.0],
+        };
+        let bytes = result.to_bytes(Precision::F32);
+        assert_eq!(
+            bytes,
+            [
+                b'F', b'F', b'T', b'S', 1, 0, 0, 0, // magic, version, kind, precision
+                0, 0, 0, 0, 1, 0, 0, 0, // sample rate, length
+                1, 0, 0, 0, 0, 0, 0, 0, // total samples
+                0, 0, 0, 0, 0, 0, 0, 0, // start time
+                0, 0, 0x80, 0x3f, 0, 0, 0, 0xc0, // 1.0f32, -2.0f32
+            ]
+        );
+        assert_eq!(message_length(&bytes[..HEADER_SIZE]), Ok(bytes.len()));
+    }
+
+    #[test]
+    fn rejects_invalid_messages() {
+        let bytes = spectrum().to_bytes(Precision::F64);
+
+        assert_eq!(
+            Frequencies::from_bytes(&bytes[..10]),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(
+            Frequencies::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::LengthMismatch {
+                expected: bytes.len(),
+                actual: bytes.len() - 1
+            })
+        );
+        assert_eq!(
+            FftResult::from_bytes(&bytes),
+            Err(DecodeError::WrongKind(1))
+        );
+
+        let mut corrupted = bytes.clone();
+        corrupted[0] = b'X';
+        assert_eq!(
+            Frequencies::from_bytes(&corrupted),
+            Err(DecodeError::BadMagic)
+        );
+
+        let mut corrupted = bytes.clone();
+        corrupted[6] = 7;
+        assert_eq!(
+            Frequencies::from_bytes(&corrupted),
+            Err(DecodeError::UnknownPrecision(7))
+        );
+
+        // A huge length is an error, whether or not its size overflows usize
+        let mut corrupted = bytes;
+        corrupted[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Frequencies::from_bytes(&corrupted).is_err());
+        if usize::BITS == 32 {
+            assert_eq!(
+                message_length(&corrupted),
+                Err(DecodeError::TooLong(u32::MAX))
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let frequencies = spectrum();
+        let json = serde_json::to_string(&frequencies).unwrap();
+        let decoded: Frequencies = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, frequencies);
+    }
+}
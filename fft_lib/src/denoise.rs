@@ -0,0 +1,499 @@
+//! Noise reduction by spectral subtraction or Wiener filtering.
+//!
+//! The signal is analyzed with an STFT, every bin is scaled by a gain computed from the
+//! estimated noise power, and the result is resynthesized by overlap-add. The noise power
+//! is either learned from a segment that contains only noise, or tracked continuously with
+//! minimum statistics.
+
+use crate::stft::{OverlapAdd, Stft};
+use crate::window::Window;
+use crate::FftResult;
+
+/// How the gain of each bin is derived from the signal and noise powers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SuppressionMethod {
+    /// Power spectral subtraction. `over_subtraction` scales the noise estimate
+    /// (values above 1.0 remove more noise at the cost of more distortion).
+    SpectralSubtraction { over_subtraction: f64 },
+    /// Wiener gain with a decision-directed a priori SNR estimate. `smoothing`
+    /// (0.9 to 0.99) weighs the previous frame's clean estimate against the current one,
+    /// which is what keeps isolated noise peaks from turning into musical noise.
+    Wiener { smoothing: f64 },
+}
+
+/// Settings of a `NoiseReducer`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseReductionSettings {
+    /// The STFT frame size, must be a power of 2.
+    pub frame_size: usize,
+    /// The number of samples between frames.
+    pub hop_size: usize,
+    /// How the gains are computed.
+    pub method: SuppressionMethod,
+    /// The smallest gain applied to any bin, e.g. 0.1 for at most 20 dB of attenuation.
+    /// A floor keeps the residual noise natural sounding.
+    pub gain_floor: f64,
+    /// Smoothing of each bin's gain over time, between 0.0 (none) and 1.0.
+    pub gain_smoothing: f64,
+}
+
+impl Default for NoiseReductionSettings {
+    fn default() -> Self {
+        Self {
+            frame_size: 1024,
+            hop_size: 256,
+            method: SuppressionMethod::Wiener { smoothing: 0.98 },
+            gain_floor: 0.1,
+            gain_smoothing: 0.5,
+        }
+    }
+}
+
+/// The average noise power per STFT bin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseProfile {
+    /// The noise power of bins 0 to `frame_size / 2`.
+    pub power: Vec<f64>,
+}
+
+impl NoiseProfile {
+    /// Learns a noise profile from a segment that contains only noise.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - The noise-only segment, at least one frame long.
+    /// * `frame_size` - The STFT frame size the profile will be used with.
+    /// * `hop_size` - The number of samples between frames.
+    ///
+    /// # Returns
+    ///
+    /// The profile, or `None` if the segment is shorter than one frame.
+    pub fn learn(samples: &[f64], frame_size: usize, hop_size: usize) -> Option<Self> {
+        let frames = Stft::new(frame_size, hop_size, Window::Hann).analyze(samples);
+        if frames.is_empty() {
+            return None;
+        }
+
+        let mut power = vec![0.0; frame_size / 2 + 1];
+        for frame in &frames {
+            for (k, p) in power.iter_mut().enumerate() {
+                *p += bin_power(frame, k);
+            }
+        }
+        for p in power.iter_mut() {
+            *p /= frames.len() as f64;
+        }
+
+        Some(Self { power })
+    }
+}
+
+/// Tracks the noise power as the minimum of the smoothed signal power over a sliding
+/// window, which follows slowly changing noise while ignoring speech or music on top of it
+/// (Martin, 2001, simplified).
+struct MinimumStatistics {
+    smoothed: Vec<f64>,
+    current_minimum: Vec<f64>,
+    window_minima: Vec<Vec<f64>>,
+    frames_in_subwindow: usize,
+    subwindow_length: usize,
+    frames: usize,
+}
+
+impl MinimumStatistics {
+    /// Smoothing of the periodogram before the minimum search.
+    const SMOOTHING: f64 = 0.85;
+    /// Frames to wait before the smoothed periodogram enters the minimum search.
+    const WARM_UP_FRAMES: usize = 20;
+    /// Number of subwindows the search window is split into.
+    const SUBWINDOWS: usize = 8;
+    /// Length of the search window in frames, about 1.5 s at 44.1 kHz with a hop of 256.
+    const WINDOW_FRAMES: usize = 256;
+    /// Compensates for the minimum of a noisy estimate lying below its mean.
+    const BIAS: f64 = 2.5;
+
+    fn new(bins: usize) -> Self {
+        Self {
+            smoothed: vec![0.0; bins],
+            current_minimum: vec![f64::MAX; bins],
+            window_minima: Vec::new(),
+            frames_in_subwindow: 0,
+            subwindow_length: Self::WINDOW_FRAMES / Self::SUBWINDOWS,
+            frames: 0,
+        }
+    }
+
+    fn update(&mut self, power: &[f64]) {
+        for (k, p) in power.iter().enumerate() {
+            self.smoothed[k] = if self.frames > 0 {
+                Self::SMOOTHING * self.smoothed[k] + (1.0 - Self::SMOOTHING) * p
+            } else {
+                *p
+            };
+        }
+        self.frames += 1;
+        if self.frames < Self::WARM_UP_FRAMES {
+            return;
+        }
+
+        for (minimum, smoothed) in self.current_minimum.iter_mut().zip(self.smoothed.iter()) {
+            *minimum = minimum.min(*smoothed);
+        }
+
+        self.frames_in_subwindow += 1;
+        if self.frames_in_subwindow >= self.subwindow_length {
+            self.frames_in_subwindow = 0;
+            self.window_minima.push(self.current_minimum.clone());
+            if self.window_minima.len() > Self::SUBWINDOWS {
+                self.window_minima.remove(0);
+            }
+            self.current_minimum = self.smoothed.clone();
+        }
+    }
+
+    fn noise(&self, k: usize) -> f64 {
+        if self.frames < Self::WARM_UP_FRAMES {
+            return self.smoothed[k];
+        }
+
+        let minimum = self
+            .window_minima
+            .iter()
+            .map(|minima| minima[k])
+            .fold(self.current_minimum[k], f64::min);
+        Self::BIAS * minimum
+    }
+}
+
+enum NoiseEstimate {
+    Profile(NoiseProfile),
+    Tracked(MinimumStatistics),
+}
+
+/// Removes stationary noise from a signal, offline or as a streaming stage.
+///
+/// # How to use:
+/// ```ignore
+/// // Learn the noise from the first second of the microphone, then clean the rest
+/// let settings = NoiseReductionSettings::default();
+/// let profile = NoiseProfile::learn(&first_second, settings.frame_size, settings.hop_size)
+///     .expect("Noise segment too short");
+/// let mut reducer = NoiseReducer::with_profile(profile, settings);
+///
+/// while let Ok(chunk) = audio_rx.recv() {
+///     let chunk: Vec<f64> = chunk.into_iter().map(|s| s as f64).collect();
+///     let cleaned = reducer.process(&chunk);
+///     // Analyze or record `cleaned` here
+/// }
+/// ```
+pub struct NoiseReducer {
+    settings: NoiseReductionSettings,
+    stft: Stft,
+    synthesis: OverlapAdd,
+    noise: NoiseEstimate,
+    gains: Vec<f64>,
+    previous_clean_power: Vec<f64>,
+    /// Input samples fed in but not yet returned.
+    pending: usize,
+}
+
+impl NoiseReducer {
+    /// Creates a noise reducer that uses a fixed, previously learned noise profile.
+    ///
+    /// # Panics
+    ///
+    /// If the profile does not match the frame size of the settings.
+    pub fn with_profile(profile: NoiseProfile, settings: NoiseReductionSettings) -> Self {
+        assert_eq!(
+            profile.power.len(),
+            settings.frame_size / 2 + 1,
+            "Noise profile must match the frame size."
+        );
+        Self::new(NoiseEstimate::Profile(profile), settings)
+    }
+
+    /// Creates a noise reducer that tracks the noise with minimum statistics,
+    /// so no noise-only segment is needed. It needs a second or two to settle.
+    pub fn adaptive(settings: NoiseReductionSettings) -> Self {
+        let bins = settings.frame_size / 2 + 1;
+        Self::new(
+            NoiseEstimate::Tracked(MinimumStatistics::new(bins)),
+            settings,
+        )
+    }
+
+    fn new(noise: NoiseEstimate, settings: NoiseReductionSettings) -> Self {
+        let bins = settings.frame_size / 2 + 1;
+        Self {
+            settings,
+            stft: Stft::new(settings.frame_size, settings.hop_size, Window::Hann),
+            synthesis: OverlapAdd::new(settings.frame_size, settings.hop_size, Window::Hann),
+            noise,
+            gains: vec![1.0; bins],
+            previous_clean_power: vec![0.0; bins],
+            pending: 0,
+        }
+    }
+
+    /// Replaces the noise estimate with a profile learned from a noise-only segment,
+    /// e.g. one the user marked while nobody was speaking.
+    ///
+    /// # Returns
+    ///
+    /// `false` (keeping the current estimate) if the segment is shorter than one frame.
+    pub fn learn_noise(&mut self, segment: &[f64]) -> bool {
+        match NoiseProfile::learn(segment, self.settings.frame_size, self.settings.hop_size) {
+            Some(profile) => {
+                self.noise = NoiseEstimate::Profile(profile);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Feeds a chunk of noisy samples and returns the cleaned samples that are ready.
+    ///
+    /// The output lags the input by `frame_size - hop_size` samples.
+    pub fn process(&mut self, chunk: &[f64]) -> Vec<f64> {
+        let output = self.process_frames(chunk);
+        self.pending = self.pending + chunk.len() - output.len();
+        output
+    }
+
+    /// Returns the samples still held back at the end of a stream, so that the whole
+    /// output has the same length and alignment as the input. The next chunk starts a
+    /// new stream.
+    pub fn finish(&mut self) -> Vec<f64> {
+        // Past the end the input is silent, feed it until every held back sample is complete
+        let silence = vec![0.0; self.settings.hop_size];
+        let mut output = Vec::with_capacity(self.pending + self.settings.hop_size);
+        while output.len() < self.pending {
+            output.extend(self.process_frames(&silence));
+        }
+        output.truncate(self.pending);
+
+        self.pending = 0;
+        self.stft.reset();
+        self.synthesis.finish();
+        output
+    }
+
+    fn process_frames(&mut self, chunk: &[f64]) -> Vec<f64> {
+        let mut output = Vec::with_capacity(chunk.len());
+        for frame in self.stft.process(chunk) {
+            let cleaned = self.suppress(&frame);
+            output.extend(self.synthesis.process(&cleaned));
+        }
+        output
+    }
+
+    fn suppress(&mut self, frame: &FftResult) -> FftResult {
+        let n = frame.real.len();
+        let power: Vec<f64> = (0..=n / 2).map(|k| bin_power(frame, k)).collect();
+        if let NoiseEstimate::Tracked(tracker) = &mut self.noise {
+            tracker.update(&power);
+        }
+
+        for (k, &p) in power.iter().enumerate() {
+            let noise = match &self.noise {
+                NoiseEstimate::Profile(profile) => profile.power[k],
+                NoiseEstimate::Tracked(tracker) => tracker.noise(k),
+            };
+
+            let gain = if noise <= 0.0 {
+                1.0
+            } else {
+                match self.settings.method {
+                    SuppressionMethod::SpectralSubtraction { over_subtraction } => (1.0
+                        - over_subtraction * noise / p.max(f64::MIN_POSITIVE))
+                    .max(0.0)
+                    .sqrt(),
+                    SuppressionMethod::Wiener { smoothing } => {
+                        let posterior_snr = p / noise;
+                        let prior_snr = smoothing * self.previous_clean_power[k] / noise
+                            + (1.0 - smoothing) * (posterior_snr - 1.0).max(0.0);
+                        prior_snr / (1.0 + prior_snr)
+                    }
+                }
+            }
+            .max(self.settings.gain_floor);
+
+            let smoothing = self.settings.gain_smoothing;
+            self.gains[k] = smoothing * self.gains[k] + (1.0 - smoothing) * gain;
+            self.previous_clean_power[k] = self.gains[k] * self.gains[k] * p;
+        }
+
+        // Bins above N/2 mirror the ones below, scale them alike so the output stays real
+        let gain = |i: usize| self.gains[i.min(n - i)];
+        FftResult {
+            real: frame
+                .real
+                .iter()
+                .enumerate()
+                .map(|(i, v)| v * gain(i))
+                .collect(),
+            imag: frame
+                .imag
+                .iter()
+                .enumerate()
+                .map(|(i, v)| v * gain(i))
+                .collect(),
+        }
+    }
+}
+
+/// Removes noise from a complete signal.
+///
+/// # Arguments
+///
+/// * `samples` - The noisy signal.
+/// * `noise` - A noise-only segment to learn from, or `None` to track the noise with
+///   minimum statistics.
+/// * `settings` - The noise reduction settings.
+///
+/// # Returns
+///
+/// The cleaned signal, with the same length and alignment as the input.
+///
+/// # Panics
+///
+/// If the noise segment is shorter than one frame.
+pub fn reduce_noise(
+    samples: &[f64],
+    noise: Option<&[f64]>,
+    settings: NoiseReductionSettings,
+) -> Vec<f64> {
+    let mut reducer = match noise {
+        Some(segment) => {
+            let profile = NoiseProfile::learn(segment, settings.frame_size, settings.hop_size)
+                .expect("Noise segment must be at least one frame long.");
+            NoiseReducer::with_profile(profile, settings)
+        }
+        None => NoiseReducer::adaptive(settings),
+    };
+
+    let mut output = reducer.process(samples);
+    output.extend(reducer.finish());
+    output
+}
+
+fn bin_power(frame: &FftResult, k: usize) -> f64 {
+    frame.real[k] * frame.real[k] + frame.imag[k] * frame.imag[k]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{noise, sine};
+
+    fn white_noise(length: usize, level: f64, seed: u64) -> Vec<f64> {
+        noise(seed, length).iter().map(|n| level * n).collect()
+    }
+
+    /// Signal-to-noise ratio in dB of `signal` against the known clean signal,
+    /// skipping the edges where the STFT is not fully covered.
+    fn snr(clean: &[f64], signal: &[f64]) -> f64 {
+        let range = 2048..clean.len() - 2048;
+        let signal_power: f64 = clean[range.clone()].iter().map(|s| s * s).sum();
+        let error_power: f64 = range.map(|i| (signal[i] - clean[i]).powi(2)).sum();
+        10.0 * (signal_power / error_power).log10()
+    }
+
+    #[test]
+    fn profile_of_silence_is_zero() {
+        let profile = NoiseProfile::learn(&[0.0; 4096], 1024, 256).unwrap();
+        assert_eq!(profile.power.len(), 513);
+        assert!(profile.power.iter().all(|p| *p == 0.0));
+        assert!(NoiseProfile::learn(&[0.0; 100], 1024, 256).is_none());
+    }
+
+    #[test]
+    fn clean_signal_passes_unchanged() {
+        let clean = sine(440.0, 16000, 16384);
+        let settings = NoiseReductionSettings::default();
+        let output = reduce_noise(&clean, Some(&[0.0; 4096]), settings);
+        assert!(snr(&clean, &output) > 100.0);
+    }
+
+    #[test]
+    fn learned_profile_improves_snr() {
+        let clean = sine(440.0, 16000, 32768);
+        let noise = white_noise(clean.len() + 8192, 0.2, 7);
+        let noisy: Vec<f64> = clean.iter().zip(noise.iter()).map(|(c, n)| c + n).collect();
+
+        for method in [
+            SuppressionMethod::SpectralSubtraction {
+                over_subtraction: 1.5,
+            },
+            SuppressionMethod::Wiener { smoothing: 0.98 },
+        ] {
+            let settings = NoiseReductionSettings {
+                method,
+                ..Default::default()
+            };
+            let output = reduce_noise(&noisy, Some(&noise[clean.len()..]), settings);
+            let improvement = snr(&clean, &output) - snr(&clean, &noisy);
+            assert!(
+                improvement > 6.0,
+                "{:?} improved SNR by {} dB",
+                method,
+                improvement
+            );
+        }
+    }
+
+    #[test]
+    fn minimum_statistics_improves_snr() {
+        // Minimum statistics needs pauses to see the noise floor, so gate the tone on and off
+        let clean: Vec<f64> = sine(440.0, 16000, 65536)
+            .iter()
+            .enumerate()
+            .map(|(i, s)| if (i / 8000) % 2 == 0 { *s } else { 0.0 })
+            .collect();
+        let noise = white_noise(clean.len(), 0.2, 11);
+        let noisy: Vec<f64> = clean.iter().zip(noise.iter()).map(|(c, n)| c + n).collect();
+
+        let output = reduce_noise(&noisy, None, NoiseReductionSettings::default());
+        // Only judge the second half, after the tracker has settled
+        let half = clean.len() / 2;
+        let improvement =
+            snr(&clean[half..], &output[half..]) - snr(&clean[half..], &noisy[half..]);
+        assert!(improvement > 6.0, "improved SNR by {} dB", improvement);
+    }
+
+    #[test]
+    fn streaming_matches_offline() {
+        let noise = white_noise(20000, 0.1, 3);
+        let signal: Vec<f64> = sine(300.0, 16000, 20000)
+            .iter()
+            .zip(noise.iter())
+            .map(|(s, n)| s + n)
+            .collect();
+        let settings = NoiseReductionSettings::default();
+        let offline = reduce_noise(&signal, Some(&noise[..4096]), settings);
+
+        let profile = NoiseProfile::learn(&noise[..4096], settings.frame_size, settings.hop_size);
+        let mut reducer = NoiseReducer::with_profile(profile.unwrap(), settings);
+        let mut streamed: Vec<f64> = signal
+            .chunks(300)
+            .flat_map(|c| reducer.process(c))
+            .collect();
+        assert!(streamed.len() > 16000);
+        streamed.extend(reducer.finish());
+
+        assert_eq!(streamed.len(), signal.len());
+        for (a, b) in streamed.iter().zip(offline.iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn short_noise_segment_is_rejected() {
+        reduce_noise(
+            &[0.0; 4096],
+            Some(&[0.0; 100]),
+            NoiseReductionSettings::default(),
+        );
+    }
+}
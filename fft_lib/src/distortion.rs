@@ -0,0 +1,345 @@
+//! Distortion and noise measurements of a test tone, following the IEEE 1241 conventions.
+//!
+//! The signal is windowed and transformed once. The power of the fundamental and of each
+//! harmonic is the sum over the bins of its main lobe, the bins around DC are ignored and
+//! everything else is noise. Harmonics above the Nyquist frequency are folded back to where
+//! they alias, as they would in a sampled system.
+
+use alloc::{vec, vec::Vec};
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{fft, window::apply_window, window::Window};
+
+/// The level of one spectral component of the test tone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Harmonic {
+    /// 1 for the fundamental, 2 for the second harmonic and so on.
+    pub order: usize,
+    /// Where the component appears in the spectrum, after folding around the Nyquist frequency.
+    pub frequency: f64,
+    /// The RMS level, in the units of the samples.
+    pub rms: f64,
+    /// The level relative to the fundamental, in dB (dBc).
+    pub level_db: f64,
+}
+
+/// The result of `analyze_distortion`.
+///
+/// The ratios (`thd`, `thd_n`) are amplitude ratios, so 0.01 is 1 % or -40 dB.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistortionAnalysis {
+    pub fundamental: Harmonic,
+    /// The harmonics from order 2 up, skipping those that land on DC or on the fundamental.
+    pub harmonics: Vec<Harmonic>,
+    /// The RMS level of everything that is neither DC, the fundamental nor a harmonic.
+    pub noise_rms: f64,
+    /// Total harmonic distortion: the RMS of the harmonics over the fundamental.
+    pub thd: f64,
+    pub thd_db: f64,
+    /// Total harmonic distortion plus noise: the RMS of everything but DC and the fundamental,
+    /// over the fundamental.
+    pub thd_n: f64,
+    pub thd_n_db: f64,
+    /// Signal to noise ratio in dB, harmonics excluded.
+    pub snr_db: f64,
+    /// Signal to noise and distortion ratio in dB.
+    pub sinad_db: f64,
+    /// Effective number of bits, assuming the tone is at full scale.
+    pub enob: f64,
+}
+
+impl DistortionAnalysis {
+    /// The effective number of bits for a tone below full scale.
+    ///
+    /// # Arguments
+    ///
+    /// * `full_scale` - The peak amplitude of a full scale sine, e.g. 1.0 for normalized samples.
+    pub fn enob_at_full_scale(&self, full_scale: f64) -> f64 {
+        let amplitude = self.fundamental.rms * core::f64::consts::SQRT_2;
+        self.enob + 20.0 * (full_scale / amplitude).log10() / 6.02
+    }
+}
+
+/// Measures the fundamental, the harmonics and the noise of a recorded test tone.
+///
+/// The tone does not have to be sampled coherently, but the window then has to keep the
+/// leakage of the fundamental below the noise; `Window::BlackmanHarris` does for about 15
+/// bits. The fundamental is the strongest component above DC.
+///
+/// Without energy above DC, e.g. for silence, there is no tone to measure and every level
+/// and ratio of the result is zero.
+///
+/// # Arguments
+///
+/// * `samples` - The recorded tone, its length must be a power of 2.
+/// * `sample_rate` - The sample rate of the recording.
+/// * `window` - The window applied before transforming.
+/// * `harmonics` - The highest harmonic order counted as distortion, e.g. 10.
+///
+/// # Returns
+///
+/// A `DistortionAnalysis` with the levels and ratios.
+///
+/// # Panics
+///
+/// If the length of `samples` is not a power of 2 or is too short to separate the tone from DC.
+pub fn analyze_distortion(
+    samples: &[f64],
+    sample_rate: u32,
+    window: Window,
+    harmonics: usize,
+) -> DistortionAnalysis {
+    let N = samples.len();
+    let half_width = main_lobe_half_width(window);
+    assert!(
+        N >= 8 * half_width,
+        "Too few samples to separate the tone from DC."
+    );
+
+    let coefficients = window.generate(N);
+    let power_gain = coefficients.iter().map(|w| w * w).sum::<f64>() / N as f64;
+    let spectrum = fft(&apply_window(samples, &coefficients));
+
+    // One-sided power per bin, scaled so a lobe sums to the mean square of its component
+    let last = N / 2;
+    let power: Vec<f64> = (0..=last)
+        .map(|k| {
+            let p = spectrum.real[k].powi(2) + spectrum.imag[k].powi(2);
+            let one_sided = if k == 0 || k == last { p } else { 2.0 * p };
+            one_sided / power_gain
+        })
+        .collect();
+    let bin_width = sample_rate as f64 / N as f64;
+
+    let mut used = vec![false; last + 1];
+    used[..=half_width].iter_mut().for_each(|u| *u = true);
+
+    let peak = (half_width + 1..=last)
+        .max_by(|&a, &b| power[a].total_cmp(&power[b]))
+        .unwrap();
+    let (fundamental_power, fundamental_bin, _) = lobe(&power, &mut used, peak, half_width);
+    let fundamental_frequency = fundamental_bin * bin_width;
+    if fundamental_power == 0.0 {
+        return DistortionAnalysis {
+            fundamental: Harmonic {
+                order: 1,
+                frequency: fundamental_frequency,
+                rms: 0.0,
+                level_db: 0.0,
+            },
+            harmonics: Vec::new(),
+            noise_rms: 0.0,
+            thd: 0.0,
+            thd_db: 0.0,
+            thd_n: 0.0,
+            thd_n_db: 0.0,
+            snr_db: 0.0,
+            sinad_db: 0.0,
+            enob: 0.0,
+        };
+    }
+
+    let mut harmonic_levels = Vec::new();
+    let mut harmonic_power = 0.0;
+    let mut harmonic_bins = 0;
+    for order in 2..=harmonics {
+        let frequency = fold(order as f64 * fundamental_frequency, sample_rate as f64);
+        let expected = (frequency / bin_width).round() as usize;
+
+        // The estimate of the fundamental is off by a fraction of a bin, multiplied by the order
+        let center = (expected.saturating_sub(1)..=(expected + 1).min(last))
+            .filter(|&k| !used[k])
+            .max_by(|&a, &b| power[a].total_cmp(&power[b]));
+        let Some(center) = center else {
+            continue;
+        };
+        let (power_sum, _, bins) = lobe(&power, &mut used, center, half_width);
+        harmonic_power += power_sum;
+        harmonic_bins += bins;
+        harmonic_levels.push(Harmonic {
+            order,
+            frequency,
+            rms: power_sum.sqrt(),
+            level_db: 10.0 * (power_sum / fundamental_power).log10(),
+        });
+    }
+
+    // The noise in the bins taken by the harmonics is assumed to match the rest
+    let noise_bins: Vec<usize> = (0..=last).filter(|&k| !used[k]).collect();
+    let residual: f64 = noise_bins.iter().map(|&k| power[k]).sum();
+    let noise_power = if noise_bins.is_empty() {
+        0.0
+    } else {
+        residual * (noise_bins.len() + harmonic_bins) as f64 / noise_bins.len() as f64
+    };
+    let distortion_power = residual + harmonic_power;
+
+    let sinad_db = 10.0 * (fundamental_power / distortion_power).log10();
+    DistortionAnalysis {
+        fundamental: Harmonic {
+            order: 1,
+            frequency: fundamental_frequency,
+            rms: fundamental_power.sqrt(),
+            level_db: 0.0,
+        },
+        harmonics: harmonic_levels,
+        noise_rms: noise_power.sqrt(),
+        thd: (harmonic_power / fundamental_power).sqrt(),
+        thd_db: 10.0 * (harmonic_power / fundamental_power).log10(),
+        thd_n: (distortion_power / fundamental_power).sqrt(),
+        thd_n_db: -sinad_db,
+        snr_db: 10.0 * (fundamental_power / noise_power).log10(),
+        sinad_db,
+        enob: (sinad_db - 1.76) / 6.02,
+    }
+}
+
+/// The number of bins on each side of a peak that belong to the window's main lobe.
+fn main_lobe_half_width(window: Window) -> usize {
+    match window {
+        Window::Rectangular => 1,
+        Window::Hann | Window::Hamming => 2,
+        Window::Blackman => 3,
+        Window::BlackmanHarris => 4,
+    }
+}
+
+/// Sums the unused bins of the lobe around `center` and marks them as used.
+///
+/// # Returns
+///
+/// The power of the lobe, its power weighted center in bins and the number of bins summed.
+fn lobe(power: &[f64], used: &mut [bool], center: usize, half_width: usize) -> (f64, f64, usize) {
+    let start = center.saturating_sub(half_width);
+    let end = (center + half_width).min(power.len() - 1);
+    let mut sum = 0.0;
+    let mut moment = 0.0;
+    let mut bins = 0;
+    for k in start..=end {
+        if !used[k] {
+            used[k] = true;
+            sum += power[k];
+            moment += k as f64 * power[k];
+            bins += 1;
+        }
+    }
+    let weighted_center = if sum > 0.0 {
+        moment / sum
+    } else {
+        center as f64
+    };
+    (sum, weighted_center, bins)
+}
+
+/// Where a frequency appears after sampling, between 0 and the Nyquist frequency.
+fn fold(frequency: f64, sample_rate: f64) -> f64 {
+    let wrapped = frequency % sample_rate;
+    if wrapped > sample_rate / 2.0 {
+        sample_rate - wrapped
+    } else {
+        wrapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::noise;
+    use assert_float_eq::assert_float_absolute_eq;
+    use core::f64::consts::PI;
+
+    const SAMPLE_RATE: u32 = 48000;
+    const N: usize = 8192;
+
+    fn tone(frequency: f64, amplitudes: &[f64]) -> Vec<f64> {
+        (0..N)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                amplitudes
+                    .iter()
+                    .enumerate()
+                    .map(|(h, a)| a * (2.0 * PI * frequency * (h + 1) as f64 * t).sin())
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn measures_known_harmonics() {
+        // -40 dBc second and -60 dBc third harmonic, on a tone between two bins
+        let samples = tone(1000.3, &[1.0, 0.01, 0.001]);
+        let analysis = analyze_distortion(&samples, SAMPLE_RATE, Window::BlackmanHarris, 5);
+
+        assert_float_absolute_eq!(analysis.fundamental.frequency, 1000.3, 0.05);
+        assert_float_absolute_eq!(analysis.fundamental.rms, 0.5f64.sqrt(), 1e-4);
+        assert_float_absolute_eq!(analysis.harmonics[0].level_db, -40.0, 0.05);
+        assert_float_absolute_eq!(analysis.harmonics[1].level_db, -60.0, 0.05);
+        assert!(analysis.harmonics[2].level_db < -120.0);
+
+        let expected_thd = (0.01f64.powi(2) + 0.001f64.powi(2)).sqrt();
+        assert_float_absolute_eq!(analysis.thd, expected_thd, expected_thd * 0.01);
+        assert_float_absolute_eq!(analysis.thd_db, 20.0 * expected_thd.log10(), 0.1);
+        assert!(analysis.thd_n >= analysis.thd);
+    }
+
+    #[test]
+    fn folds_harmonics_above_nyquist() {
+        let samples = tone(15000.0, &[1.0, 0.0, 0.01]);
+        let analysis = analyze_distortion(&samples, SAMPLE_RATE, Window::BlackmanHarris, 3);
+
+        let third = &analysis.harmonics[1];
+        assert_float_absolute_eq!(third.frequency, 3000.0, 1.0);
+        assert_float_absolute_eq!(third.level_db, -40.0, 0.05);
+    }
+
+    #[test]
+    fn measures_snr_of_white_noise() {
+        // Uniform noise has an RMS of amplitude / sqrt(3)
+        let noise_amplitude = 0.01;
+        let samples: Vec<f64> = tone(997.0, &[1.0])
+            .into_iter()
+            .zip(noise(1, N))
+            .map(|(s, n)| s + noise_amplitude * n)
+            .collect();
+        let analysis = analyze_distortion(&samples, SAMPLE_RATE, Window::BlackmanHarris, 10);
+
+        let noise_rms = noise_amplitude / 3f64.sqrt();
+        let expected_snr = 20.0 * (0.5f64.sqrt() / noise_rms).log10();
+        assert_float_absolute_eq!(analysis.noise_rms, noise_rms, noise_rms * 0.05);
+        assert_float_absolute_eq!(analysis.snr_db, expected_snr, 0.5);
+        assert_float_absolute_eq!(analysis.sinad_db, expected_snr, 0.5);
+        assert!(analysis.sinad_db <= analysis.snr_db);
+    }
+
+    #[test]
+    fn quantized_sine_has_expected_enob() {
+        let bits = 12;
+        let step = 2.0 / (1u32 << bits) as f64;
+        let samples: Vec<f64> = tone(1234.5, &[1.0 - step])
+            .into_iter()
+            .map(|s| (s / step).round() * step)
+            .collect();
+        let analysis = analyze_distortion(&samples, SAMPLE_RATE, Window::BlackmanHarris, 10);
+
+        assert_float_absolute_eq!(analysis.enob, bits as f64, 0.15);
+        assert_float_absolute_eq!(analysis.enob_at_full_scale(1.0), bits as f64, 0.15);
+    }
+
+    #[test]
+    fn silence_gives_zero_metrics() {
+        let analysis = analyze_distortion(&[0.0; N], SAMPLE_RATE, Window::BlackmanHarris, 10);
+        assert_eq!(analysis.fundamental.rms, 0.0);
+        assert!(analysis.harmonics.is_empty());
+        for value in [
+            analysis.thd,
+            analysis.thd_n,
+            analysis.snr_db,
+            analysis.sinad_db,
+            analysis.enob,
+        ] {
+            assert_eq!(value, 0.0);
+        }
+        assert!(!analysis.fundamental.frequency.is_nan());
+    }
+}
@@ -0,0 +1,340 @@
+//! Spectral and time-domain feature extractors.
+//!
+//! The spectral descriptors work on a single `Frequencies` frame (as returned by
+//! `get_frequencies`), the time-domain ones on a chunk of raw samples.
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::Frequencies;
+
+/// Calculates the spectral centroid, the amplitude-weighted mean frequency of a frame.
+///
+/// # Arguments
+///
+/// * `frequencies` - The spectrum to analyze.
+///
+/// # Returns
+///
+/// The centroid in Hz, or 0.0 if the spectrum is silent.
+pub fn spectral_centroid(frequencies: &Frequencies) -> f64 {
+    let total: f64 = frequencies.amplitudes.iter().sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+
+    frequencies
+        .frequencies
+        .iter()
+        .zip(frequencies.amplitudes.iter())
+        .map(|(f, a)| f * a)
+        .sum::<f64>()
+        / total
+}
+
+/// Calculates the spectral spread (bandwidth), the amplitude-weighted standard deviation
+/// of the frequencies around the spectral centroid.
+///
+/// # Arguments
+///
+/// * `frequencies` - The spectrum to analyze.
+///
+/// # Returns
+///
+/// The spread in Hz, or 0.0 if the spectrum is silent.
+pub fn spectral_spread(frequencies: &Frequencies) -> f64 {
+    let total: f64 = frequencies.amplitudes.iter().sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+
+    let centroid = spectral_centroid(frequencies);
+    let variance = frequencies
+        .frequencies
+        .iter()
+        .zip(frequencies.amplitudes.iter())
+        .map(|(f, a)| (f - centroid) * (f - centroid) * a)
+        .sum::<f64>()
+        / total;
+
+    variance.sqrt()
+}
+
+/// Calculates the spectral rolloff, the lowest frequency below which `percentage`
+/// of the total spectral energy is contained.
+///
+/// # Arguments
+///
+/// * `frequencies` - The spectrum to analyze.
+/// * `percentage` - The fraction of energy to include, between 0.0 and 1.0 (0.85 is common).
+///
+/// # Returns
+///
+/// The rolloff frequency in Hz, or 0.0 if the spectrum is silent.
+///
+/// # Panics
+///
+/// If `percentage` is not within 0.0..=1.0.
+pub fn spectral_rolloff(frequencies: &Frequencies, percentage: f64) -> f64 {
+    assert!(
+        (0.0..=1.0).contains(&percentage),
+        "Rolloff percentage must be between 0.0 and 1.0."
+    );
+
+    let total: f64 = frequencies.amplitudes.iter().map(|a| a * a).sum();
+    if total <= 0.0 {
+        return 0.0;
+    }
+
+    let threshold = total * percentage;
+    let mut cumulative = 0.0;
+    for (f, a) in frequencies
+        .frequencies
+        .iter()
+        .zip(frequencies.amplitudes.iter())
+    {
+        cumulative += a * a;
+        if cumulative >= threshold {
+            return *f;
+        }
+    }
+
+    frequencies.frequencies.last().copied().unwrap_or(0.0)
+}
+
+/// Calculates the spectral flatness (Wiener entropy), the ratio between the geometric and
+/// the arithmetic mean of the power spectrum.
+///
+/// Values close to 1.0 indicate a noise-like spectrum, values close to 0.0 a tonal one.
+///
+/// # Arguments
+///
+/// * `frequencies` - The spectrum to analyze.
+///
+/// # Returns
+///
+/// The flatness between 0.0 and 1.0, or 0.0 if the spectrum is silent.
+pub fn spectral_flatness(frequencies: &Frequencies) -> f64 {
+    // Floor the power so a single empty bin does not force the geometric mean to zero
+    const POWER_FLOOR: f64 = 1e-20;
+
+    let n = frequencies.amplitudes.len();
+    if n == 0 {
+        return 0.0;
+    }
+
+    let arithmetic_mean = frequencies.amplitudes.iter().map(|a| a * a).sum::<f64>() / n as f64;
+    if arithmetic_mean <= POWER_FLOOR {
+        return 0.0;
+    }
+
+    let log_mean = frequencies
+        .amplitudes
+        .iter()
+        .map(|a| (a * a).max(POWER_FLOOR).ln())
+        .sum::<f64>()
+        / n as f64;
+
+    log_mean.exp() / arithmetic_mean
+}
+
+/// Calculates the spectral crest factor, the ratio between the largest amplitude and the
+/// mean amplitude of a frame.
+///
+/// # Arguments
+///
+/// * `frequencies` - The spectrum to analyze.
+///
+/// # Returns
+///
+/// The crest factor (1.0 for a perfectly flat spectrum), or 0.0 if the spectrum is silent.
+pub fn spectral_crest(frequencies: &Frequencies) -> f64 {
+    let n = frequencies.amplitudes.len();
+    if n == 0 {
+        return 0.0;
+    }
+
+    let mean = frequencies.amplitudes.iter().sum::<f64>() / n as f64;
+    if mean <= 0.0 {
+        return 0.0;
+    }
+
+    let max = frequencies
+        .amplitudes
+        .iter()
+        .fold(0.0_f64, |max, &a| max.max(a));
+    max / mean
+}
+
+/// Calculates the spectral flux between two consecutive frames.
+///
+/// Only increases in amplitude are counted (half-wave rectification), which makes the flux
+/// peak at note onsets rather than at note releases.
+///
+/// # Arguments
+///
+/// * `previous` - The earlier of the two frames.
+/// * `current` - The later of the two frames.
+///
+/// # Returns
+///
+/// The sum of the positive amplitude differences between the frames.
+///
+/// # Panics
+///
+/// If the frames do not have the same number of bins.
+pub fn spectral_flux(previous: &Frequencies, current: &Frequencies) -> f64 {
+    assert_eq!(
+        previous.amplitudes.len(),
+        current.amplitudes.len(),
+        "Frames must have the same number of bins."
+    );
+
+    previous
+        .amplitudes
+        .iter()
+        .zip(current.amplitudes.iter())
+        .map(|(p, c)| (c - p).max(0.0))
+        .sum()
+}
+
+/// Calculates the zero-crossing rate of a chunk of samples.
+///
+/// # Arguments
+///
+/// * `samples` - The time-domain samples.
+///
+/// # Returns
+///
+/// The fraction of consecutive sample pairs that change sign, between 0.0 and 1.0.
+pub fn zero_crossing_rate(samples: &[f64]) -> f64 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+
+    let crossings = samples
+        .windows(2)
+        .filter(|pair| (pair[0] >= 0.0) != (pair[1] >= 0.0))
+        .count();
+    crossings as f64 / (samples.len() - 1) as f64
+}
+
+/// Calculates the root mean square (RMS) level of a chunk of samples.
+///
+/// # Arguments
+///
+/// * `samples` - The time-domain samples.
+///
+/// # Returns
+///
+/// The RMS level, or 0.0 for an empty chunk.
+pub fn rms(samples: &[f64]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    (samples.iter().map(|s| s * s).sum::<f64>() / samples.len() as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fft, get_frequencies};
+    use assert_float_eq::assert_float_absolute_eq;
+
+    /// Builds a spectrum with bins every 100 Hz and the given amplitudes.
+    fn spectrum(amplitudes: &[f64]) -> Frequencies {
+        Frequencies {
+            frequencies: (0..amplitudes.len()).map(|i| i as f64 * 100.0).collect(),
+            amplitudes: amplitudes.to_vec(),
+            total_samples: amplitudes.len() * 2,
+            sample_rate: (amplitudes.len() * 200) as u32,
+            start_time: 0.0,
+        }
+    }
+
+    #[test]
+    fn centroid_of_single_peak() {
+        let f = spectrum(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_float_absolute_eq!(spectral_centroid(&f), 300.0, 1e-9);
+        assert_float_absolute_eq!(spectral_spread(&f), 0.0, 1e-9);
+    }
+
+    #[test]
+    fn centroid_and_spread_of_two_peaks() {
+        let f = spectrum(&[0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_float_absolute_eq!(spectral_centroid(&f), 200.0, 1e-9);
+        assert_float_absolute_eq!(spectral_spread(&f), 100.0, 1e-9);
+    }
+
+    #[test]
+    fn centroid_of_real_sine() {
+        // 8 cycles over 64 samples at 6400 Hz lands exactly on the 800 Hz bin
+        let samples: Vec<f64> = (0..64)
+            .map(|i| (2.0 * std::f64::consts::PI * 8.0 * i as f64 / 64.0).sin())
+            .collect();
+        let f = get_frequencies(&fft(&samples), 6400);
+        assert_float_absolute_eq!(spectral_centroid(&f), 800.0, 1e-6);
+    }
+
+    #[test]
+    fn rolloff_percentages() {
+        let f = spectrum(&[1.0, 1.0, 1.0, 1.0]);
+        assert_float_absolute_eq!(spectral_rolloff(&f, 0.5), 100.0, 1e-9);
+        assert_float_absolute_eq!(spectral_rolloff(&f, 0.75), 200.0, 1e-9);
+        assert_float_absolute_eq!(spectral_rolloff(&f, 1.0), 300.0, 1e-9);
+    }
+
+    #[test]
+    fn flatness_of_flat_and_tonal_spectra() {
+        let flat = spectrum(&[0.5; 16]);
+        assert_float_absolute_eq!(spectral_flatness(&flat), 1.0, 1e-9);
+
+        let mut tonal = vec![0.0; 16];
+        tonal[4] = 1.0;
+        assert!(spectral_flatness(&spectrum(&tonal)) < 1e-6);
+    }
+
+    #[test]
+    fn crest_of_flat_and_tonal_spectra() {
+        assert_float_absolute_eq!(spectral_crest(&spectrum(&[0.5; 8])), 1.0, 1e-9);
+
+        let mut tonal = vec![0.0; 8];
+        tonal[2] = 1.0;
+        assert_float_absolute_eq!(spectral_crest(&spectrum(&tonal)), 8.0, 1e-9);
+    }
+
+    #[test]
+    fn flux_ignores_decreases() {
+        let previous = spectrum(&[1.0, 0.0, 0.5, 0.0]);
+        let current = spectrum(&[0.0, 1.0, 1.0, 0.0]);
+        assert_float_absolute_eq!(spectral_flux(&previous, &current), 1.5, 1e-9);
+        assert_float_absolute_eq!(spectral_flux(&current, &current), 0.0, 1e-9);
+    }
+
+    #[test]
+    fn silent_spectrum_features_are_zero() {
+        let f = spectrum(&[0.0; 8]);
+        assert_eq!(spectral_centroid(&f), 0.0);
+        assert_eq!(spectral_spread(&f), 0.0);
+        assert_eq!(spectral_rolloff(&f, 0.85), 0.0);
+        assert_eq!(spectral_flatness(&f), 0.0);
+        assert_eq!(spectral_crest(&f), 0.0);
+    }
+
+    #[test]
+    fn zero_crossing_rate_of_alternating_signal() {
+        assert_float_absolute_eq!(zero_crossing_rate(&[1.0, -1.0, 1.0, -1.0, 1.0]), 1.0, 1e-9);
+        assert_float_absolute_eq!(zero_crossing_rate(&[1.0, 1.0, -1.0, -1.0, 1.0]), 0.5, 1e-9);
+        assert_eq!(zero_crossing_rate(&[1.0]), 0.0);
+    }
+
+    #[test]
+    fn rms_of_constant_and_sine() {
+        assert_float_absolute_eq!(rms(&[0.5; 16]), 0.5, 1e-9);
+
+        let sine: Vec<f64> = (0..1024)
+            .map(|i| (2.0 * std::f64::consts::PI * 4.0 * i as f64 / 1024.0).sin())
+            .collect();
+        assert_float_absolute_eq!(rms(&sine), 1.0 / 2.0_f64.sqrt(), 1e-9);
+    }
+}
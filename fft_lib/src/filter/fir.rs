@@ -0,0 +1,241 @@
+//! Finite impulse response (FIR) filters designed with the windowed-sinc method.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+use super::Filter;
+use crate::window::Window;
+
+/// A FIR filter that convolves the input with its taps.
+///
+/// The designed filters are symmetric, so they have a linear phase and delay every
+/// frequency by `(taps - 1) / 2` samples.
+pub struct FirFilter {
+    taps: Vec<f64>,
+    history: Vec<f64>,
+    position: usize,
+}
+
+impl FirFilter {
+    /// Creates a FIR filter from its taps (the impulse response).
+    ///
+    /// # Panics
+    ///
+    /// If there are no taps.
+    pub fn new(taps: Vec<f64>) -> Self {
+        assert!(!taps.is_empty(), "A FIR filter needs at least one tap.");
+        let history = vec![0.0; taps.len()];
+        Self {
+            taps,
+            history,
+            position: 0,
+        }
+    }
+
+    /// Designs a lowpass filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `cutoff` - The cutoff frequency in Hz, where the gain is -6 dB.
+    /// * `sample_rate` - The sample rate the filter runs at.
+    /// * `taps` - The number of taps, must be odd. More taps give a steeper transition.
+    /// * `window` - The window that trades transition width for stopband attenuation.
+    ///
+    /// # Panics
+    ///
+    /// If the number of taps is even or the cutoff is not between 0 and the Nyquist frequency.
+    pub fn lowpass(cutoff: f64, sample_rate: u32, taps: usize, window: Window) -> Self {
+        Self::new(lowpass_taps(cutoff, sample_rate, taps, window))
+    }
+
+    /// Designs a highpass filter by spectrally inverting a lowpass filter.
+    ///
+    /// See `lowpass` for the arguments.
+    pub fn highpass(cutoff: f64, sample_rate: u32, taps: usize, window: Window) -> Self {
+        Self::new(invert(lowpass_taps(cutoff, sample_rate, taps, window)))
+    }
+
+    /// Designs a bandpass filter as the difference of two lowpass filters.
+    ///
+    /// # Arguments
+    ///
+    /// * `low` - The lower edge of the passband in Hz.
+    /// * `high` - The upper edge of the passband in Hz.
+    ///
+    /// See `lowpass` for the other arguments.
+    pub fn bandpass(low: f64, high: f64, sample_rate: u32, taps: usize, window: Window) -> Self {
+        assert!(
+            low < high,
+            "The lower band edge must be below the upper one."
+        );
+        let wide = lowpass_taps(high, sample_rate, taps, window);
+        let narrow = lowpass_taps(low, sample_rate, taps, window);
+        Self::new(wide.iter().zip(narrow.iter()).map(|(w, n)| w - n).collect())
+    }
+
+    /// Designs a bandstop (band-reject) filter by spectrally inverting a bandpass filter.
+    ///
+    /// See `bandpass` for the arguments.
+    pub fn bandstop(low: f64, high: f64, sample_rate: u32, taps: usize, window: Window) -> Self {
+        let bandpass = Self::bandpass(low, high, sample_rate, taps, window);
+        Self::new(invert(bandpass.taps))
+    }
+
+    pub fn taps(&self) -> &[f64] {
+        &self.taps
+    }
+
+    /// Filters a single sample.
+    pub fn process_sample(&mut self, sample: f64) -> f64 {
+        let length = self.taps.len();
+        self.history[self.position] = sample;
+
+        // history[position] is the newest sample, walk backwards through the ring
+        let mut output = 0.0;
+        for (i, tap) in self.taps.iter().enumerate() {
+            output += tap * self.history[(self.position + length - i) % length];
+        }
+
+        self.position = (self.position + 1) % length;
+        output
+    }
+}
+
+impl Filter for FirFilter {
+    fn process(&mut self, input: &[f64]) -> Vec<f64> {
+        input.iter().map(|&s| self.process_sample(s)).collect()
+    }
+
+    fn reset(&mut self) {
+        self.history.iter_mut().for_each(|h| *h = 0.0);
+        self.position = 0;
+    }
+
+    fn sections(&self) -> Vec<(Vec<f64>, Vec<f64>)> {
+        vec![(self.taps.clone(), vec![1.0])]
+    }
+}
+
+fn lowpass_taps(cutoff: f64, sample_rate: u32, taps: usize, window: Window) -> Vec<f64> {
+    assert!(taps % 2 == 1, "The number of taps must be odd.");
+    let nyquist = sample_rate as f64 / 2.0;
+    assert!(
+        cutoff > 0.0 && cutoff < nyquist,
+        "Cutoff must be between 0 and the Nyquist frequency."
+    );
+
+    let normalized = cutoff / sample_rate as f64;
+    let center = (taps / 2) as f64;
+    let window = window.generate_symmetric(taps);
+
+    let mut coefficients: Vec<f64> = (0..taps)
+        .map(|i| {
+            let x = i as f64 - center;
+            let sinc = if x == 0.0 {
+                2.0 * normalized
+            } else {
+                (2.0 * PI * normalized * x).sin() / (PI * x)
+            };
+            sinc * window[i]
+        })
+        .collect();
+
+    // Normalize for exactly unity gain at DC
+    let sum: f64 = coefficients.iter().sum();
+    coefficients.iter_mut().for_each(|c| *c /= sum);
+    coefficients
+}
+
+/// Spectral inversion: turns a filter with response H into one with response 1 - H.
+fn invert(mut taps: Vec<f64>) -> Vec<f64> {
+    taps.iter_mut().for_each(|t| *t = -*t);
+    let center = taps.len() / 2;
+    taps[center] += 1.0;
+    taps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FrequencyResponse;
+
+    fn gain_at(response: &FrequencyResponse, frequency: f64) -> f64 {
+        let index = response
+            .frequencies
+            .iter()
+            .position(|f| *f >= frequency)
+            .unwrap();
+        response.magnitude_db[index]
+    }
+
+    #[test]
+    fn lowpass_response() {
+        let filter = FirFilter::lowpass(1000.0, 16000, 101, Window::Blackman);
+        let response = filter.frequency_response(1024, 16000);
+
+        assert!(gain_at(&response, 0.0).abs() < 0.01);
+        assert!(gain_at(&response, 500.0).abs() < 0.1);
+        assert!((gain_at(&response, 1000.0) + 6.0).abs() < 0.5);
+        assert!(gain_at(&response, 2000.0) < -60.0);
+    }
+
+    #[test]
+    fn highpass_and_bandstop_responses() {
+        let highpass = FirFilter::highpass(2000.0, 16000, 101, Window::Blackman);
+        let response = highpass.frequency_response(1024, 16000);
+        assert!(gain_at(&response, 500.0) < -60.0);
+        assert!(gain_at(&response, 5000.0).abs() < 0.1);
+
+        let bandstop = FirFilter::bandstop(2000.0, 4000.0, 16000, 101, Window::Blackman);
+        let response = bandstop.frequency_response(1024, 16000);
+        assert!(gain_at(&response, 500.0).abs() < 0.1);
+        assert!(gain_at(&response, 3000.0) < -60.0);
+        assert!(gain_at(&response, 6000.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn bandpass_keeps_only_its_band() {
+        let filter = FirFilter::bandpass(2000.0, 4000.0, 16000, 101, Window::Blackman);
+        let response = filter.frequency_response(1024, 16000);
+        assert!(gain_at(&response, 500.0) < -60.0);
+        assert!(gain_at(&response, 3000.0).abs() < 0.1);
+        assert!(gain_at(&response, 6000.0) < -60.0);
+    }
+
+    #[test]
+    fn linear_phase_delay() {
+        let filter = FirFilter::lowpass(2000.0, 16000, 31, Window::Hamming);
+        let response = filter.frequency_response(256, 16000);
+        // The phase of a symmetric filter falls by 15 samples' worth per radian of frequency
+        for k in 1..20 {
+            let omega = 2.0 * PI * response.frequencies[k] / 16000.0;
+            assert!((response.phase[k] + 15.0 * omega).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn chunked_filtering_matches_single_pass() {
+        let input: Vec<f64> = (0..500).map(|i| ((i * 37) % 11) as f64 - 5.0).collect();
+        let mut whole = FirFilter::lowpass(3000.0, 16000, 21, Window::Hamming);
+        let expected = whole.process(&input);
+
+        let mut chunked = FirFilter::lowpass(3000.0, 16000, 21, Window::Hamming);
+        let output: Vec<f64> = input.chunks(64).flat_map(|c| chunked.process(c)).collect();
+        assert_eq!(output, expected);
+
+        chunked.reset();
+        assert_eq!(chunked.process(&input), expected);
+    }
+
+    #[test]
+    fn impulse_response_is_the_taps() {
+        let mut filter = FirFilter::new(vec![0.5, 0.25, -0.125]);
+        assert_eq!(
+            filter.process(&[1.0, 0.0, 0.0, 0.0]),
+            vec![0.5, 0.25, -0.125, 0.0]
+        );
+    }
+}
@@ -0,0 +1,478 @@
+//! Infinite impulse response (IIR) filters: single biquads from the RBJ audio EQ cookbook
+//! and Butterworth and Chebyshev type I designs built as cascades of biquads.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+use super::Filter;
+
+/// A second order IIR section, run in transposed direct form II.
+#[derive(Debug, Clone)]
+pub struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    /// Creates a biquad from its coefficients.
+    ///
+    /// The transfer function is `(b0 + b1 z^-1 + b2 z^-2) / (a0 + a1 z^-1 + a2 z^-2)`, and all
+    /// coefficients are divided by `a0`.
+    ///
+    /// # Panics
+    ///
+    /// If `a0` is 0.
+    pub fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        assert!(a[0] != 0.0, "The a0 coefficient can not be 0.");
+        Self {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [1.0, a[1] / a[0], a[2] / a[0]],
+            state: [0.0; 2],
+        }
+    }
+
+    /// Designs a second order lowpass filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `frequency` - The cutoff frequency in Hz.
+    /// * `q` - The quality factor, 1/sqrt(2) gives a Butterworth response.
+    /// * `sample_rate` - The sample rate the filter runs at.
+    pub fn lowpass(frequency: f64, q: f64, sample_rate: u32) -> Self {
+        let (cos, alpha) = intermediates(frequency, q, sample_rate);
+        Self::new(
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Designs a second order highpass filter. See `lowpass` for the arguments.
+    pub fn highpass(frequency: f64, q: f64, sample_rate: u32) -> Self {
+        let (cos, alpha) = intermediates(frequency, q, sample_rate);
+        Self::new(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Designs a bandpass filter with unity gain at the center frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `frequency` - The center frequency in Hz.
+    /// * `q` - The quality factor, the center frequency divided by the bandwidth.
+    /// * `sample_rate` - The sample rate the filter runs at.
+    pub fn bandpass(frequency: f64, q: f64, sample_rate: u32) -> Self {
+        let (cos, alpha) = intermediates(frequency, q, sample_rate);
+        Self::new([alpha, 0.0, -alpha], [1.0 + alpha, -2.0 * cos, 1.0 - alpha])
+    }
+
+    /// Designs a notch filter that removes the center frequency. See `bandpass` for the arguments.
+    pub fn notch(frequency: f64, q: f64, sample_rate: u32) -> Self {
+        let (cos, alpha) = intermediates(frequency, q, sample_rate);
+        Self::new(
+            [1.0, -2.0 * cos, 1.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Designs a peaking equalizer that boosts or cuts around a center frequency.
+    ///
+    /// # Arguments
+    ///
+    /// * `frequency` - The center frequency in Hz.
+    /// * `q` - The quality factor, higher values affect a narrower band.
+    /// * `gain_db` - The gain at the center frequency in dB, negative for a cut.
+    /// * `sample_rate` - The sample rate the filter runs at.
+    pub fn peaking(frequency: f64, q: f64, gain_db: f64, sample_rate: u32) -> Self {
+        let (cos, alpha) = intermediates(frequency, q, sample_rate);
+        let amplitude = 10f64.powf(gain_db / 40.0);
+        Self::new(
+            [1.0 + alpha * amplitude, -2.0 * cos, 1.0 - alpha * amplitude],
+            [1.0 + alpha / amplitude, -2.0 * cos, 1.0 - alpha / amplitude],
+        )
+    }
+
+    /// Designs a low shelf that applies `gain_db` below the corner frequency.
+    ///
+    /// See `peaking` for the arguments, `q` = 1/sqrt(2) gives the steepest slope without overshoot.
+    pub fn low_shelf(frequency: f64, q: f64, gain_db: f64, sample_rate: u32) -> Self {
+        let (cos, alpha) = intermediates(frequency, q, sample_rate);
+        let amplitude = 10f64.powf(gain_db / 40.0);
+        let beta = 2.0 * amplitude.sqrt() * alpha;
+        let (plus, minus) = (amplitude + 1.0, amplitude - 1.0);
+        Self::new(
+            [
+                amplitude * (plus - minus * cos + beta),
+                2.0 * amplitude * (minus - plus * cos),
+                amplitude * (plus - minus * cos - beta),
+            ],
+            [
+                plus + minus * cos + beta,
+                -2.0 * (minus + plus * cos),
+                plus + minus * cos - beta,
+            ],
+        )
+    }
+
+    /// Designs a high shelf that applies `gain_db` above the corner frequency.
+    ///
+    /// See `peaking` for the arguments.
+    pub fn high_shelf(frequency: f64, q: f64, gain_db: f64, sample_rate: u32) -> Self {
+        let (cos, alpha) = intermediates(frequency, q, sample_rate);
+        let amplitude = 10f64.powf(gain_db / 40.0);
+        let beta = 2.0 * amplitude.sqrt() * alpha;
+        let (plus, minus) = (amplitude + 1.0, amplitude - 1.0);
+        Self::new(
+            [
+                amplitude * (plus + minus * cos + beta),
+                -2.0 * amplitude * (minus + plus * cos),
+                amplitude * (plus + minus * cos - beta),
+            ],
+            [
+                plus - minus * cos + beta,
+                2.0 * (minus - plus * cos),
+                plus - minus * cos - beta,
+            ],
+        )
+    }
+
+    /// The normalized numerator and denominator coefficients.
+    pub fn coefficients(&self) -> ([f64; 3], [f64; 3]) {
+        (self.b, self.a)
+    }
+
+    /// Filters a single sample.
+    pub fn process_sample(&mut self, sample: f64) -> f64 {
+        let output = self.b[0] * sample + self.state[0];
+        self.state[0] = self.b[1] * sample - self.a[1] * output + self.state[1];
+        self.state[1] = self.b[2] * sample - self.a[2] * output;
+        output
+    }
+}
+
+impl Filter for Biquad {
+    fn process(&mut self, input: &[f64]) -> Vec<f64> {
+        input.iter().map(|&s| self.process_sample(s)).collect()
+    }
+
+    fn reset(&mut self) {
+        self.state = [0.0; 2];
+    }
+
+    fn sections(&self) -> Vec<(Vec<f64>, Vec<f64>)> {
+        vec![(self.b.to_vec(), self.a.to_vec())]
+    }
+}
+
+/// A chain of biquads, which keeps high order IIR filters numerically stable.
+#[derive(Debug, Clone)]
+pub struct BiquadCascade {
+    pub sections: Vec<Biquad>,
+}
+
+impl BiquadCascade {
+    pub fn new(sections: Vec<Biquad>) -> Self {
+        Self { sections }
+    }
+
+    /// Designs a Butterworth lowpass filter, which is maximally flat in the passband.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The filter order, the roll-off is 6 dB per octave per order.
+    /// * `cutoff` - The -3 dB frequency in Hz.
+    /// * `sample_rate` - The sample rate the filter runs at.
+    ///
+    /// # Panics
+    ///
+    /// If the order is 0 or the cutoff is not between 0 and the Nyquist frequency.
+    pub fn butterworth_lowpass(order: usize, cutoff: f64, sample_rate: u32) -> Self {
+        design(&butterworth_poles(order), 1.0, cutoff, sample_rate, false)
+    }
+
+    /// Designs a Butterworth highpass filter. See `butterworth_lowpass` for the arguments.
+    pub fn butterworth_highpass(order: usize, cutoff: f64, sample_rate: u32) -> Self {
+        design(&butterworth_poles(order), 1.0, cutoff, sample_rate, true)
+    }
+
+    /// Designs a Chebyshev type I lowpass filter, which trades passband ripple for a
+    /// steeper transition than a Butterworth filter of the same order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The filter order.
+    /// * `ripple_db` - The peak-to-peak passband ripple in dB.
+    /// * `cutoff` - The passband edge in Hz, where the gain last equals `-ripple_db`.
+    /// * `sample_rate` - The sample rate the filter runs at.
+    ///
+    /// # Panics
+    ///
+    /// If the order is 0, the ripple is not positive or the cutoff is not between 0 and
+    /// the Nyquist frequency.
+    pub fn chebyshev1_lowpass(order: usize, ripple_db: f64, cutoff: f64, sample_rate: u32) -> Self {
+        let (poles, gain) = chebyshev1_poles(order, ripple_db);
+        design(&poles, gain, cutoff, sample_rate, false)
+    }
+
+    /// Designs a Chebyshev type I highpass filter. See `chebyshev1_lowpass` for the arguments.
+    pub fn chebyshev1_highpass(
+        order: usize,
+        ripple_db: f64,
+        cutoff: f64,
+        sample_rate: u32,
+    ) -> Self {
+        let (poles, gain) = chebyshev1_poles(order, ripple_db);
+        design(&poles, gain, cutoff, sample_rate, true)
+    }
+}
+
+impl Filter for BiquadCascade {
+    fn process(&mut self, input: &[f64]) -> Vec<f64> {
+        self.sections
+            .iter_mut()
+            .fold(input.to_vec(), |signal, section| section.process(&signal))
+    }
+
+    fn reset(&mut self) {
+        self.sections.iter_mut().for_each(|s| s.reset());
+    }
+
+    fn sections(&self) -> Vec<(Vec<f64>, Vec<f64>)> {
+        self.sections.iter().flat_map(|s| s.sections()).collect()
+    }
+}
+
+/// The cosine of the normalized frequency and the bandwidth term shared by the cookbook designs.
+fn intermediates(frequency: f64, q: f64, sample_rate: u32) -> (f64, f64) {
+    assert!(
+        frequency > 0.0 && frequency < sample_rate as f64 / 2.0,
+        "Frequency must be between 0 and the Nyquist frequency."
+    );
+    assert!(q > 0.0, "Q must be greater than 0.");
+    let omega = 2.0 * PI * frequency / sample_rate as f64;
+    (omega.cos(), omega.sin() / (2.0 * q))
+}
+
+/// The analog prototype poles (cutoff 1 rad/s) in the upper half plane, as (real, imag).
+/// Odd orders end with the single real pole.
+fn butterworth_poles(order: usize) -> Vec<(f64, f64)> {
+    assert!(order > 0, "The filter order must be greater than 0.");
+    (1..=order.div_ceil(2))
+        .map(|k| {
+            let theta = PI * (2 * k - 1) as f64 / (2 * order) as f64;
+            (-theta.sin(), theta.cos())
+        })
+        .collect()
+}
+
+/// The Chebyshev type I prototype poles and the gain that puts the ripple peaks at 0 dB.
+fn chebyshev1_poles(order: usize, ripple_db: f64) -> (Vec<(f64, f64)>, f64) {
+    assert!(ripple_db > 0.0, "The ripple must be greater than 0 dB.");
+    let epsilon = (10f64.powf(ripple_db / 10.0) - 1.0).sqrt();
+    let mu = (1.0 / epsilon).asinh() / order as f64;
+
+    let poles = butterworth_poles(order)
+        .into_iter()
+        .map(|(re, im)| (re * mu.sinh(), im * mu.cosh()))
+        .collect();
+
+    // Odd orders start the ripple at 0 dB, even orders at the bottom of the ripple
+    let gain = if order.is_multiple_of(2) {
+        1.0 / (1.0 + epsilon * epsilon).sqrt()
+    } else {
+        1.0
+    };
+    (poles, gain)
+}
+
+/// Maps the prototype poles to digital sections with the bilinear transform.
+fn design(
+    poles: &[(f64, f64)],
+    gain: f64,
+    cutoff: f64,
+    sample_rate: u32,
+    highpass: bool,
+) -> BiquadCascade {
+    let fs = sample_rate as f64;
+    assert!(
+        cutoff > 0.0 && cutoff < fs / 2.0,
+        "Cutoff must be between 0 and the Nyquist frequency."
+    );
+
+    // Prewarp so the digital filter has its cutoff exactly at `cutoff`
+    let k = 2.0 * fs;
+    let omega = k * (PI * cutoff / fs).tan();
+
+    let mut sections: Vec<Biquad> = poles
+        .iter()
+        .map(|&(re, im)| {
+            // Analog sections as (B0 s^2 + B1 s + B2) / (A0 s^2 + A1 s + A2)
+            let (numerator, denominator) = if im.abs() < 1e-12 {
+                let sigma = -re;
+                if highpass {
+                    ([0.0, 1.0, 0.0], [0.0, 1.0, omega / sigma])
+                } else {
+                    ([0.0, 0.0, sigma * omega], [0.0, 1.0, sigma * omega])
+                }
+            } else {
+                let power = re * re + im * im;
+                if highpass {
+                    (
+                        [1.0, 0.0, 0.0],
+                        [1.0, -2.0 * re * omega / power, omega * omega / power],
+                    )
+                } else {
+                    (
+                        [0.0, 0.0, power * omega * omega],
+                        [1.0, -2.0 * re * omega, power * omega * omega],
+                    )
+                }
+            };
+            let second_order = denominator[0] != 0.0;
+            Biquad::new(
+                bilinear(numerator, k, second_order),
+                bilinear(denominator, k, second_order),
+            )
+        })
+        .collect();
+
+    sections[0].b.iter_mut().for_each(|b| *b *= gain);
+    BiquadCascade::new(sections)
+}
+
+/// Substitutes s = k (1 - z^-1) / (1 + z^-1) into a polynomial `a0 s^2 + a1 s + a2` of a
+/// first or second order section.
+fn bilinear(analog: [f64; 3], k: f64, second_order: bool) -> [f64; 3] {
+    let [a0, a1, a2] = analog;
+    if !second_order {
+        // Multiplied by (1 + z^-1)
+        [a1 * k + a2, a2 - a1 * k, 0.0]
+    } else {
+        // Multiplied by (1 + z^-1)^2
+        let k2 = k * k;
+        [
+            a0 * k2 + a1 * k + a2,
+            2.0 * a2 - 2.0 * a0 * k2,
+            a0 * k2 - a1 * k + a2,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    // With 1024 points at 16 kHz every multiple of 7.8125 Hz lands exactly on a bin
+    const RATE: u32 = 16000;
+    const POINTS: usize = 1024;
+
+    fn bin(frequency: f64) -> usize {
+        (frequency * (2 * POINTS) as f64 / RATE as f64).round() as usize
+    }
+
+    #[test]
+    fn peaking_reaches_gain_at_center() {
+        let filter = Biquad::peaking(1000.0, 2.0, 6.0, RATE);
+        let response = filter.frequency_response(POINTS, RATE);
+        assert_float_absolute_eq!(response.magnitude_db[bin(1000.0)], 6.0, 1e-9);
+        assert!(response.magnitude_db[0].abs() < 0.05);
+
+        let cut = Biquad::peaking(1000.0, 2.0, -6.0, RATE);
+        let response = cut.frequency_response(POINTS, RATE);
+        assert_float_absolute_eq!(response.magnitude_db[bin(1000.0)], -6.0, 1e-9);
+    }
+
+    #[test]
+    fn shelves_reach_gain_at_the_edges() {
+        let low = Biquad::low_shelf(500.0, 0.707, 9.0, RATE).frequency_response(POINTS, RATE);
+        assert_float_absolute_eq!(low.magnitude_db[0], 9.0, 1e-9);
+        assert!(low.magnitude_db[POINTS - 1].abs() < 0.05);
+
+        let high = Biquad::high_shelf(4000.0, 0.707, -9.0, RATE).frequency_response(POINTS, RATE);
+        assert!(high.magnitude_db[0].abs() < 1e-9);
+        assert!((high.magnitude_db[POINTS - 1] + 9.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn notch_removes_center_frequency() {
+        let filter = Biquad::notch(1000.0, 5.0, RATE);
+        let response = filter.frequency_response(POINTS, RATE);
+        assert!(response.magnitude_db[bin(1000.0)] < -100.0);
+        assert!(response.magnitude_db[bin(250.0)].abs() < 0.1);
+        assert!(response.magnitude_db[bin(4000.0)].abs() < 0.1);
+    }
+
+    #[test]
+    fn butterworth_is_3_db_down_at_cutoff() {
+        for order in 1..=6 {
+            let lowpass = BiquadCascade::butterworth_lowpass(order, 1000.0, RATE);
+            let response = lowpass.frequency_response(POINTS, RATE);
+            assert_float_absolute_eq!(response.magnitude_db[0], 0.0, 1e-9);
+            assert_float_absolute_eq!(response.magnitude_db[bin(1000.0)], -3.0103, 1e-3);
+
+            let highpass = BiquadCascade::butterworth_highpass(order, 1000.0, RATE);
+            let response = highpass.frequency_response(POINTS, RATE);
+            assert_float_absolute_eq!(response.magnitude_db[bin(1000.0)], -3.0103, 1e-3);
+            assert!(response.magnitude_db[POINTS - 1].abs() < 1e-3);
+        }
+
+        // An octave above the cutoff a 4th order filter is down by about 24 dB
+        let response =
+            BiquadCascade::butterworth_lowpass(4, 1000.0, RATE).frequency_response(POINTS, RATE);
+        assert!(response.magnitude_db[bin(2000.0)] < -24.0);
+    }
+
+    #[test]
+    fn chebyshev_ripple_stays_within_spec() {
+        for order in [3, 4] {
+            let filter = BiquadCascade::chebyshev1_lowpass(order, 1.0, 1000.0, RATE);
+            let response = filter.frequency_response(POINTS, RATE);
+            let passband = &response.magnitude_db[..=bin(1000.0)];
+
+            assert!(passband.iter().all(|g| *g <= 1e-9 && *g >= -1.0 - 1e-9));
+            assert_float_absolute_eq!(passband[bin(1000.0)], -1.0, 1e-6);
+            // Steeper than the Butterworth filter of the same order
+            let butterworth = BiquadCascade::butterworth_lowpass(order, 1000.0, RATE)
+                .frequency_response(POINTS, RATE);
+            assert!(response.magnitude_db[bin(2000.0)] < butterworth.magnitude_db[bin(2000.0)]);
+        }
+
+        let highpass = BiquadCascade::chebyshev1_highpass(4, 0.5, 2000.0, RATE);
+        let response = highpass.frequency_response(POINTS, RATE);
+        let passband = &response.magnitude_db[bin(2000.0)..];
+        assert!(passband.iter().all(|g| *g <= 1e-9 && *g >= -0.5 - 1e-9));
+    }
+
+    #[test]
+    fn filtering_matches_response() {
+        let mut filter = BiquadCascade::butterworth_lowpass(4, 1000.0, RATE);
+        let amplitude = |filter: &mut BiquadCascade, frequency: f64| {
+            filter.reset();
+            let input: Vec<f64> = (0..8000)
+                .map(|i| (2.0 * PI * frequency * i as f64 / RATE as f64).sin())
+                .collect();
+            let output = filter.process(&input);
+            output[4000..].iter().fold(0.0f64, |m, s| m.max(s.abs()))
+        };
+
+        assert!((amplitude(&mut filter, 250.0) - 1.0).abs() < 0.01);
+        assert!(amplitude(&mut filter, 4000.0) < 0.01);
+    }
+
+    #[test]
+    fn chunked_filtering_matches_single_pass() {
+        let input: Vec<f64> = (0..500).map(|i| ((i * 37) % 11) as f64 - 5.0).collect();
+        let mut whole = BiquadCascade::chebyshev1_lowpass(5, 0.5, 3000.0, RATE);
+        let expected = whole.process(&input);
+
+        let mut chunked = BiquadCascade::chebyshev1_lowpass(5, 0.5, 3000.0, RATE);
+        let output: Vec<f64> = input.chunks(64).flat_map(|c| chunked.process(c)).collect();
+        assert_eq!(output, expected);
+    }
+}
@@ -0,0 +1,113 @@
+//! Digital filters: windowed-sinc FIR designs and IIR biquads and cascades.
+//!
+//! Every filter keeps its state between calls to `process`, so a stream can be filtered
+//! chunk by chunk before it reaches `fft`.
+
+pub mod fir;
+pub mod iir;
+
+use alloc::{vec, vec::Vec};
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{fft, unwrap_phase};
+
+/// The frequency response of a filter.
+#[derive(Debug, Clone)]
+pub struct FrequencyResponse {
+    /// The frequencies in Hz, from 0 up to (but excluding) the Nyquist frequency.
+    pub frequencies: Vec<f64>,
+    /// The linear gain at each frequency.
+    pub magnitude: Vec<f64>,
+    /// The gain in dB at each frequency.
+    pub magnitude_db: Vec<f64>,
+    /// The unwrapped phase shift in radians at each frequency.
+    pub phase: Vec<f64>,
+}
+
+pub trait Filter {
+    /// Filters a chunk of samples, continuing from the state left by the previous chunk.
+    fn process(&mut self, input: &[f64]) -> Vec<f64>;
+
+    /// Clears the filter state, as if no samples had been processed.
+    fn reset(&mut self);
+
+    /// The transfer function as a product of sections, each given as the numerator and
+    /// denominator coefficients in powers of z^-1.
+    fn sections(&self) -> Vec<(Vec<f64>, Vec<f64>)>;
+
+    /// Calculates the frequency response by transforming the coefficients with `fft`.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The number of frequencies to evaluate, must be a power of 2.
+    /// * `sample_rate` - The sample rate the filter runs at.
+    fn frequency_response(&self, points: usize, sample_rate: u32) -> FrequencyResponse {
+        if points == 0 || (points & (points - 1)) != 0 {
+            panic!("Number of points must be a power of 2 and greater than 0.");
+        }
+
+        let mut real = vec![1.0; points];
+        let mut imag = vec![0.0; points];
+        for (numerator, denominator) in self.sections() {
+            let (num_real, num_imag) = evaluate_polynomial(&numerator, points);
+            let (den_real, den_imag) = evaluate_polynomial(&denominator, points);
+
+            for k in 0..points {
+                // (real + j imag) * numerator / denominator
+                let denominator_power = den_real[k] * den_real[k] + den_imag[k] * den_imag[k];
+                let ratio_real =
+                    (num_real[k] * den_real[k] + num_imag[k] * den_imag[k]) / denominator_power;
+                let ratio_imag =
+                    (num_imag[k] * den_real[k] - num_real[k] * den_imag[k]) / denominator_power;
+                let new_real = real[k] * ratio_real - imag[k] * ratio_imag;
+                imag[k] = real[k] * ratio_imag + imag[k] * ratio_real;
+                real[k] = new_real;
+            }
+        }
+
+        let magnitude: Vec<f64> = real
+            .iter()
+            .zip(imag.iter())
+            .map(|(re, im)| (re * re + im * im).sqrt())
+            .collect();
+        let phases: Vec<f64> = real
+            .iter()
+            .zip(imag.iter())
+            .map(|(re, im)| im.atan2(*re))
+            .collect();
+
+        FrequencyResponse {
+            frequencies: (0..points)
+                .map(|k| k as f64 * sample_rate as f64 / (2 * points) as f64)
+                .collect(),
+            magnitude_db: magnitude
+                .iter()
+                .map(|m| 20.0 * m.max(1e-300).log10())
+                .collect(),
+            magnitude,
+            phase: unwrap_phase(&phases),
+        }
+    }
+}
+
+/// Evaluates a polynomial in z^-1 at `points` frequencies from 0 up to the Nyquist frequency.
+fn evaluate_polynomial(coefficients: &[f64], points: usize) -> (Vec<f64>, Vec<f64>) {
+    let size = (2 * points).max(coefficients.len().next_power_of_two());
+    let step = size / (2 * points);
+
+    let mut padded = coefficients.to_vec();
+    padded.resize(size, 0.0);
+    let spectrum = fft(&padded);
+
+    // fft divides by its length, undo that to get the plain polynomial values
+    let scale = size as f64;
+    (
+        (0..points)
+            .map(|k| spectrum.real[k * step] * scale)
+            .collect(),
+        (0..points)
+            .map(|k| spectrum.imag[k * step] * scale)
+            .collect(),
+    )
+}
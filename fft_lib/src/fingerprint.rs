@@ -0,0 +1,286 @@
+//! Landmark-based audio fingerprinting, after Wang, "An Industrial-Strength Audio Search
+//! Algorithm" (2003).
+//!
+//! The loudest points of the spectrogram survive noise and compression. Pairs of nearby
+//! peaks are hashed from their frequencies and their distance in time, which makes the
+//! hashes independent of where a clip starts. A clip matches a reference when many of its
+//! hashes occur in the reference at the same time offset.
+
+use alloc::collections::BTreeMap;
+use alloc::{vec, vec::Vec};
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::stft::Stft;
+use crate::window::Window;
+
+/// The sample rate fingerprints are computed at. Signals at other rates must be resampled
+/// first, or their hashes will not match.
+pub const SAMPLE_RATE: u32 = 11025;
+/// The STFT frame size.
+pub const FRAME_SIZE: usize = 1024;
+/// The number of samples between frames, which is the time resolution of the offsets.
+pub const HOP_SIZE: usize = 256;
+
+/// A peak must be the largest value within this many bins above and below it.
+const PEAK_BINS: usize = 12;
+/// A peak must be the largest value within this many frames before and after it.
+const PEAK_FRAMES: usize = 6;
+/// Peaks quieter than this magnitude are ignored, so silence gives no landmarks.
+const MIN_PEAK_MAGNITUDE: f64 = 1e-5;
+/// The number of later peaks each peak is paired with.
+const FAN_OUT: usize = 5;
+/// The largest distance in frames between the peaks of a pair (6 bits).
+const MAX_PAIR_FRAMES: usize = 63;
+/// The largest distance in bins between the peaks of a pair (7 bits with the sign).
+const MAX_PAIR_BINS: i64 = 63;
+
+/// A hashed pair of spectrogram peaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Landmark {
+    /// The frequency of the first peak, the frequency difference and the time difference
+    /// packed into 22 bits.
+    pub hash: u32,
+    /// The frame of the first peak.
+    pub frame: u32,
+}
+
+/// A reference that a clip matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    /// The index of the reference, in the order they were added.
+    pub reference: usize,
+    /// The frame of the reference where the clip starts.
+    pub offset: i64,
+    /// The number of landmarks that agree on the offset.
+    pub score: usize,
+}
+
+impl Match {
+    /// The time in seconds of the reference where the clip starts.
+    pub fn offset_seconds(&self) -> f64 {
+        (self.offset * HOP_SIZE as i64) as f64 / SAMPLE_RATE as f64
+    }
+}
+
+/// Finds the landmarks of a signal.
+///
+/// # Arguments
+///
+/// * `samples` - The signal at `SAMPLE_RATE`.
+///
+/// # Returns
+///
+/// The landmarks, ordered by frame.
+pub fn fingerprint(samples: &[f64]) -> Vec<Landmark> {
+    let stft = Stft::new(FRAME_SIZE, HOP_SIZE, Window::Hann);
+    let bins = FRAME_SIZE / 2;
+    let spectrogram: Vec<Vec<f64>> = stft
+        .analyze(samples)
+        .iter()
+        .map(|frame| {
+            (0..bins)
+                .map(|k| (frame.real[k] * frame.real[k] + frame.imag[k] * frame.imag[k]).sqrt())
+                .collect()
+        })
+        .collect();
+
+    let peaks = find_peaks(&spectrogram);
+    let mut landmarks = Vec::new();
+    for (i, &(frame, bin)) in peaks.iter().enumerate() {
+        let targets = peaks[i + 1..]
+            .iter()
+            .take_while(|(target_frame, _)| target_frame - frame <= MAX_PAIR_FRAMES)
+            .filter(|(target_frame, target_bin)| {
+                *target_frame > frame && (*target_bin as i64 - bin as i64).abs() <= MAX_PAIR_BINS
+            })
+            .take(FAN_OUT);
+        for &(target_frame, target_bin) in targets {
+            let bin_difference = (target_bin as i64 - bin as i64 + MAX_PAIR_BINS) as u32;
+            landmarks.push(Landmark {
+                hash: ((bin as u32) << 13) | (bin_difference << 6) | (target_frame - frame) as u32,
+                frame: frame as u32,
+            });
+        }
+    }
+    landmarks
+}
+
+/// The landmarks of a set of references, looked up by hash.
+#[derive(Debug, Clone, Default)]
+pub struct FingerprintIndex {
+    entries: BTreeMap<u32, Vec<(u32, u32)>>,
+    references: usize,
+}
+
+impl FingerprintIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the landmarks of a reference.
+    ///
+    /// # Returns
+    ///
+    /// The index of the reference, which `query` reports in its matches.
+    pub fn add(&mut self, landmarks: &[Landmark]) -> usize {
+        let reference = self.references;
+        for landmark in landmarks {
+            self.entries
+                .entry(landmark.hash)
+                .or_default()
+                .push((reference as u32, landmark.frame));
+        }
+        self.references += 1;
+        reference
+    }
+
+    /// The number of references added.
+    pub fn len(&self) -> usize {
+        self.references
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.references == 0
+    }
+
+    /// Matches the landmarks of a clip against the references.
+    ///
+    /// Every landmark found in a reference votes for the offset between the reference and
+    /// the clip. The score of a reference is the number of votes for its best offset.
+    ///
+    /// # Returns
+    ///
+    /// The best offset of every reference that shares a landmark with the clip, the highest
+    /// score first.
+    pub fn query(&self, landmarks: &[Landmark]) -> Vec<Match> {
+        let mut votes: BTreeMap<(usize, i64), usize> = BTreeMap::new();
+        for landmark in landmarks {
+            if let Some(entries) = self.entries.get(&landmark.hash) {
+                for &(reference, frame) in entries {
+                    let offset = frame as i64 - landmark.frame as i64;
+                    *votes.entry((reference as usize, offset)).or_default() += 1;
+                }
+            }
+        }
+
+        let mut best: Vec<Option<Match>> = vec![None; self.references];
+        for ((reference, offset), score) in votes {
+            if best[reference].is_none_or(|m| score > m.score) {
+                best[reference] = Some(Match {
+                    reference,
+                    offset,
+                    score,
+                });
+            }
+        }
+        let mut matches: Vec<Match> = best.into_iter().flatten().collect();
+        matches.sort_by_key(|m| core::cmp::Reverse(m.score));
+        matches
+    }
+}
+
+/// The peaks of a spectrogram as (frame, bin) pairs, ordered by frame then bin. A peak is
+/// the largest value within `PEAK_FRAMES` frames and `PEAK_BINS` bins.
+fn find_peaks(spectrogram: &[Vec<f64>]) -> Vec<(usize, usize)> {
+    // The maximum over frequency, then over time
+    let across_bins: Vec<Vec<f64>> = spectrogram
+        .iter()
+        .map(|frame| running_max(frame, PEAK_BINS))
+        .collect();
+    let bins = spectrogram.first().map_or(0, |frame| frame.len());
+    let mut neighbourhood = vec![vec![0.0; bins]; spectrogram.len()];
+    for k in 0..bins {
+        let track: Vec<f64> = across_bins.iter().map(|frame| frame[k]).collect();
+        for (frame, value) in neighbourhood
+            .iter_mut()
+            .zip(running_max(&track, PEAK_FRAMES))
+        {
+            frame[k] = value;
+        }
+    }
+
+    let mut peaks = Vec::new();
+    for (t, frame) in spectrogram.iter().enumerate() {
+        // Bin 0 is DC, which carries no pitch information
+        for k in 1..bins {
+            if frame[k] >= MIN_PEAK_MAGNITUDE && frame[k] == neighbourhood[t][k] {
+                peaks.push((t, k));
+            }
+        }
+    }
+    peaks
+}
+
+/// The maximum over the `reach` values on each side of every value.
+fn running_max(values: &[f64], reach: usize) -> Vec<f64> {
+    (0..values.len())
+        .map(|i| {
+            let low = i.saturating_sub(reach);
+            let high = (i + reach + 1).min(values.len());
+            values[low..high]
+                .iter()
+                .fold(f64::NEG_INFINITY, |max, &v| max.max(v))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::{music, Random};
+
+    #[test]
+    fn landmarks_do_not_depend_on_the_start() {
+        let samples = music(1, 4, SAMPLE_RATE);
+        let shifted = fingerprint(&samples[10 * HOP_SIZE..]);
+        let original = fingerprint(&samples);
+
+        // Away from the edges, every landmark of the shifted signal is found 10 frames later
+        let inner: Vec<&Landmark> = shifted
+            .iter()
+            .filter(|l| l.frame > 20 && (l.frame as usize) < 100)
+            .collect();
+        assert!(inner.len() > 50);
+        for landmark in inner {
+            assert!(original
+                .iter()
+                .any(|l| l.hash == landmark.hash && l.frame == landmark.frame + 10));
+        }
+    }
+
+    #[test]
+    fn noisy_clip_matches_its_reference() {
+        let references = [
+            music(1, 20, SAMPLE_RATE),
+            music(2, 20, SAMPLE_RATE),
+            music(3, 20, SAMPLE_RATE),
+        ];
+        let mut index = FingerprintIndex::new();
+        for reference in &references {
+            index.add(&fingerprint(reference));
+        }
+        assert_eq!(index.len(), 3);
+
+        // Five seconds from 7.5 seconds into the second reference, with noise at about 10 dB SNR
+        let start = SAMPLE_RATE as usize * 15 / 2;
+        let mut random = Random(42);
+        let clip: Vec<f64> = references[1][start..start + 5 * SAMPLE_RATE as usize]
+            .iter()
+            .map(|s| s + 0.15 * (random.uniform() - 0.5))
+            .collect();
+
+        let matches = index.query(&fingerprint(&clip));
+        let best = matches[0];
+        assert_eq!(best.reference, 1);
+        assert!((best.offset_seconds() - 7.5).abs() <= HOP_SIZE as f64 / SAMPLE_RATE as f64);
+        assert!(best.score > 20);
+        assert!(matches[1..].iter().all(|m| m.score * 5 < best.score));
+    }
+
+    #[test]
+    fn silence_has_no_landmarks() {
+        assert!(fingerprint(&vec![0.0; 44100]).is_empty());
+        assert!(FingerprintIndex::new().query(&[]).is_empty());
+    }
+}
@@ -0,0 +1,337 @@
+//! Fixed-point FFT for processors without a floating point unit.
+//!
+//! Samples are Q15 (`i16`) or Q31 (`i32`) fractions, where the full range of the integer
+//! type maps to -1.0..1.0. The transform uses block floating point: before each butterfly
+//! stage the largest value in the buffers is checked, and the whole block is shifted right
+//! just enough that the stage can not overflow. The total shift is returned as an exponent,
+//! so the small signals keep their precision while loud ones do not wrap around.
+//!
+//! Only the plan is created with floating point math (for the twiddle factors); the
+//! transform itself uses integer arithmetic.
+
+use alloc::vec::Vec;
+use core::f64::consts::PI;
+
+use crate::bit_reverse;
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+/// A fixed-point sample format.
+pub trait FixedPoint: Copy {
+    /// The number of fractional bits, the value 1.0 is `1 << FRACTION_BITS`.
+    const FRACTION_BITS: u32;
+    const MIN: i64;
+    const MAX: i64;
+
+    fn to_i64(self) -> i64;
+
+    /// Converts back from the wider type, saturating at the limits.
+    fn from_i64(value: i64) -> Self;
+
+    /// Converts a float in the range -1.0..1.0, saturating outside of it.
+    fn from_f64(value: f64) -> Self {
+        let scaled = (value * (1i64 << Self::FRACTION_BITS) as f64).round();
+        Self::from_i64(scaled.clamp(Self::MIN as f64, Self::MAX as f64) as i64)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.to_i64() as f64 / (1i64 << Self::FRACTION_BITS) as f64
+    }
+}
+
+/// Q15: 16-bit samples with 15 fractional bits.
+impl FixedPoint for i16 {
+    const FRACTION_BITS: u32 = 15;
+    const MIN: i64 = i16::MIN as i64;
+    const MAX: i64 = i16::MAX as i64;
+
+    fn to_i64(self) -> i64 {
+        self as i64
+    }
+
+    fn from_i64(value: i64) -> Self {
+        value.clamp(i16::MIN as i64, i16::MAX as i64) as i16
+    }
+}
+
+/// Q31: 32-bit samples with 31 fractional bits.
+impl FixedPoint for i32 {
+    const FRACTION_BITS: u32 = 31;
+    const MIN: i64 = i32::MIN as i64;
+    const MAX: i64 = i32::MAX as i64;
+
+    fn to_i64(self) -> i64 {
+        self as i64
+    }
+
+    fn from_i64(value: i64) -> Self {
+        value.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+/// A reusable fixed-point FFT of a fixed size, working in place on the caller's buffers.
+///
+/// # How to use:
+/// ```ignore
+/// let plan = FixedFftPlan::<i16>::new(256);
+/// let mut real: [i16; 256] = read_adc();
+/// let mut imag = [0i16; 256];
+/// let exponent = plan.process(&mut real, &mut imag);
+///
+/// let mut magnitudes = [0u32; 128];
+/// fixed_magnitudes(&real, &imag, &mut magnitudes);
+/// // Bin k has the amplitude magnitudes[k] * 2^exponent / 256 in Q15 units,
+/// // the same scale `get_frequencies` would report for float samples.
+/// ```
+pub struct FixedFftPlan<T: FixedPoint> {
+    size: usize,
+    bits: u32,
+    twiddle_real: Vec<T>,
+    twiddle_imag: Vec<T>,
+}
+
+impl<T: FixedPoint> FixedFftPlan<T> {
+    /// Creates a plan and precomputes its twiddle factors.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The transform size, must be a power of 2.
+    ///
+    /// # Panics
+    ///
+    /// If the size is not a power of 2 or is 0.
+    pub fn new(size: usize) -> Self {
+        if size == 0 || (size & (size - 1)) != 0 {
+            panic!("Size must be a power of 2 and greater than 0.");
+        }
+
+        // cos(0) = 1.0 saturates to the largest value just below it
+        let (twiddle_imag, twiddle_real) = (0..size / 2)
+            .map(|k| {
+                let (sin, cos) = (-2.0 * PI * k as f64 / size as f64).sin_cos();
+                (T::from_f64(sin), T::from_f64(cos))
+            })
+            .unzip();
+
+        Self {
+            size,
+            bits: size.trailing_zeros(),
+            twiddle_real,
+            twiddle_imag,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Transforms a complex signal in place.
+    ///
+    /// Unlike `fft` the result is not divided by the size. Instead the buffers hold the
+    /// spectrum divided by `2^exponent`, which is the number of right shifts needed to keep
+    /// the values in range.
+    ///
+    /// # Arguments
+    ///
+    /// * `real` - The real samples, replaced by the real components of the spectrum.
+    /// * `imag` - The imaginary samples (usually zeros), replaced by the imaginary components.
+    ///
+    /// # Returns
+    ///
+    /// The block exponent: the spectrum is `(real[k] + j imag[k]) * 2^exponent`.
+    ///
+    /// # Panics
+    ///
+    /// If either buffer does not have the plan's size.
+    pub fn process(&self, real: &mut [T], imag: &mut [T]) -> u32 {
+        assert_eq!(real.len(), self.size, "Buffers must have the plan's size.");
+        assert_eq!(imag.len(), self.size, "Buffers must have the plan's size.");
+
+        for i in 0..self.size {
+            let j = bit_reverse(i as u64, self.bits) as usize;
+            if j > i {
+                real.swap(i, j);
+                imag.swap(i, j);
+            }
+        }
+
+        let mut exponent = 0;
+        let mut half_step = 1;
+        while half_step < self.size {
+            let shift = stage_shift::<T>(real, imag);
+            exponent += shift;
+
+            let stride = self.size / (2 * half_step);
+            for start in (0..self.size).step_by(2 * half_step) {
+                for j in 0..half_step {
+                    let twiddle_real = self.twiddle_real[j * stride].to_i64();
+                    let twiddle_imag = self.twiddle_imag[j * stride].to_i64();
+
+                    let a = start + j;
+                    let b = a + half_step;
+                    let (b_real, b_imag) = (real[b].to_i64(), imag[b].to_i64());
+                    let temp_real =
+                        multiply::<T>(twiddle_real, b_real) - multiply::<T>(twiddle_imag, b_imag);
+                    let temp_imag =
+                        multiply::<T>(twiddle_real, b_imag) + multiply::<T>(twiddle_imag, b_real);
+                    let (a_real, a_imag) = (real[a].to_i64(), imag[a].to_i64());
+
+                    real[a] = T::from_i64(round_shift(a_real + temp_real, shift));
+                    imag[a] = T::from_i64(round_shift(a_imag + temp_imag, shift));
+                    real[b] = T::from_i64(round_shift(a_real - temp_real, shift));
+                    imag[b] = T::from_i64(round_shift(a_imag - temp_imag, shift));
+                }
+            }
+            half_step *= 2;
+        }
+        exponent
+    }
+}
+
+/// Calculates the magnitude of each bin of a fixed-point spectrum with an integer square root.
+///
+/// # Arguments
+///
+/// * `real` - The real components from `FixedFftPlan::process`.
+/// * `imag` - The imaginary components from `FixedFftPlan::process`.
+/// * `magnitudes` - Receives one magnitude per bin, in the same scale as the components.
+///   Usually half the size of the spectrum, as the upper half mirrors the lower for real input.
+///
+/// # Panics
+///
+/// If `magnitudes` is longer than the spectrum.
+pub fn fixed_magnitudes<T: FixedPoint>(real: &[T], imag: &[T], magnitudes: &mut [u32]) {
+    assert!(
+        magnitudes.len() <= real.len() && magnitudes.len() <= imag.len(),
+        "There can not be more magnitudes than bins."
+    );
+    for (k, magnitude) in magnitudes.iter_mut().enumerate() {
+        let (re, im) = (real[k].to_i64(), imag[k].to_i64());
+        // Fits even for Q31: 2 * (2^31)^2 = 2^63
+        let power = (re * re) as u64 + (im * im) as u64;
+        *magnitude = power.isqrt() as u32;
+    }
+}
+
+/// The number of right shifts needed before the next stage so no butterfly can overflow.
+///
+/// A butterfly output component is at most `|a| + |b| * (|cos| + |sin|)`, which is up to
+/// `1 + sqrt(2)` times the largest input component.
+fn stage_shift<T: FixedPoint>(real: &[T], imag: &[T]) -> u32 {
+    let largest = real
+        .iter()
+        .chain(imag.iter())
+        .map(|v| v.to_i64().abs())
+        .max()
+        .unwrap_or(0);
+
+    // (1 + sqrt(2)) ≈ 2.414 < 5/2, so the stage is safe when largest * 5/2 fits
+    let limit = T::MAX;
+    let mut shift = 0;
+    while (largest >> shift) * 5 / 2 > limit {
+        shift += 1;
+    }
+    shift
+}
+
+/// Multiplies two fixed-point values, rounding the result back to the same format.
+fn multiply<T: FixedPoint>(a: i64, b: i64) -> i64 {
+    round_shift(a * b, T::FRACTION_BITS)
+}
+
+/// Arithmetic right shift with rounding to nearest.
+fn round_shift(value: i64, shift: u32) -> i64 {
+    if shift == 0 {
+        value
+    } else {
+        (value + (1 << (shift - 1))) >> shift
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fft;
+    use crate::test_signals::noise;
+    use alloc::vec;
+
+    /// Signal-to-error ratio in dB of the fixed-point spectrum compared to `fft`.
+    fn accuracy<T: FixedPoint>(samples: &[f64]) -> f64 {
+        let size = samples.len();
+        let expected = fft(samples);
+
+        let mut real: Vec<T> = samples.iter().map(|s| T::from_f64(*s)).collect();
+        let mut imag: Vec<T> = vec![T::from_i64(0); size];
+        let exponent = FixedFftPlan::<T>::new(size).process(&mut real, &mut imag);
+
+        // fft divides by the size, the fixed-point transform by 2^exponent
+        let scale = (1u64 << exponent) as f64 / size as f64;
+        let (mut signal, mut error) = (0.0, 0.0);
+        for k in 0..size {
+            let re = real[k].to_f64() * scale;
+            let im = imag[k].to_f64() * scale;
+            signal += expected.real[k].powi(2) + expected.imag[k].powi(2);
+            error += (re - expected.real[k]).powi(2) + (im - expected.imag[k]).powi(2);
+        }
+        10.0 * (signal / error).log10()
+    }
+
+    #[test]
+    fn matches_float_fft_on_test_vectors() {
+        // The vectors from the float tests, halved to fit in -1.0..1.0
+        let vectors: [&[f64]; 3] = [
+            &[1.0, 1.0, 1.0, 1.0],
+            &[0.0, 1.0, 0.0, -1.0],
+            &[0.0, 1.707, 1.0, -0.293, 0.0, -1.707, -1.0, 1.707],
+        ];
+        for vector in vectors {
+            let halved: Vec<f64> = vector.iter().map(|s| s * 0.5).collect();
+            assert!(accuracy::<i16>(&halved) > 70.0);
+            assert!(accuracy::<i32>(&halved) > 150.0);
+        }
+    }
+
+    #[test]
+    fn matches_float_fft_on_random_input() {
+        for (size, seed) in [(64, 1), (1024, 2), (4096, 3)] {
+            let samples = noise(seed, size);
+            let q15 = accuracy::<i16>(&samples);
+            let q31 = accuracy::<i32>(&samples);
+            assert!(q15 > 60.0, "Q15 accuracy {:.1} dB at size {}", q15, size);
+            assert!(q31 > 150.0, "Q31 accuracy {:.1} dB at size {}", q31, size);
+        }
+    }
+
+    #[test]
+    fn full_scale_input_does_not_overflow() {
+        // A full-scale square wave concentrates everything in a few large bins
+        let samples: Vec<f64> = (0..512)
+            .map(|i| if i % 64 < 32 { 1.0 } else { -1.0 })
+            .collect();
+        assert!(accuracy::<i16>(&samples) > 60.0);
+
+        // A constant puts the whole sum in bin 0, which needs every stage to be scaled
+        let mut real = vec![i16::MAX; 256];
+        let mut imag = vec![0i16; 256];
+        let exponent = FixedFftPlan::<i16>::new(256).process(&mut real, &mut imag);
+        let dc = real[0] as f64 * (1u64 << exponent) as f64;
+        assert!((dc / (256.0 * i16::MAX as f64) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn magnitudes_match_amplitudes() {
+        // A sine at bin 8 with amplitude 0.5 shows up as 0.25 in bins 8 and 56
+        let samples: Vec<f64> = (0..64)
+            .map(|i| 0.5 * (2.0 * PI * 8.0 * i as f64 / 64.0).sin())
+            .collect();
+        let mut real: Vec<i16> = samples.iter().map(|s| i16::from_f64(*s)).collect();
+        let mut imag = vec![0i16; 64];
+        let exponent = FixedFftPlan::<i16>::new(64).process(&mut real, &mut imag);
+
+        let mut magnitudes = [0u32; 32];
+        fixed_magnitudes(&real, &imag, &mut magnitudes);
+        let amplitude = magnitudes[8] as f64 * (1u64 << exponent) as f64 / 64.0 / 32768.0;
+        assert!((amplitude - 0.25).abs() < 1e-3);
+        assert!(magnitudes[3] <= 2);
+    }
+}
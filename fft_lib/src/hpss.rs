@@ -0,0 +1,277 @@
+//! Harmonic-percussive source separation by median filtering, after Fitzgerald,
+//! "Harmonic/Percussive Separation using Median Filtering" (2010).
+//!
+//! In a spectrogram, tonal sounds are horizontal lines and drum hits are vertical ones. A
+//! median across time keeps the lines and removes the hits, a median across frequency does
+//! the opposite. The two filtered spectrograms give a mask for each part, which is applied
+//! to the STFT before resynthesizing both parts by overlap-add.
+
+use alloc::{vec, vec::Vec};
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::stft::{OverlapAdd, Stft};
+use crate::window::Window;
+use crate::FftResult;
+
+/// How the filtered spectrograms are turned into masks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mask {
+    /// Each bin goes entirely to the part with the larger filtered magnitude.
+    Hard,
+    /// Each bin is shared in proportion to the filtered magnitudes raised to `power`. A power
+    /// of 2.0 is a Wiener filter, larger powers get closer to the hard mask.
+    Soft { power: f64 },
+}
+
+/// Settings of the separation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HpssSettings {
+    /// The STFT frame size, must be a power of 2.
+    pub frame_size: usize,
+    /// The number of samples between frames. A quarter of the frame size or less lets the
+    /// overlap-add reconstruct the signal exactly.
+    pub hop_size: usize,
+    /// The number of frames the median across time covers, should be odd.
+    pub harmonic_length: usize,
+    /// The number of bins the median across frequency covers, should be odd.
+    pub percussive_length: usize,
+    pub mask: Mask,
+}
+
+impl Default for HpssSettings {
+    fn default() -> Self {
+        Self {
+            frame_size: 2048,
+            hop_size: 512,
+            harmonic_length: 17,
+            percussive_length: 17,
+            mask: Mask::Soft { power: 2.0 },
+        }
+    }
+}
+
+/// The two parts of a separated signal. They add up to the original signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Separation {
+    /// The sustained, tonal part.
+    pub harmonic: Vec<f64>,
+    /// The transient part, e.g. drums.
+    pub percussive: Vec<f64>,
+}
+
+/// Calculates the harmonic and percussive masks of a magnitude spectrogram.
+///
+/// # Arguments
+///
+/// * `magnitudes` - The magnitudes of each frame, all with the same number of bins.
+/// * `settings` - The median lengths and the kind of mask.
+///
+/// # Returns
+///
+/// The harmonic and the percussive mask, with the same shape as the spectrogram. Each pair
+/// of mask values adds up to 1.
+pub fn masks(magnitudes: &[Vec<f64>], settings: &HpssSettings) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+    let frames = magnitudes.len();
+    let bins = magnitudes.first().map_or(0, |frame| frame.len());
+
+    let percussive: Vec<Vec<f64>> = magnitudes
+        .iter()
+        .map(|frame| median_filter(frame, settings.percussive_length))
+        .collect();
+    let mut harmonic = vec![vec![0.0; bins]; frames];
+    for k in 0..bins {
+        let track: Vec<f64> = magnitudes.iter().map(|frame| frame[k]).collect();
+        for (frame, value) in harmonic
+            .iter_mut()
+            .zip(median_filter(&track, settings.harmonic_length))
+        {
+            frame[k] = value;
+        }
+    }
+
+    let mut harmonic_mask = vec![vec![0.0; bins]; frames];
+    let mut percussive_mask = vec![vec![0.0; bins]; frames];
+    for t in 0..frames {
+        for k in 0..bins {
+            let (h, p) = (harmonic[t][k], percussive[t][k]);
+            let share = match settings.mask {
+                Mask::Hard => {
+                    if h >= p {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                }
+                Mask::Soft { power } => {
+                    let (h, p) = (h.powf(power), p.powf(power));
+                    if h + p > 0.0 {
+                        h / (h + p)
+                    } else {
+                        0.5
+                    }
+                }
+            };
+            harmonic_mask[t][k] = share;
+            percussive_mask[t][k] = 1.0 - share;
+        }
+    }
+    (harmonic_mask, percussive_mask)
+}
+
+/// Separates a signal into its harmonic and percussive parts.
+///
+/// The signal is padded so that every sample is covered by the same number of frames, and
+/// the padding is removed again after resynthesis.
+///
+/// # Arguments
+///
+/// * `samples` - The signal.
+/// * `settings` - The separation settings.
+///
+/// # Panics
+///
+/// If the frame size is not a power of 2 or the hop size is 0 or larger than the frame size.
+pub fn separate(samples: &[f64], settings: &HpssSettings) -> Separation {
+    let stft = Stft::new(settings.frame_size, settings.hop_size, Window::Hann);
+    let padding = settings.frame_size - settings.hop_size;
+    let frame_count = (samples.len() + padding).div_ceil(settings.hop_size);
+    let mut padded = vec![0.0; padding];
+    padded.extend_from_slice(samples);
+    padded.resize(
+        (frame_count - 1) * settings.hop_size + settings.frame_size,
+        0.0,
+    );
+
+    let frames = stft.analyze(&padded);
+    let bins = settings.frame_size / 2 + 1;
+    let magnitudes: Vec<Vec<f64>> = frames
+        .iter()
+        .map(|frame| {
+            (0..bins)
+                .map(|k| (frame.real[k] * frame.real[k] + frame.imag[k] * frame.imag[k]).sqrt())
+                .collect()
+        })
+        .collect();
+    let (harmonic_mask, percussive_mask) = masks(&magnitudes, settings);
+
+    let resynthesize = |mask: &[Vec<f64>]| {
+        let masked: Vec<FftResult> = frames
+            .iter()
+            .zip(mask.iter())
+            .map(|(frame, mask)| apply_mask(frame, mask))
+            .collect();
+        let mut synthesizer = OverlapAdd::new(settings.frame_size, settings.hop_size, Window::Hann);
+        let output = synthesizer.synthesize(&masked);
+        output[padding..padding + samples.len()].to_vec()
+    };
+
+    Separation {
+        harmonic: resynthesize(&harmonic_mask),
+        percussive: resynthesize(&percussive_mask),
+    }
+}
+
+/// Scales the bins of a frame by a mask over bins 0 to `frame_size / 2`, mirrored onto the
+/// negative frequencies so that the resynthesized frame stays real.
+fn apply_mask(frame: &FftResult, mask: &[f64]) -> FftResult {
+    let size = frame.real.len();
+    let mut masked = frame.clone();
+    for k in 0..size {
+        let gain = mask[if k <= size / 2 { k } else { size - k }];
+        masked.real[k] *= gain;
+        masked.imag[k] *= gain;
+    }
+    masked
+}
+
+/// The running median over `length` values centered on each value. Near the edges the
+/// window is cut off instead of padded.
+fn median_filter(values: &[f64], length: usize) -> Vec<f64> {
+    let half = length / 2;
+    let mut window = Vec::with_capacity(length);
+    (0..values.len())
+        .map(|i| {
+            let low = i.saturating_sub(half);
+            let high = (i + half + 1).min(values.len());
+            window.clear();
+            window.extend_from_slice(&values[low..high]);
+            window.sort_by(|a, b| a.total_cmp(b));
+            let middle = window.len() / 2;
+            if window.len() % 2 == 1 {
+                window[middle]
+            } else {
+                (window[middle - 1] + window[middle]) / 2.0
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::assert_float_absolute_eq;
+    use core::f64::consts::PI;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    fn energy(samples: &[f64]) -> f64 {
+        samples.iter().map(|s| s * s).sum()
+    }
+
+    /// A steady 440 Hz tone and clicks every quarter second.
+    fn tone_and_clicks() -> (Vec<f64>, Vec<f64>) {
+        let tone: Vec<f64> = (0..16000)
+            .map(|i| 0.3 * (2.0 * PI * 440.0 * i as f64 / SAMPLE_RATE as f64).sin())
+            .collect();
+        let clicks: Vec<f64> = (0..16000)
+            .map(|i| if i % 4000 == 2000 { 1.0 } else { 0.0 })
+            .collect();
+        (tone, clicks)
+    }
+
+    #[test]
+    fn median_filter_removes_outliers() {
+        let filtered = median_filter(&[1.0, 1.0, 9.0, 1.0, 2.0, 2.0], 3);
+        assert_eq!(filtered, vec![1.0, 1.0, 1.0, 2.0, 2.0, 2.0]);
+    }
+
+    #[test]
+    fn parts_add_up_to_the_signal() {
+        let (tone, clicks) = tone_and_clicks();
+        let mixture: Vec<f64> = tone.iter().zip(clicks.iter()).map(|(t, c)| t + c).collect();
+
+        for mask in [Mask::Hard, Mask::Soft { power: 2.0 }] {
+            let settings = HpssSettings {
+                mask,
+                ..HpssSettings::default()
+            };
+            let separation = separate(&mixture, &settings);
+            assert_eq!(separation.harmonic.len(), mixture.len());
+            for (i, sample) in mixture.iter().enumerate() {
+                let sum = separation.harmonic[i] + separation.percussive[i];
+                assert_float_absolute_eq!(sum, *sample, 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn separates_tone_from_clicks() {
+        let (tone, clicks) = tone_and_clicks();
+        let mixture: Vec<f64> = tone.iter().zip(clicks.iter()).map(|(t, c)| t + c).collect();
+        let separation = separate(&mixture, &HpssSettings::default());
+
+        let error: Vec<f64> = separation
+            .harmonic
+            .iter()
+            .zip(tone.iter())
+            .map(|(h, t)| h - t)
+            .collect();
+        assert!(energy(&error) < 0.01 * energy(&tone));
+
+        // Most of the click at sample 2000 is in the percussive part, and between the clicks
+        // almost nothing of the tone is
+        assert!(energy(&separation.percussive[1936..2064]) > 0.9);
+        assert!(energy(&separation.percussive[2500..5500]) < 1e-5 * energy(&tone[2500..5500]));
+    }
+}
@@ -0,0 +1,228 @@
+//! Musical key estimation with the Krumhansl–Schmuckler algorithm.
+//!
+//! The chroma of a passage is correlated with the Krumhansl–Kessler tonal profiles
+//! rotated to all 24 major and minor keys, and the best match wins.
+
+use crate::chroma::{chromagram, Chroma, DEFAULT_HOP_SIZE, PITCH_CLASS_NAMES};
+
+/// Krumhansl–Kessler probe-tone ratings for a major key, starting at the tonic.
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+/// Krumhansl–Kessler probe-tone ratings for a minor key, starting at the tonic.
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// The mode of a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Major,
+    Minor,
+}
+
+/// The result of a key estimation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyEstimate {
+    /// The pitch class of the tonic, 0 being C.
+    pub tonic: usize,
+    /// Whether the key is major or minor.
+    pub mode: Mode,
+    /// The correlation between the chroma and the key's profile, between -1.0 and 1.0.
+    pub correlation: f64,
+    /// How clearly the key beat the runner-up: the difference between their correlations.
+    pub confidence: f64,
+}
+
+impl KeyEstimate {
+    /// The name of the key, e.g. "A minor".
+    pub fn name(&self) -> String {
+        let mode = match self.mode {
+            Mode::Major => "major",
+            Mode::Minor => "minor",
+        };
+        format!("{} {}", PITCH_CLASS_NAMES[self.tonic], mode)
+    }
+}
+
+/// Estimates the key of a chroma vector.
+///
+/// # Arguments
+///
+/// * `chroma` - The pitch-class profile, e.g. summed over a passage.
+///
+/// # Returns
+///
+/// The best matching key, or `None` if the chroma is flat (e.g. silence).
+pub fn estimate_key(chroma: &Chroma) -> Option<KeyEstimate> {
+    let mut correlations = Vec::with_capacity(24);
+    for (mode, profile) in [(Mode::Major, &MAJOR_PROFILE), (Mode::Minor, &MINOR_PROFILE)] {
+        for tonic in 0..12 {
+            let rotated: Vec<f64> = (0..12).map(|i| profile[(i + 12 - tonic) % 12]).collect();
+            correlations.push((tonic, mode, pearson(chroma, &rotated)?));
+        }
+    }
+
+    correlations.sort_by(|a, b| b.2.total_cmp(&a.2));
+    let (tonic, mode, correlation) = correlations[0];
+    Some(KeyEstimate {
+        tonic,
+        mode,
+        correlation,
+        confidence: correlation - correlations[1].2,
+    })
+}
+
+/// Estimates the key of a sequence of chroma vectors by summing them.
+pub fn estimate_key_from_chromagram(chromagram: &[Chroma]) -> Option<KeyEstimate> {
+    let mut total = [0.0; 12];
+    for chroma in chromagram {
+        for (t, c) in total.iter_mut().zip(chroma.iter()) {
+            *t += c;
+        }
+    }
+    estimate_key(&total)
+}
+
+/// Estimates the key of a complete signal, e.g. a whole WAV file.
+///
+/// # Arguments
+///
+/// * `samples` - The samples of the signal.
+/// * `sample_rate` - The sample rate of the signal.
+pub fn estimate_key_from_samples(samples: &[f64], sample_rate: u32) -> Option<KeyEstimate> {
+    estimate_key_from_chromagram(&chromagram(samples, sample_rate))
+}
+
+/// Estimates the key over consecutive windows of a signal, to follow modulations.
+///
+/// # Arguments
+///
+/// * `samples` - The samples of the signal.
+/// * `sample_rate` - The sample rate of the signal.
+/// * `window_seconds` - The length of each analysis window in seconds.
+///
+/// # Returns
+///
+/// The start time in seconds and the key estimate of every window that contained a key.
+pub fn estimate_key_windowed(
+    samples: &[f64],
+    sample_rate: u32,
+    window_seconds: f64,
+) -> Vec<(f64, KeyEstimate)> {
+    let frames = chromagram(samples, sample_rate);
+    let frame_duration = DEFAULT_HOP_SIZE as f64 / sample_rate as f64;
+    let frames_per_window = ((window_seconds / frame_duration).round() as usize).max(1);
+
+    frames
+        .chunks(frames_per_window)
+        .enumerate()
+        .filter_map(|(i, window)| {
+            let start = (i * frames_per_window) as f64 * frame_duration;
+            estimate_key_from_chromagram(window).map(|key| (start, key))
+        })
+        .collect()
+}
+
+fn pearson(a: &[f64], b: &[f64]) -> Option<f64> {
+    let n = a.len() as f64;
+    let (mean_a, mean_b) = (a.iter().sum::<f64>() / n, b.iter().sum::<f64>() / n);
+    let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b.iter()) {
+        covariance += (x - mean_a) * (y - mean_b);
+        variance_a += (x - mean_a) * (x - mean_a);
+        variance_b += (y - mean_b) * (y - mean_b);
+    }
+
+    if variance_a <= f64::EPSILON || variance_b <= f64::EPSILON {
+        return None;
+    }
+    Some(covariance / (variance_a * variance_b).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroma::tests::tones;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    fn progression(chords: &[&[f64]], seconds_per_chord: f64, sample_rate: u32) -> Vec<f64> {
+        chords
+            .iter()
+            .flat_map(|chord| tones(chord, seconds_per_chord, sample_rate))
+            .collect()
+    }
+
+    #[test]
+    fn profile_matches_itself() {
+        let mut chroma = [0.0; 12];
+        for i in 0..12 {
+            chroma[(i + 2) % 12] = MAJOR_PROFILE[i];
+        }
+        let key = estimate_key(&chroma).unwrap();
+        assert_eq!((key.tonic, key.mode), (2, Mode::Major));
+        assert_float_absolute_eq!(key.correlation, 1.0, 1e-9);
+        assert_eq!(key.name(), "D major");
+    }
+
+    #[test]
+    fn c_major_progression() {
+        // I - IV - V - I
+        let samples = progression(
+            &[
+                &[60.0, 64.0, 67.0],
+                &[65.0, 69.0, 72.0],
+                &[67.0, 71.0, 74.0],
+                &[60.0, 64.0, 67.0],
+            ],
+            1.0,
+            16384,
+        );
+        let key = estimate_key_from_samples(&samples, 16384).unwrap();
+        assert_eq!(key.name(), "C major");
+        assert!(key.confidence > 0.0);
+    }
+
+    #[test]
+    fn a_minor_progression() {
+        // i - iv - V - i with the raised leading tone
+        let samples = progression(
+            &[
+                &[57.0, 60.0, 64.0],
+                &[62.0, 65.0, 69.0],
+                &[64.0, 68.0, 71.0],
+                &[57.0, 60.0, 64.0],
+            ],
+            1.0,
+            16384,
+        );
+        let key = estimate_key_from_samples(&samples, 16384).unwrap();
+        assert_eq!(key.name(), "A minor");
+    }
+
+    #[test]
+    fn windowed_estimation_follows_modulation() {
+        let c_major: &[&[f64]] = &[
+            &[60.0, 64.0, 67.0],
+            &[65.0, 69.0, 72.0],
+            &[67.0, 71.0, 74.0],
+        ];
+        let e_major: &[&[f64]] = &[
+            &[64.0, 68.0, 71.0],
+            &[69.0, 73.0, 76.0],
+            &[71.0, 75.0, 78.0],
+        ];
+        let mut samples = progression(c_major, 1.0, 16384);
+        samples.extend(progression(e_major, 1.0, 16384));
+
+        let keys = estimate_key_windowed(&samples, 16384, 3.0);
+        assert_eq!(keys.first().unwrap().1.name(), "C major");
+        assert_eq!(keys.last().unwrap().1.name(), "E major");
+    }
+
+    #[test]
+    fn silence_has_no_key() {
+        assert_eq!(estimate_key(&[0.0; 12]), None);
+        assert_eq!(estimate_key_from_samples(&[0.0; 16384], 16384), None);
+    }
+}
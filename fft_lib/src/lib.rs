@@ -1,7 +1,54 @@
 #![allow(non_snake_case)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod averaging;
+#[cfg(feature = "std")]
+pub mod beat;
+pub mod cepstrum;
+#[cfg(feature = "std")]
+pub mod chroma;
+pub mod codec;
+#[cfg(feature = "std")]
+pub mod denoise;
+pub mod distortion;
+pub mod features;
+pub mod filter;
+pub mod fingerprint;
+pub mod fixed;
+pub mod hpss;
+#[cfg(feature = "std")]
+pub mod key;
+pub mod log_spectrum;
+pub mod lomb_scargle;
+pub mod lpc;
+#[cfg(not(feature = "std"))]
+mod math;
+pub mod midi;
+#[cfg(feature = "std")]
+pub mod onset;
+pub mod plan;
+pub mod sinusoidal;
+pub mod stft;
+#[cfg(test)]
+pub(crate) mod test_signals;
+pub mod transcription;
+pub mod transfer;
+pub mod vocoder;
+pub mod wavelet;
+pub mod window;
+
+pub use plan::FftPlan;
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+#[cfg(not(feature = "std"))]
+use math::Float;
 
 /// Represents the result of a Fast Fourier Transform (FFT).
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FftResult {
     /// Vector containing the real components of the FFT result.
     pub real: Vec<f64>,
@@ -10,7 +57,8 @@ pub struct FftResult {
 }
 
 /// Represent the frequencies in a range of samples
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Frequencies {
     /// The frequencies corresponding to the FFT result.
     pub frequencies: Vec<f64>,
@@ -25,7 +73,7 @@ pub struct Frequencies {
 }
 
 fn twiddle_factor(k: f64, N: usize) -> (f64, f64) {
-    let angle = -2.0 * std::f64::consts::PI * k / (N as f64);
+    let angle = -2.0 * PI * k / (N as f64);
     angle.sin_cos()
 }
 
@@ -69,6 +117,45 @@ pub fn get_frequencies(fft_result: &FftResult, sample_rate: u32) -> Frequencies
     }
 }
 
+/// Unwraps a sequence of phases by removing the jumps of 2π between consecutive values.
+///
+/// # Arguments
+///
+/// * `phases` - The wrapped phases in radians, e.g. from `atan2`.
+///
+/// # Returns
+///
+/// The continuous phases in radians.
+pub fn unwrap_phase(phases: &[f64]) -> Vec<f64> {
+    let mut unwrapped = Vec::with_capacity(phases.len());
+    let mut offset = 0.0;
+    for (i, &phase) in phases.iter().enumerate() {
+        if i > 0 {
+            let difference = phase - phases[i - 1];
+            if difference > PI {
+                offset -= 2.0 * PI;
+            } else if difference < -PI {
+                offset += 2.0 * PI;
+            }
+        }
+        unwrapped.push(phase + offset);
+    }
+    unwrapped
+}
+
+/// Wraps a phase into the range -π to π.
+///
+/// # Arguments
+///
+/// * `phase` - The phase in radians.
+///
+/// # Returns
+///
+/// The same angle in the range -π to π.
+pub(crate) fn wrap_phase(phase: f64) -> f64 {
+    phase - 2.0 * PI * ((phase + PI) / (2.0 * PI)).floor()
+}
+
 fn bit_reverse(n: u64, num_bits: u32) -> u64 {
     let mut reversed = 0;
     for i in 0..num_bits {
@@ -80,6 +167,42 @@ fn bit_reverse(n: u64, num_bits: u32) -> u64 {
     reversed
 }
 
+/// Runs the butterfly stages on bit-reversed data, in place.
+/// The inverse transform uses the conjugated twiddle factors.
+fn butterflies(real: &mut [f64], imag: &mut [f64], inverse: bool) {
+    let N = real.len();
+    let m = ((N as f64).log2() as u64) + 1;
+
+    for s in 1..m {
+        let n_step = 2_i32.pow(s as u32);
+        let half_step = 2_i32.pow(s as u32 - 1);
+
+        for i in (0..N).step_by(n_step as usize) {
+            for j in 0..half_step {
+                let a_real = real[i + j as usize];
+                let a_imag = imag[i + j as usize];
+
+                let b_real = real[i + j as usize + half_step as usize];
+                let b_imag = imag[i + j as usize + half_step as usize];
+
+                let (mut twiddle_imag, twiddle_real) = twiddle_factor(j as f64, n_step as usize);
+                if inverse {
+                    twiddle_imag = -twiddle_imag;
+                }
+
+                let temp_real = twiddle_real * b_real - twiddle_imag * b_imag;
+                let temp_imag = twiddle_real * b_imag + twiddle_imag * b_real;
+
+                real[i + j as usize] = a_real + temp_real;
+                imag[i + j as usize] = a_imag + temp_imag;
+
+                real[i + j as usize + half_step as usize] = a_real - temp_real;
+                imag[i + j as usize + half_step as usize] = a_imag - temp_imag;
+            }
+        }
+    }
+}
+
 /// Performs a Fast Fourier Transform (FFT) on the given input data.
 ///
 /// # Arguments
@@ -110,41 +233,59 @@ pub fn fft(in_data: &[f64]) -> FftResult {
         real[j as usize] = in_data[i];
     }
 
-    // Butterfly
-    let m = ((N as f64).log2() as u64) + 1;
+    butterflies(&mut real, &mut imag, false);
 
-    for s in 1..m {
-        let n_step = 2_i32.pow(s as u32);
-        let half_step = 2_i32.pow(s as u32 - 1);
-
-        for i in (0..N).step_by(n_step as usize) {
-            for j in 0..half_step {
-                let a_real = real[i + j as usize];
-                let a_imag = imag[i + j as usize];
-
-                let b_real = real[i + j as usize + half_step as usize];
-                let b_imag = imag[i + j as usize + half_step as usize];
-
-                let (twiddle_imag, twiddle_real) = twiddle_factor(j as f64, n_step as usize);
+    // Normalize the result by dividing by N
+    for i in 0..N {
+        real[i] /= N as f64;
+        imag[i] /= N as f64;
+    }
 
-                let temp_real = twiddle_real * b_real - twiddle_imag * b_imag;
-                let temp_imag = twiddle_real * b_imag + twiddle_imag * b_real;
+    FftResult { real, imag }
+}
 
-                real[i + j as usize] = a_real + temp_real;
-                imag[i + j as usize] = a_imag + temp_imag;
+/// Performs an inverse Fast Fourier Transform on the given frequency-domain data.
+///
+/// `fft` divides its result by N, so no further scaling is applied here and
+/// `ifft(&fft(x)).real` reproduces `x`.
+///
+/// # Arguments
+///
+/// * `fft_result` - The real and imaginary components of the spectrum.
+///
+/// # Returns
+///
+/// An `FftResult` struct containing the real and imaginary components of the time-domain signal.
+///
+/// # Panics
+///
+/// If the length is not a power of 2, is 0, or differs between the real and imaginary components.
+pub fn ifft(fft_result: &FftResult) -> FftResult {
+    let N = fft_result.real.len();
 
-                real[i + j as usize + half_step as usize] = a_real - temp_real;
-                imag[i + j as usize + half_step as usize] = a_imag - temp_imag;
-            }
-        }
+    // N needs to be a power of 2
+    if N == 0 || (N & (N - 1)) != 0 {
+        panic!("Input length must be a power of 2 and greater than 0.");
     }
+    assert_eq!(
+        N,
+        fft_result.imag.len(),
+        "Real and imaginary components must have the same length."
+    );
 
-    // Normalize the result by dividing by N
+    let mut real: Vec<f64> = vec![0.0; N];
+    let mut imag: Vec<f64> = vec![0.0; N];
+
+    // Bit reversal
+    let num_bits = (N as f64).log2() as u32;
     for i in 0..N {
-        real[i] /= N as f64;
-        imag[i] /= N as f64;
+        let j = bit_reverse(i as u64, num_bits) as usize;
+        real[j] = fft_result.real[i];
+        imag[j] = fft_result.imag[i];
     }
 
+    butterflies(&mut real, &mut imag, true);
+
     FftResult { real, imag }
 }
 
@@ -158,7 +299,7 @@ pub fn dft(in_data: &[f64]) -> FftResult {
         let mut sum_real: f64 = 0.0;
         let mut sum_imag: f64 = 0.0;
         for n in 0..N {
-            let angle = -2.0 * std::f64::consts::PI * (k as f64) * (n as f64) / (N as f64);
+            let angle = -2.0 * PI * (k as f64) * (n as f64) / (N as f64);
             sum_real += in_data[n] as f64 * angle.cos();
             sum_imag += in_data[n] as f64 * angle.sin();
         }
@@ -239,6 +380,22 @@ mod tests {
         assert_float_vec_eq(&result.imag, &expected_imag);
     }
 
+    #[test]
+    fn inverse_restores_signal() {
+        let in_data: &[f64] = &[0.5, 1.0, -0.25, 0.0, 2.0, -1.5, 0.75, 0.1];
+        let result = ifft(&fft(in_data));
+        assert_float_vec_eq(&result.real, in_data);
+        assert_float_vec_eq(&result.imag, &[0.0; 8]);
+    }
+
+    #[test]
+    fn unwrap_removes_jumps() {
+        let wrapped: Vec<f64> = (0..20).map(|i| wrap_phase(i as f64 * 0.9)).collect();
+        assert!(wrapped.iter().all(|phase| (-PI..PI).contains(phase)));
+        let expected: Vec<f64> = (0..20).map(|i| i as f64 * 0.9).collect();
+        assert_float_vec_eq(&unwrap_phase(&wrapped), &expected);
+    }
+
     #[test]
     fn combination_of_two_frequencies() {
         let in_data: &[f64] = &[1.0, 2.0, 1.0, 0.0, 1.0, 2.0, 1.0, 0.0];
@@ -0,0 +1,133 @@
+//! Log-frequency spectra, with bins spaced evenly in pitch rather than in Hz.
+
+use alloc::vec::Vec;
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::Frequencies;
+
+/// A spectrum resampled onto logarithmically spaced bins.
+#[derive(Debug, Clone)]
+pub struct LogFrequencySpectrum {
+    /// The center frequency of each bin in Hz.
+    pub center_frequencies: Vec<f64>,
+    /// The power (squared amplitude) collected in each bin.
+    pub powers: Vec<f64>,
+    /// The number of bins per octave.
+    pub bins_per_octave: usize,
+}
+
+impl LogFrequencySpectrum {
+    /// Resamples a linear spectrum onto logarithmically spaced bins.
+    ///
+    /// Each log bin collects the power of the linear bins within half a bin of its center.
+    /// At low frequencies, where a log bin is narrower than the linear bin spacing, the
+    /// power is interpolated at the center frequency instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `frequencies` - The linear spectrum.
+    /// * `min_frequency` - The center frequency of the lowest bin in Hz.
+    /// * `bins_per_octave` - The number of bins per octave (12 gives one bin per semitone).
+    /// * `octaves` - The number of octaves to cover.
+    pub fn from_frequencies(
+        frequencies: &Frequencies,
+        min_frequency: f64,
+        bins_per_octave: usize,
+        octaves: usize,
+    ) -> Self {
+        let bin_count = bins_per_octave * octaves;
+        let half_bin = 2.0_f64.powf(0.5 / bins_per_octave as f64);
+        let spacing = if frequencies.total_samples > 0 {
+            frequencies.sample_rate as f64 / frequencies.total_samples as f64
+        } else {
+            0.0
+        };
+
+        let mut center_frequencies = Vec::with_capacity(bin_count);
+        let mut powers = Vec::with_capacity(bin_count);
+
+        for k in 0..bin_count {
+            let center = min_frequency * 2.0_f64.powf(k as f64 / bins_per_octave as f64);
+            let (low, high) = (center / half_bin, center * half_bin);
+
+            let power = if spacing > 0.0 && high - low >= spacing {
+                frequencies
+                    .frequencies
+                    .iter()
+                    .zip(frequencies.amplitudes.iter())
+                    .filter(|(f, _)| **f >= low && **f < high)
+                    .map(|(_, a)| a * a)
+                    .sum()
+            } else {
+                interpolated_power(frequencies, center, spacing)
+            };
+
+            center_frequencies.push(center);
+            powers.push(power);
+        }
+
+        Self {
+            center_frequencies,
+            powers,
+            bins_per_octave,
+        }
+    }
+
+    /// The pitch of a bin's center as a (fractional) MIDI note number, where 69 is A4 at 440 Hz.
+    pub fn midi_pitch(&self, index: usize) -> f64 {
+        frequency_to_midi(self.center_frequencies[index])
+    }
+}
+
+/// Converts a frequency in Hz to a (fractional) MIDI note number, where 69 is A4 at 440 Hz.
+pub fn frequency_to_midi(frequency: f64) -> f64 {
+    69.0 + 12.0 * (frequency / 440.0).log2()
+}
+
+/// Converts a (fractional) MIDI note number to a frequency in Hz.
+pub fn midi_to_frequency(pitch: f64) -> f64 {
+    440.0 * 2.0_f64.powf((pitch - 69.0) / 12.0)
+}
+
+fn interpolated_power(frequencies: &Frequencies, frequency: f64, spacing: f64) -> f64 {
+    if spacing <= 0.0 {
+        return 0.0;
+    }
+
+    let position = frequency / spacing;
+    let index = position.floor() as usize;
+    let fraction = position - index as f64;
+    let amplitude = |i: usize| frequencies.amplitudes.get(i).copied().unwrap_or(0.0);
+    let value = amplitude(index) * (1.0 - fraction) + amplitude(index + 1) * fraction;
+    value * value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fft, get_frequencies};
+    use assert_float_eq::assert_float_absolute_eq;
+
+    #[test]
+    fn midi_conversions() {
+        assert_float_absolute_eq!(frequency_to_midi(440.0), 69.0, 1e-9);
+        assert_float_absolute_eq!(frequency_to_midi(261.6256), 60.0, 1e-4);
+        assert_float_absolute_eq!(midi_to_frequency(81.0), 880.0, 1e-9);
+    }
+
+    #[test]
+    fn tone_lands_in_its_semitone_bin() {
+        let sample_rate = 8192;
+        let samples: Vec<f64> = (0..4096)
+            .map(|i| (2.0 * std::f64::consts::PI * 440.0 * i as f64 / sample_rate as f64).sin())
+            .collect();
+        let f = get_frequencies(&fft(&samples), sample_rate);
+        let spectrum = LogFrequencySpectrum::from_frequencies(&f, midi_to_frequency(48.0), 12, 4);
+
+        let loudest = (0..spectrum.powers.len())
+            .max_by(|&a, &b| spectrum.powers[a].total_cmp(&spectrum.powers[b]))
+            .unwrap();
+        assert_float_absolute_eq!(spectrum.midi_pitch(loudest), 69.0, 1e-9);
+    }
+}
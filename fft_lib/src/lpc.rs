@@ -0,0 +1,545 @@
+//! Linear prediction (LPC) by the autocorrelation method, with the spectral envelope and
+//! formant estimates it gives.
+//!
+//! Each sample is predicted from the `order` samples before it, which models the signal as
+//! white noise through the all-pole filter `G / A(z)`. For speech the poles of `A(z)` are the
+//! resonances of the vocal tract, so their angles and radii give the formant frequencies and
+//! bandwidths.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+use crate::fft;
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::window::{apply_window, Window};
+
+/// Poles below this frequency in Hz are part of the spectral tilt, not formants.
+pub const MIN_FORMANT_FREQUENCY: f64 = 90.0;
+/// Poles with a wider bandwidth in Hz than this are too damped to be formants.
+pub const MAX_FORMANT_BANDWIDTH: f64 = 400.0;
+
+/// The maximum number of iterations when searching for the roots of `A(z)`.
+const MAX_ROOT_ITERATIONS: usize = 500;
+
+/// The result of a linear prediction analysis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lpc {
+    /// The coefficients of `A(z) = 1 + a1 z^-1 + ... + ap z^-p`, starting with the 1.
+    pub coefficients: Vec<f64>,
+    /// The reflection (PARCOR) coefficients of each order, all between -1 and 1.
+    pub reflection: Vec<f64>,
+    /// The power of the prediction error, which is the squared gain `G^2`.
+    pub error: f64,
+}
+
+/// A resonance of the LPC filter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Formant {
+    /// The center frequency in Hz.
+    pub frequency: f64,
+    /// The -3 dB bandwidth in Hz.
+    pub bandwidth: f64,
+}
+
+impl Lpc {
+    /// The order of the prediction.
+    pub fn order(&self) -> usize {
+        self.coefficients.len() - 1
+    }
+
+    /// Evaluates the spectral envelope `G^2 / |A|^2` with `fft`.
+    ///
+    /// The envelope is a power spectral density: its mean over all frequencies equals the
+    /// zero-lag autocorrelation the coefficients were computed from.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The number of frequencies to evaluate, must be a power of 2.
+    ///
+    /// # Returns
+    ///
+    /// The envelope at `points` frequencies from 0 up to (but excluding) the Nyquist
+    /// frequency, `sample_rate / (2 * points)` apart.
+    ///
+    /// # Panics
+    ///
+    /// If `points` is not a power of 2.
+    pub fn envelope(&self, points: usize) -> Vec<f64> {
+        if points == 0 || (points & (points - 1)) != 0 {
+            panic!("Number of points must be a power of 2 and greater than 0.");
+        }
+
+        let size = (2 * points).max(self.coefficients.len().next_power_of_two());
+        let step = size / (2 * points);
+        let mut padded = self.coefficients.clone();
+        padded.resize(size, 0.0);
+        let spectrum = fft(&padded);
+
+        // fft divides by its length, undo that to get the plain polynomial values
+        let scale = size as f64 * size as f64;
+        (0..points)
+            .map(|k| {
+                let (re, im) = (spectrum.real[k * step], spectrum.imag[k * step]);
+                self.error / ((re * re + im * im) * scale).max(1e-300)
+            })
+            .collect()
+    }
+
+    /// Finds the roots of `A(z)`, the poles of the LPC filter.
+    ///
+    /// # Returns
+    ///
+    /// The roots as (real, imaginary) pairs, in no particular order.
+    pub fn roots(&self) -> Vec<(f64, f64)> {
+        find_roots(&self.coefficients)
+    }
+
+    /// Estimates the formants from the poles of the LPC filter.
+    ///
+    /// Each pole pair gives a resonance. Real poles, poles below `MIN_FORMANT_FREQUENCY` and
+    /// poles wider than `MAX_FORMANT_BANDWIDTH` are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The sample rate of the analyzed signal.
+    ///
+    /// # Returns
+    ///
+    /// The formants, ordered by frequency.
+    pub fn formants(&self, sample_rate: u32) -> Vec<Formant> {
+        let sample_rate = sample_rate as f64;
+        let mut formants: Vec<Formant> = self
+            .roots()
+            .iter()
+            .filter(|(_, im)| *im > 0.0)
+            .map(|&(re, im)| Formant {
+                frequency: im.atan2(re) * sample_rate / (2.0 * PI),
+                bandwidth: -(re * re + im * im).sqrt().ln() * sample_rate / PI,
+            })
+            .filter(|f| {
+                f.frequency >= MIN_FORMANT_FREQUENCY && f.bandwidth <= MAX_FORMANT_BANDWIDTH
+            })
+            .collect();
+        formants.sort_by(|a, b| a.frequency.total_cmp(&b.frequency));
+        formants
+    }
+}
+
+/// Calculates the autocorrelation of a signal, divided by its length.
+///
+/// # Arguments
+///
+/// * `samples` - The signal, usually a windowed frame.
+/// * `max_lag` - The largest lag to calculate.
+///
+/// # Returns
+///
+/// The autocorrelation at lags 0 to `max_lag`. Lags beyond the signal are 0.
+pub fn autocorrelation(samples: &[f64], max_lag: usize) -> Vec<f64> {
+    let length = samples.len().max(1) as f64;
+    (0..=max_lag)
+        .map(|lag| {
+            if lag >= samples.len() {
+                return 0.0;
+            }
+            samples
+                .iter()
+                .zip(samples[lag..].iter())
+                .map(|(a, b)| a * b)
+                .sum::<f64>()
+                / length
+        })
+        .collect()
+}
+
+/// Solves for the prediction coefficients with the Levinson-Durbin recursion.
+///
+/// # Arguments
+///
+/// * `autocorrelation` - The autocorrelation at lags 0 to at least `order`.
+/// * `order` - The number of coefficients to predict with.
+///
+/// # Returns
+///
+/// The coefficients. A silent signal gives `A(z) = 1` with no error.
+///
+/// # Panics
+///
+/// If fewer than `order + 1` autocorrelation lags are given.
+pub fn levinson_durbin(autocorrelation: &[f64], order: usize) -> Lpc {
+    assert!(
+        autocorrelation.len() > order,
+        "The autocorrelation must have at least order + 1 lags."
+    );
+
+    let mut coefficients = vec![0.0; order + 1];
+    coefficients[0] = 1.0;
+    let mut reflection = vec![0.0; order];
+    let mut error = autocorrelation[0];
+    if error <= 0.0 {
+        return Lpc {
+            coefficients,
+            reflection,
+            error: 0.0,
+        };
+    }
+
+    for i in 1..=order {
+        let correlation: f64 = (0..i)
+            .map(|j| coefficients[j] * autocorrelation[i - j])
+            .sum();
+        let k = -correlation / error;
+        reflection[i - 1] = k;
+
+        let previous = coefficients.clone();
+        for j in 1..i {
+            coefficients[j] += k * previous[i - j];
+        }
+        coefficients[i] = k;
+        error *= 1.0 - k * k;
+        if error <= 0.0 {
+            // The signal is perfectly predictable, higher orders add nothing
+            error = 0.0;
+            break;
+        }
+    }
+
+    Lpc {
+        coefficients,
+        reflection,
+        error,
+    }
+}
+
+/// Analyzes a frame with linear prediction.
+///
+/// # Arguments
+///
+/// * `frame` - The samples, already windowed.
+/// * `order` - The number of coefficients to predict with, about 2 plus the sample rate in
+///   kHz for speech.
+pub fn lpc(frame: &[f64], order: usize) -> Lpc {
+    levinson_durbin(&autocorrelation(frame, order), order)
+}
+
+/// Boosts the high frequencies with `y[n] = x[n] - coefficient * x[n - 1]`.
+///
+/// Voiced speech falls off by about 6 dB per octave, which would otherwise spend most of the
+/// LPC poles on the low frequencies.
+pub fn pre_emphasis(samples: &[f64], coefficient: f64) -> Vec<f64> {
+    let mut previous = 0.0;
+    samples
+        .iter()
+        .map(|&sample| {
+            let emphasized = sample - coefficient * previous;
+            previous = sample;
+            emphasized
+        })
+        .collect()
+}
+
+/// The linear prediction of one frame of a stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LpcFrame {
+    /// The time in seconds at the center of the frame.
+    pub time: f64,
+    pub lpc: Lpc,
+    pub formants: Vec<Formant>,
+}
+
+/// Runs linear prediction and formant estimation on consecutive frames of a stream.
+pub struct LpcAnalyzer {
+    sample_rate: u32,
+    order: usize,
+    hop_size: usize,
+    pre_emphasis: f64,
+    window: Vec<f64>,
+    buffer: Vec<f64>,
+    previous_sample: f64,
+    frames: usize,
+}
+
+impl LpcAnalyzer {
+    /// Creates an analyzer for speech, with 25 ms Hamming windowed frames every 10 ms, an
+    /// order of 2 plus the sample rate in kHz and a pre-emphasis of 0.97. At very low sample
+    /// rates the frames grow to one sample more than the order and the hop to one sample.
+    pub fn new(sample_rate: u32) -> Self {
+        let order = 2 + sample_rate as usize / 1000;
+        Self::with_order(
+            sample_rate,
+            order,
+            (sample_rate as usize / 40).max(order + 1),
+            (sample_rate as usize / 100).max(1),
+        )
+    }
+
+    /// Creates an analyzer with a pre-emphasis of 0.97 and a Hamming window.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample_rate` - The sample rate of the input signal.
+    /// * `order` - The number of coefficients to predict with.
+    /// * `frame_size` - The number of samples per frame, any length above `order`.
+    /// * `hop_size` - The number of samples between the starts of consecutive frames.
+    ///
+    /// # Panics
+    ///
+    /// If the frame is not longer than the order, or the hop size is 0.
+    pub fn with_order(sample_rate: u32, order: usize, frame_size: usize, hop_size: usize) -> Self {
+        assert!(
+            frame_size > order,
+            "Frame size must be larger than the order."
+        );
+        assert!(hop_size > 0, "Hop size must be greater than 0.");
+
+        Self {
+            sample_rate,
+            order,
+            hop_size,
+            pre_emphasis: 0.97,
+            window: Window::Hamming.generate_symmetric(frame_size),
+            buffer: Vec::with_capacity(frame_size * 2),
+            previous_sample: 0.0,
+            frames: 0,
+        }
+    }
+
+    /// Sets the pre-emphasis coefficient, 0.0 turns it off.
+    pub fn set_pre_emphasis(&mut self, coefficient: f64) {
+        self.pre_emphasis = coefficient;
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Feeds a chunk of samples and analyzes every frame that became complete.
+    pub fn process(&mut self, chunk: &[f64]) -> Vec<LpcFrame> {
+        for &sample in chunk {
+            self.buffer
+                .push(sample - self.pre_emphasis * self.previous_sample);
+            self.previous_sample = sample;
+        }
+
+        let frame_size = self.window.len();
+        let mut frames = Vec::new();
+        while self.buffer.len() >= frame_size {
+            let lpc = lpc(
+                &apply_window(&self.buffer[..frame_size], &self.window),
+                self.order,
+            );
+            frames.push(LpcFrame {
+                time: (self.frames * self.hop_size) as f64 / self.sample_rate as f64
+                    + frame_size as f64 / (2.0 * self.sample_rate as f64),
+                formants: lpc.formants(self.sample_rate),
+                lpc,
+            });
+            self.frames += 1;
+            self.buffer.drain(..self.hop_size.min(self.buffer.len()));
+        }
+        frames
+    }
+
+    /// Drops any buffered samples so the next chunk starts a new stream.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.previous_sample = 0.0;
+        self.frames = 0;
+    }
+}
+
+/// Finds the roots of the polynomial `z^p + c1 z^(p-1) + ... + cp` given `[1, c1, ..., cp]`,
+/// with the Durand-Kerner iteration.
+fn find_roots(coefficients: &[f64]) -> Vec<(f64, f64)> {
+    let degree = coefficients.len() - 1;
+    // Start on a circle inside the unit circle, at angles that are not symmetric about the
+    // real axis so that complex pairs can separate
+    let mut roots: Vec<(f64, f64)> = (0..degree)
+        .map(|i| {
+            let angle = 2.0 * PI * i as f64 / degree as f64 + 0.4;
+            (0.9 * angle.cos(), 0.9 * angle.sin())
+        })
+        .collect();
+
+    for _ in 0..MAX_ROOT_ITERATIONS {
+        let mut largest_step: f64 = 0.0;
+        for i in 0..degree {
+            let root = roots[i];
+            let value = evaluate(coefficients, root);
+            let mut denominator = (1.0, 0.0);
+            for (j, other) in roots.iter().enumerate() {
+                if j != i {
+                    denominator = multiply(denominator, (root.0 - other.0, root.1 - other.1));
+                }
+            }
+            let step = divide(value, denominator);
+            roots[i] = (root.0 - step.0, root.1 - step.1);
+            largest_step = largest_step.max((step.0 * step.0 + step.1 * step.1).sqrt());
+        }
+        if largest_step < 1e-14 {
+            break;
+        }
+    }
+    roots
+}
+
+/// Evaluates a monic polynomial at a complex point with Horner's scheme.
+fn evaluate(coefficients: &[f64], z: (f64, f64)) -> (f64, f64) {
+    coefficients.iter().fold((0.0, 0.0), |value, &c| {
+        let product = multiply(value, z);
+        (product.0 + c, product.1)
+    })
+}
+
+fn multiply(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+fn divide(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    let power = (b.0 * b.0 + b.1 * b.1).max(1e-300);
+    (
+        (a.0 * b.0 + a.1 * b.1) / power,
+        (a.1 * b.0 - a.0 * b.1) / power,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::iir::Biquad;
+    use crate::filter::Filter;
+    use crate::test_signals::noise;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    const SAMPLE_RATE: u32 = 10000;
+
+    /// Filters a signal through the all-pole filter `1 / A(z)`.
+    fn all_pole(input: &[f64], coefficients: &[f64]) -> Vec<f64> {
+        let mut output: Vec<f64> = Vec::with_capacity(input.len());
+        for (n, x) in input.iter().enumerate() {
+            let feedback: f64 = (1..coefficients.len())
+                .filter(|&k| k <= n)
+                .map(|k| coefficients[k] * output[n - k])
+                .sum();
+            output.push(x - feedback);
+        }
+        output
+    }
+
+    /// The coefficients of a pole pair with the given frequency and bandwidth.
+    fn resonance(frequency: f64, bandwidth: f64) -> [f64; 3] {
+        let radius = (-PI * bandwidth / SAMPLE_RATE as f64).exp();
+        let angle = 2.0 * PI * frequency / SAMPLE_RATE as f64;
+        [1.0, -2.0 * radius * angle.cos(), radius * radius]
+    }
+
+    #[test]
+    fn recovers_autoregressive_coefficients() {
+        let coefficients = [1.0, -1.2, 0.8, -0.2];
+        let signal = all_pole(&noise(1, 100_000), &coefficients);
+        let result = lpc(&signal, 3);
+
+        assert_eq!(result.order(), 3);
+        for (estimated, actual) in result.coefficients.iter().zip(coefficients.iter()) {
+            assert_float_absolute_eq!(*estimated, *actual, 0.02);
+        }
+        assert!(result.reflection.iter().all(|k| k.abs() < 1.0));
+        // The prediction error is the white noise, with a variance of 1/3
+        assert_float_absolute_eq!(result.error, 1.0 / 3.0, 0.01);
+    }
+
+    #[test]
+    fn levinson_durbin_matches_first_order_solution() {
+        // For order 1, a1 = -r1 / r0 and the error is r0 (1 - a1^2)
+        let result = levinson_durbin(&[2.0, 1.0, 0.3], 1);
+        assert_eq!(result.coefficients, vec![1.0, -0.5]);
+        assert_eq!(result.reflection, vec![-0.5]);
+        assert_float_absolute_eq!(result.error, 1.5, 1e-12);
+
+        let silent = levinson_durbin(&[0.0, 0.0, 0.0], 2);
+        assert_eq!(silent.coefficients, vec![1.0, 0.0, 0.0]);
+        assert_eq!(silent.error, 0.0);
+    }
+
+    #[test]
+    fn envelope_mean_equals_signal_power() {
+        let signal = all_pole(&noise(2, 8192), &resonance(1500.0, 200.0));
+        let windowed = apply_window(&signal, &Window::Hamming.generate_symmetric(8192));
+        let result = lpc(&windowed, 8);
+        let envelope = result.envelope(1024);
+
+        let mean = envelope.iter().sum::<f64>() / 1024.0;
+        assert_float_absolute_eq!(mean / autocorrelation(&windowed, 0)[0], 1.0, 0.01);
+
+        // The envelope peaks at the resonance, 1024 points cover 0 to 5000 Hz
+        let peak = (0..1024)
+            .max_by(|&a, &b| envelope[a].total_cmp(&envelope[b]))
+            .unwrap();
+        assert_float_absolute_eq!(peak as f64 * 5000.0 / 1024.0, 1500.0, 30.0);
+    }
+
+    #[test]
+    fn roots_of_a_known_polynomial() {
+        // (z - 0.5)(z^2 - z + 0.5) has roots 0.5 and 0.5 +- 0.5i
+        let lpc = Lpc {
+            coefficients: vec![1.0, -1.5, 1.0, -0.25],
+            reflection: vec![],
+            error: 1.0,
+        };
+        let mut roots = lpc.roots();
+        roots.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let expected = [(0.5, -0.5), (0.5, 0.0), (0.5, 0.5)];
+        for (root, expected) in roots.iter().zip(expected.iter()) {
+            assert_float_absolute_eq!(root.0, expected.0, 1e-9);
+            assert_float_absolute_eq!(root.1, expected.1, 1e-9);
+        }
+    }
+
+    #[test]
+    fn analyzer_finds_vowel_formants() {
+        // A 100 Hz pulse train through three resonances, roughly the vowel /a/
+        let formants = [(700.0, 80.0), (1200.0, 90.0), (2600.0, 120.0)];
+        let mut signal: Vec<f64> = (0..10000)
+            .map(|i| if i % 100 == 0 { 1.0 } else { 0.0 })
+            .collect();
+        for (frequency, bandwidth) in formants {
+            signal = all_pole(&signal, &resonance(frequency, bandwidth));
+        }
+        // The glottal pulses fall off towards the high frequencies
+        let mut lowpass = Biquad::lowpass(300.0, 0.707, SAMPLE_RATE);
+        let signal = lowpass.process(&signal);
+
+        let mut analyzer = LpcAnalyzer::new(SAMPLE_RATE);
+        assert_eq!(analyzer.order(), 12);
+        let mut frames = Vec::new();
+        for chunk in signal.chunks(333) {
+            frames.extend(analyzer.process(chunk));
+        }
+        assert_eq!(frames.len(), (10000 - 250) / 100 + 1);
+        assert_float_absolute_eq!(frames[0].time, 0.0125, 1e-12);
+
+        for frame in &frames[5..] {
+            for (frequency, _) in formants {
+                let nearest = frame
+                    .formants
+                    .iter()
+                    .map(|f| (f.frequency - frequency).abs())
+                    .fold(f64::INFINITY, f64::min);
+                assert!(
+                    nearest < 0.05 * frequency,
+                    "no formant near {} Hz",
+                    frequency
+                );
+            }
+        }
+    }
+    #[test]
+    fn analyzer_works_at_low_sample_rates() {
+        // 25 ms would be 2 samples, no more than the order
+        let mut analyzer = LpcAnalyzer::new(100);
+        assert_eq!(analyzer.order(), 2);
+        let frames = analyzer.process(&noise(3, 100));
+        assert_eq!(frames.len(), 100 - 3 + 1);
+    }
+}
@@ -0,0 +1,112 @@
+//! Float math for `no_std` builds.
+//!
+//! Without `std`, `f64` has no `sin`, `sqrt` and so on. Importing this trait gives it methods
+//! with the same names, implemented by `libm`, so the rest of the crate reads the same in
+//! both builds.
+
+pub(crate) trait Float {
+    fn asinh(self) -> Self;
+    fn atan2(self, other: Self) -> Self;
+    fn ceil(self) -> Self;
+    fn cos(self) -> Self;
+    fn cosh(self) -> Self;
+    fn exp(self) -> Self;
+    fn exp_m1(self) -> Self;
+    fn floor(self) -> Self;
+    fn ln(self) -> Self;
+    fn ln_1p(self) -> Self;
+    fn log10(self) -> Self;
+    fn log2(self) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+    fn powi(self, exponent: i32) -> Self;
+    fn round(self) -> Self;
+    fn sin(self) -> Self;
+    fn sin_cos(self) -> (Self, Self)
+    where
+        Self: Sized;
+    fn sinh(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn tan(self) -> Self;
+}
+
+impl Float for f64 {
+    fn asinh(self) -> Self {
+        libm::asinh(self)
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        libm::atan2(self, other)
+    }
+
+    fn ceil(self) -> Self {
+        libm::ceil(self)
+    }
+
+    fn cos(self) -> Self {
+        libm::cos(self)
+    }
+
+    fn cosh(self) -> Self {
+        libm::cosh(self)
+    }
+
+    fn exp(self) -> Self {
+        libm::exp(self)
+    }
+
+    fn exp_m1(self) -> Self {
+        libm::expm1(self)
+    }
+
+    fn floor(self) -> Self {
+        libm::floor(self)
+    }
+
+    fn ln(self) -> Self {
+        libm::log(self)
+    }
+
+    fn ln_1p(self) -> Self {
+        libm::log1p(self)
+    }
+
+    fn log10(self) -> Self {
+        libm::log10(self)
+    }
+
+    fn log2(self) -> Self {
+        libm::log2(self)
+    }
+
+    fn powf(self, exponent: Self) -> Self {
+        libm::pow(self, exponent)
+    }
+
+    fn powi(self, exponent: i32) -> Self {
+        libm::pow(self, exponent as f64)
+    }
+
+    fn round(self) -> Self {
+        libm::round(self)
+    }
+
+    fn sin(self) -> Self {
+        libm::sin(self)
+    }
+
+    fn sin_cos(self) -> (Self, Self) {
+        libm::sincos(self)
+    }
+
+    fn sinh(self) -> Self {
+        libm::sinh(self)
+    }
+
+    fn sqrt(self) -> Self {
+        libm::sqrt(self)
+    }
+
+    fn tan(self) -> Self {
+        libm::tan(self)
+    }
+}
@@ -0,0 +1,433 @@
+//! Reading and writing notes as Standard MIDI Files.
+//!
+//! Files are written as format 0 (a single track) with 480 ticks per quarter note at a
+//! constant 120 BPM, so one second is 960 ticks. Reading accepts format 0 and 1 files with
+//! any tempo map, and keeps only the notes.
+
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+/// The number of ticks per quarter note in written files.
+pub const TICKS_PER_QUARTER: u16 = 480;
+/// The tempo of written files in microseconds per quarter note (120 BPM).
+const MICROSECONDS_PER_QUARTER: u32 = 500_000;
+/// The largest delta time a variable-length quantity holds, about 77 hours in written files.
+const MAX_TICK: u32 = 0x0FFF_FFFF;
+
+/// A note with its times in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    /// The MIDI note number, where 60 is middle C and 69 is A4 at 440 Hz.
+    pub pitch: u8,
+    /// The time the note starts, in seconds.
+    pub onset: f64,
+    /// The time the note ends, in seconds.
+    pub offset: f64,
+    /// How hard the note is struck, from 1 to 127.
+    pub velocity: u8,
+}
+
+/// The reasons a file can not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MidiError {
+    /// The file ends in the middle of a chunk or an event.
+    Truncated,
+    /// The file does not start with an `MThd` header chunk.
+    BadHeader,
+    /// Format 2 files (independent sequences) are not supported.
+    UnsupportedFormat(u16),
+    /// SMPTE time divisions and a division of 0 ticks are not supported.
+    UnsupportedDivision(u16),
+    /// A data byte appeared where a status byte was needed.
+    MissingStatus,
+}
+
+impl fmt::Display for MidiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MidiError::Truncated => write!(f, "file ends in the middle of a chunk"),
+            MidiError::BadHeader => write!(f, "file does not start with a MIDI header"),
+            MidiError::UnsupportedFormat(format) => write!(f, "unsupported MIDI format {}", format),
+            MidiError::UnsupportedDivision(division) => {
+                write!(f, "unsupported time division {:#06x}", division)
+            }
+            MidiError::MissingStatus => write!(f, "event without a status byte"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MidiError {}
+
+/// Encodes notes as a Standard MIDI File on channel 1.
+///
+/// Times are rounded to the nearest tick (about 1 ms), and times beyond about 77 hours are
+/// clamped. Overlapping notes of the same pitch are written as they are, so the second
+/// note-on retriggers the pitch.
+///
+/// # Returns
+///
+/// The bytes of the file.
+pub fn encode_midi(notes: &[Note]) -> Vec<u8> {
+    // (tick, is note-on, pitch, velocity), note-offs first at the same tick
+    let ticks_per_second = TICKS_PER_QUARTER as f64 * 1_000_000.0 / MICROSECONDS_PER_QUARTER as f64;
+    // One tick is left for the note-off of a note at the last onset
+    let to_ticks =
+        |seconds: f64| ((seconds.max(0.0) * ticks_per_second + 0.5) as u32).min(MAX_TICK - 1);
+    let mut events: Vec<(u32, bool, u8, u8)> = Vec::with_capacity(2 * notes.len());
+    for note in notes {
+        let onset = to_ticks(note.onset);
+        let offset = to_ticks(note.offset).max(onset.saturating_add(1));
+        events.push((
+            onset,
+            true,
+            note.pitch.min(127),
+            note.velocity.clamp(1, 127),
+        ));
+        events.push((offset, false, note.pitch.min(127), 0));
+    }
+    events.sort_by_key(|&(tick, is_on, pitch, _)| (tick, is_on, pitch));
+
+    let mut track = Vec::new();
+    // Tempo meta event
+    track.extend_from_slice(&[0x00, 0xFF, 0x51, 0x03]);
+    track.extend_from_slice(&MICROSECONDS_PER_QUARTER.to_be_bytes()[1..]);
+    let mut previous = 0;
+    for (tick, is_on, pitch, velocity) in events {
+        write_variable_length(&mut track, tick - previous);
+        previous = tick;
+        if is_on {
+            track.extend_from_slice(&[0x90, pitch, velocity]);
+        } else {
+            track.extend_from_slice(&[0x80, pitch, 0x40]);
+        }
+    }
+    // End of track meta event
+    track.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+    let mut bytes = Vec::with_capacity(22 + track.len());
+    bytes.extend_from_slice(b"MThd");
+    bytes.extend_from_slice(&6u32.to_be_bytes());
+    bytes.extend_from_slice(&0u16.to_be_bytes());
+    bytes.extend_from_slice(&1u16.to_be_bytes());
+    bytes.extend_from_slice(&TICKS_PER_QUARTER.to_be_bytes());
+    bytes.extend_from_slice(b"MTrk");
+    bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&track);
+    bytes
+}
+
+/// Decodes the notes of a Standard MIDI File.
+///
+/// The notes of all tracks and channels are merged. A note-on with velocity 0 counts as a
+/// note-off, and notes still sounding at the end of their track end there.
+///
+/// # Returns
+///
+/// The notes, ordered by onset and then pitch.
+pub fn decode_midi(bytes: &[u8]) -> Result<Vec<Note>, MidiError> {
+    let mut reader = Reader { bytes, position: 0 };
+    if reader.take(4)? != b"MThd" {
+        return Err(MidiError::BadHeader);
+    }
+    let header_length = reader.read_u32()? as usize;
+    if header_length < 6 {
+        return Err(MidiError::BadHeader);
+    }
+    let format = reader.read_u16()?;
+    let track_count = reader.read_u16()?;
+    let division = reader.read_u16()?;
+    reader.take(header_length - 6)?;
+    if format > 1 {
+        return Err(MidiError::UnsupportedFormat(format));
+    }
+    if division & 0x8000 != 0 || division == 0 {
+        return Err(MidiError::UnsupportedDivision(division));
+    }
+
+    // Notes in ticks first, since a tempo change in one track applies to all of them
+    let mut tick_notes: Vec<(u64, u64, u8, u8)> = Vec::new();
+    let mut tempo_changes: Vec<(u64, u32)> = Vec::new();
+    for _ in 0..track_count {
+        let id = reader.take(4)?;
+        let length = reader.read_u32()? as usize;
+        let chunk = reader.take(length)?;
+        if id == b"MTrk" {
+            read_track(chunk, &mut tick_notes, &mut tempo_changes)?;
+        }
+    }
+
+    tempo_changes.sort_by_key(|&(tick, _)| tick);
+    let to_seconds = |tick: u64| ticks_to_seconds(tick, division as f64, &tempo_changes);
+    let mut notes: Vec<Note> = tick_notes
+        .iter()
+        .map(|&(onset, offset, pitch, velocity)| Note {
+            pitch,
+            onset: to_seconds(onset),
+            offset: to_seconds(offset),
+            velocity,
+        })
+        .collect();
+    notes.sort_by(|a, b| a.onset.total_cmp(&b.onset).then(a.pitch.cmp(&b.pitch)));
+    Ok(notes)
+}
+
+/// Reads the events of one track, pairing note-ons with the next note-off of the same
+/// channel and pitch.
+fn read_track(
+    chunk: &[u8],
+    notes: &mut Vec<(u64, u64, u8, u8)>,
+    tempo_changes: &mut Vec<(u64, u32)>,
+) -> Result<(), MidiError> {
+    let mut reader = Reader {
+        bytes: chunk,
+        position: 0,
+    };
+    // The onset tick and velocity of the sounding note of each channel and pitch
+    let mut sounding: Vec<Option<(u64, u8)>> = vec![None; 16 * 128];
+    let mut tick = 0;
+    let mut running_status = None;
+
+    while reader.position < chunk.len() {
+        tick += reader.read_variable_length()? as u64;
+        let mut status = reader.take(1)?[0];
+        if status < 0x80 {
+            // Running status: the byte just read is the first data byte
+            status = running_status.ok_or(MidiError::MissingStatus)?;
+            reader.position -= 1;
+        }
+
+        match status {
+            0xFF => {
+                running_status = None;
+                let kind = reader.take(1)?[0];
+                let length = reader.read_variable_length()? as usize;
+                let data = reader.take(length)?;
+                if kind == 0x51 && length == 3 {
+                    let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                    tempo_changes.push((tick, tempo));
+                } else if kind == 0x2F {
+                    break;
+                }
+            }
+            0xF0 | 0xF7 => {
+                running_status = None;
+                let length = reader.read_variable_length()? as usize;
+                reader.take(length)?;
+            }
+            _ => {
+                running_status = Some(status);
+                let channel = (status & 0x0F) as usize;
+                let data_length = match status & 0xF0 {
+                    0xC0 | 0xD0 => 1,
+                    _ => 2,
+                };
+                let data = reader.take(data_length)?;
+                let kind = status & 0xF0;
+                if kind == 0x90 || kind == 0x80 {
+                    let pitch = data[0] & 0x7F;
+                    let slot = channel * 128 + pitch as usize;
+                    // Any event on a sounding pitch ends the previous note
+                    if let Some((onset, velocity)) = sounding[slot].take() {
+                        notes.push((onset, tick, pitch, velocity));
+                    }
+                    if kind == 0x90 && data[1] > 0 {
+                        sounding[slot] = Some((tick, data[1]));
+                    }
+                }
+            }
+        }
+    }
+
+    for (slot, note) in sounding.iter().enumerate() {
+        if let Some((onset, velocity)) = note {
+            notes.push((*onset, tick, (slot % 128) as u8, *velocity));
+        }
+    }
+    Ok(())
+}
+
+/// Converts a tick to seconds, following the tempo changes (120 BPM before the first one).
+fn ticks_to_seconds(tick: u64, division: f64, tempo_changes: &[(u64, u32)]) -> f64 {
+    let mut seconds = 0.0;
+    let mut last_tick = 0;
+    let mut tempo = MICROSECONDS_PER_QUARTER;
+    for &(change_tick, change_tempo) in tempo_changes {
+        if change_tick >= tick {
+            break;
+        }
+        seconds += (change_tick - last_tick) as f64 * tempo as f64 / (division * 1_000_000.0);
+        last_tick = change_tick;
+        tempo = change_tempo;
+    }
+    seconds + (tick - last_tick) as f64 * tempo as f64 / (division * 1_000_000.0)
+}
+
+/// Writes a variable-length quantity: 7 bits per byte, most significant first, with the
+/// high bit set on every byte but the last.
+fn write_variable_length(bytes: &mut Vec<u8>, mut value: u32) {
+    let mut buffer = [0u8; 5];
+    let mut length = 0;
+    loop {
+        buffer[length] = (value & 0x7F) as u8;
+        length += 1;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+    }
+    for i in (0..length).rev() {
+        bytes.push(if i > 0 { buffer[i] | 0x80 } else { buffer[i] });
+    }
+}
+
+/// Reads big-endian values from a byte slice, failing at the end instead of panicking.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], MidiError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(MidiError::Truncated)?;
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, MidiError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, MidiError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_variable_length(&mut self) -> Result<u32, MidiError> {
+        let mut value: u32 = 0;
+        // At most 4 bytes, which is 28 bits
+        for _ in 0..4 {
+            let byte = self.take(1)?[0];
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(MidiError::Truncated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    #[test]
+    fn variable_length_quantities() {
+        let cases: [(u32, &[u8]); 5] = [
+            (0, &[0x00]),
+            (0x7F, &[0x7F]),
+            (0x80, &[0x81, 0x00]),
+            (0x3FFF, &[0xFF, 0x7F]),
+            (0x0FFF_FFFF, &[0xFF, 0xFF, 0xFF, 0x7F]),
+        ];
+        for (value, expected) in cases {
+            let mut bytes = Vec::new();
+            write_variable_length(&mut bytes, value);
+            assert_eq!(bytes, expected);
+            let mut reader = Reader {
+                bytes: &bytes,
+                position: 0,
+            };
+            assert_eq!(reader.read_variable_length(), Ok(value));
+        }
+    }
+
+    #[test]
+    fn notes_round_trip() {
+        let notes = [
+            Note {
+                pitch: 60,
+                onset: 0.0,
+                offset: 0.5,
+                velocity: 100,
+            },
+            Note {
+                pitch: 64,
+                onset: 0.0,
+                offset: 1.25,
+                velocity: 80,
+            },
+            Note {
+                pitch: 60,
+                onset: 0.5,
+                offset: 1.0,
+                velocity: 64,
+            },
+        ];
+        let bytes = encode_midi(&notes);
+        assert_eq!(&bytes[..4], b"MThd");
+
+        let decoded = decode_midi(&bytes).unwrap();
+        assert_eq!(decoded.len(), 3);
+        for (decoded, note) in decoded.iter().zip([notes[0], notes[1], notes[2]]) {
+            assert_eq!(decoded.pitch, note.pitch);
+            assert_eq!(decoded.velocity, note.velocity);
+            assert_float_absolute_eq!(decoded.onset, note.onset, 1e-3);
+            assert_float_absolute_eq!(decoded.offset, note.offset, 1e-3);
+        }
+    }
+
+    #[test]
+    fn notes_beyond_the_last_tick_are_clamped() {
+        let note = Note {
+            pitch: 60,
+            onset: 1e12,
+            offset: 1e12,
+            velocity: 100,
+        };
+        let decoded = decode_midi(&encode_midi(&[note])).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert!(decoded[0].offset >= decoded[0].onset);
+    }
+
+    #[test]
+    fn reads_running_status_and_tempo_changes() {
+        #[rustfmt::skip]
+        let track: &[u8] = &[
+            // 96 ticks per quarter, the first quarter at 60 BPM, then 120 BPM
+            0x00, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40,
+            0x00, 0x91, 0x45, 0x50,
+            // Running status: note-on with velocity 0 ends the note after a quarter
+            0x60, 0x45, 0x00,
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20,
+            // Meta events cancel the running status
+            0x00, 0x91, 0x45, 0x20,
+            0x60, 0x81, 0x45, 0x00,
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x00\x60MTrk");
+        bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(track);
+
+        let notes = decode_midi(&bytes).unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!((notes[0].pitch, notes[0].velocity), (69, 80));
+        assert_float_absolute_eq!(notes[0].offset, 1.0, 1e-9);
+        assert_eq!((notes[1].pitch, notes[1].velocity), (69, 32));
+        assert_float_absolute_eq!(notes[1].onset, 1.0, 1e-9);
+        assert_float_absolute_eq!(notes[1].offset, 1.5, 1e-9);
+
+        assert_eq!(decode_midi(&bytes[..20]), Err(MidiError::Truncated));
+        assert_eq!(decode_midi(b"RIFF"), Err(MidiError::BadHeader));
+        bytes[12..14].copy_from_slice(&[0, 0]);
+        assert_eq!(decode_midi(&bytes), Err(MidiError::UnsupportedDivision(0)));
+    }
+}
@@ -0,0 +1,191 @@
+//! FFT with precomputed twiddle factors that transforms buffers owned by the caller.
+//!
+//! `fft` allocates its result on every call, which is fine on a desktop but not in an
+//! interrupt handler on a microcontroller. An `FftPlan` allocates once when it is created
+//! and then works in place.
+
+use alloc::vec::Vec;
+use core::f64::consts::PI;
+
+use crate::bit_reverse;
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+/// A reusable FFT of a fixed size.
+///
+/// The transforms use the same conventions as `fft` and `ifft`: the forward transform
+/// divides by the size and the inverse does not scale.
+pub struct FftPlan {
+    size: usize,
+    bits: u32,
+    twiddle_real: Vec<f64>,
+    twiddle_imag: Vec<f64>,
+}
+
+impl FftPlan {
+    /// Creates a plan and precomputes its twiddle factors.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The transform size, must be a power of 2.
+    ///
+    /// # Panics
+    ///
+    /// If the size is not a power of 2 or is 0.
+    pub fn new(size: usize) -> Self {
+        if size == 0 || (size & (size - 1)) != 0 {
+            panic!("Size must be a power of 2 and greater than 0.");
+        }
+
+        let (twiddle_imag, twiddle_real) = (0..size / 2)
+            .map(|k| (-2.0 * PI * k as f64 / size as f64).sin_cos())
+            .unzip();
+
+        Self {
+            size,
+            bits: size.trailing_zeros(),
+            twiddle_real,
+            twiddle_imag,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Transforms a complex signal in place, like `fft`.
+    ///
+    /// # Panics
+    ///
+    /// If either buffer does not have the plan's size.
+    pub fn forward(&self, real: &mut [f64], imag: &mut [f64]) {
+        self.transform(real, imag, false);
+
+        let scale = 1.0 / self.size as f64;
+        real.iter_mut().for_each(|r| *r *= scale);
+        imag.iter_mut().for_each(|i| *i *= scale);
+    }
+
+    /// Transforms a real signal into the caller's buffers, like `fft`.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The real samples.
+    /// * `real` - Receives the real components of the spectrum.
+    /// * `imag` - Receives the imaginary components of the spectrum.
+    ///
+    /// # Panics
+    ///
+    /// If any of the slices does not have the plan's size.
+    pub fn forward_real(&self, input: &[f64], real: &mut [f64], imag: &mut [f64]) {
+        assert_eq!(input.len(), self.size, "Input must have the plan's size.");
+        real.copy_from_slice(input);
+        imag.fill(0.0);
+        self.forward(real, imag);
+    }
+
+    /// Transforms a spectrum back into a signal in place, like `ifft`.
+    ///
+    /// # Panics
+    ///
+    /// If either buffer does not have the plan's size.
+    pub fn inverse(&self, real: &mut [f64], imag: &mut [f64]) {
+        self.transform(real, imag, true);
+    }
+
+    fn transform(&self, real: &mut [f64], imag: &mut [f64], inverse: bool) {
+        assert_eq!(real.len(), self.size, "Buffers must have the plan's size.");
+        assert_eq!(imag.len(), self.size, "Buffers must have the plan's size.");
+
+        // Bit reversal, swapping each pair once
+        for i in 0..self.size {
+            let j = bit_reverse(i as u64, self.bits) as usize;
+            if j > i {
+                real.swap(i, j);
+                imag.swap(i, j);
+            }
+        }
+
+        let mut half_step = 1;
+        while half_step < self.size {
+            // Stage with butterflies of size 2 * half_step uses every stride-th twiddle factor
+            let stride = self.size / (2 * half_step);
+            for start in (0..self.size).step_by(2 * half_step) {
+                for j in 0..half_step {
+                    let twiddle_real = self.twiddle_real[j * stride];
+                    let mut twiddle_imag = self.twiddle_imag[j * stride];
+                    if inverse {
+                        twiddle_imag = -twiddle_imag;
+                    }
+
+                    let a = start + j;
+                    let b = a + half_step;
+                    let temp_real = twiddle_real * real[b] - twiddle_imag * imag[b];
+                    let temp_imag = twiddle_real * imag[b] + twiddle_imag * real[b];
+
+                    real[b] = real[a] - temp_real;
+                    imag[b] = imag[a] - temp_imag;
+                    real[a] += temp_real;
+                    imag[a] += temp_imag;
+                }
+            }
+            half_step *= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fft, ifft};
+    use alloc::vec;
+
+    #[test]
+    fn forward_matches_fft() {
+        let samples: Vec<f64> = (0..64).map(|i| ((i * 13) % 7) as f64 - 3.0).collect();
+        let expected = fft(&samples);
+
+        let plan = FftPlan::new(64);
+        let mut real = vec![0.0; 64];
+        let mut imag = vec![0.0; 64];
+        plan.forward_real(&samples, &mut real, &mut imag);
+
+        for k in 0..64 {
+            assert!((real[k] - expected.real[k]).abs() < 1e-12);
+            assert!((imag[k] - expected.imag[k]).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn inverse_matches_ifft_and_restores_signal() {
+        let samples: Vec<f64> = (0..32).map(|i| (i as f64 * 0.7).sin()).collect();
+        let plan = FftPlan::new(32);
+        let mut real = samples.clone();
+        let mut imag = vec![0.0; 32];
+
+        plan.forward(&mut real, &mut imag);
+        let expected = ifft(&fft(&samples));
+        plan.inverse(&mut real, &mut imag);
+
+        for i in 0..32 {
+            assert!((real[i] - samples[i]).abs() < 1e-12);
+            assert!((real[i] - expected.real[i]).abs() < 1e-12);
+            assert!(imag[i].abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn size_one_is_identity() {
+        let plan = FftPlan::new(1);
+        let (mut real, mut imag) = ([2.5], [0.0]);
+        plan.forward(&mut real, &mut imag);
+        assert_eq!(real, [2.5]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_wrong_buffer_size() {
+        let plan = FftPlan::new(8);
+        plan.forward(&mut [0.0; 4], &mut [0.0; 4]);
+    }
+}
@@ -0,0 +1,546 @@
+//! Sinusoidal modeling in the style of McAulay and Quatieri, "Speech Analysis/Synthesis
+//! Based on a Sinusoidal Representation" (1986).
+//!
+//! Every STFT frame is reduced to its spectral peaks, the peaks of consecutive frames are
+//! linked into partials that are born and die over time, and the partials are resynthesized
+//! with cubic phase interpolation. Whatever the partials do not explain is left in the
+//! residual.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::stft::Stft;
+use crate::window::Window;
+use crate::{wrap_phase, FftResult};
+
+/// Settings of the sinusoidal analysis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SinusoidalSettings {
+    /// The STFT frame size, must be a power of 2. It must span a few periods of the lowest
+    /// partial for its peak to be resolved.
+    pub frame_size: usize,
+    /// The number of samples between frames.
+    pub hop_size: usize,
+    /// The analysis window. Its main lobe must be narrower than the spacing of the partials,
+    /// and its side lobes must stay below `threshold_db` or they are taken for partials.
+    pub window: Window,
+    /// The largest number of peaks kept per frame, the loudest ones.
+    pub max_peaks: usize,
+    /// Peaks with an amplitude below this level in dB (1.0 is 0 dB) are ignored.
+    pub threshold_db: f64,
+    /// The largest frequency change in Hz between frames for a peak to continue a partial.
+    pub max_deviation: f64,
+    /// Partials shorter than this number of frames are dropped and end up in the residual.
+    pub min_length: usize,
+}
+
+impl Default for SinusoidalSettings {
+    fn default() -> Self {
+        Self {
+            frame_size: 2048,
+            hop_size: 256,
+            window: Window::BlackmanHarris,
+            max_peaks: 60,
+            threshold_db: -80.0,
+            max_deviation: 30.0,
+            min_length: 3,
+        }
+    }
+}
+
+/// A spectral peak, refined between bins by parabolic interpolation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectralPeak {
+    pub frequency: f64,
+    /// The amplitude of the sinusoid the peak belongs to.
+    pub amplitude: f64,
+    /// The phase of the sinusoid at the center of the frame.
+    pub phase: f64,
+}
+
+/// A partial, one sinusoid followed through consecutive frames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partial {
+    /// The index of the frame the partial is born in.
+    pub start_frame: usize,
+    /// The frequency in Hz of each frame.
+    pub frequencies: Vec<f64>,
+    /// The amplitude of each frame.
+    pub amplitudes: Vec<f64>,
+    /// The phase at the center of each frame.
+    pub phases: Vec<f64>,
+}
+
+impl Partial {
+    /// The number of frames the partial lasts.
+    pub fn len(&self) -> usize {
+        self.frequencies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frequencies.is_empty()
+    }
+
+    /// The index of the last frame of the partial.
+    pub fn end_frame(&self) -> usize {
+        self.start_frame + self.len() - 1
+    }
+
+    /// The average frequency, weighted by amplitude.
+    pub fn mean_frequency(&self) -> f64 {
+        let total: f64 = self.amplitudes.iter().sum();
+        if total == 0.0 {
+            return 0.0;
+        }
+        self.frequencies
+            .iter()
+            .zip(self.amplitudes.iter())
+            .map(|(f, a)| f * a)
+            .sum::<f64>()
+            / total
+    }
+
+    fn push(&mut self, peak: &SpectralPeak) {
+        self.frequencies.push(peak.frequency);
+        self.amplitudes.push(peak.amplitude);
+        self.phases.push(peak.phase);
+    }
+}
+
+/// The partials of a signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SinusoidalModel {
+    pub partials: Vec<Partial>,
+    /// The number of analyzed frames. Frame `i` is centered on sample `i * hop_size`.
+    pub frame_count: usize,
+    pub hop_size: usize,
+    /// The number of samples of the analyzed signal.
+    pub length: usize,
+    pub sample_rate: u32,
+}
+
+impl SinusoidalModel {
+    /// The time in seconds at the center of a frame.
+    pub fn frame_time(&self, index: usize) -> f64 {
+        (index * self.hop_size) as f64 / self.sample_rate as f64
+    }
+
+    /// Resynthesizes the signal from the partials.
+    ///
+    /// The phase of each partial is interpolated with a cubic that matches the measured
+    /// frequency and phase at both ends of every hop, and the amplitude linearly. A partial
+    /// fades in from silence over the hop before its first frame and fades out over the hop
+    /// after its last one.
+    ///
+    /// # Returns
+    ///
+    /// A signal of the same length as the analyzed one.
+    pub fn synthesize(&self) -> Vec<f64> {
+        let mut output = vec![0.0; self.length];
+        for partial in &self.partials {
+            self.synthesize_partial(partial, &mut output);
+        }
+        output
+    }
+
+    /// The part of a signal that the partials do not explain.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - The signal the model was analyzed from.
+    ///
+    /// # Returns
+    ///
+    /// The signal minus the resynthesized partials.
+    pub fn residual(&self, samples: &[f64]) -> Vec<f64> {
+        samples
+            .iter()
+            .zip(self.synthesize())
+            .map(|(sample, sinusoids)| sample - sinusoids)
+            .collect()
+    }
+
+    fn synthesize_partial(&self, partial: &Partial, output: &mut [f64]) {
+        let hop = self.hop_size as f64;
+        // Radians per sample
+        let omegas: Vec<f64> = partial
+            .frequencies
+            .iter()
+            .map(|f| 2.0 * PI * f / self.sample_rate as f64)
+            .collect();
+        let last = partial.len() - 1;
+
+        // Birth: the first frame's frequency, extrapolated back over one hop
+        let start = partial.start_frame as i64 * self.hop_size as i64;
+        let birth_phase = partial.phases[0] - omegas[0] * hop;
+        self.add_segment(
+            output,
+            start - self.hop_size as i64,
+            (0.0, partial.amplitudes[0]),
+            (birth_phase, partial.phases[0]),
+            (omegas[0], omegas[0]),
+        );
+
+        for l in 0..last {
+            self.add_segment(
+                output,
+                start + (l * self.hop_size) as i64,
+                (partial.amplitudes[l], partial.amplitudes[l + 1]),
+                (partial.phases[l], partial.phases[l + 1]),
+                (omegas[l], omegas[l + 1]),
+            );
+        }
+
+        // Death: the last frame's frequency, continued over one hop
+        let death_phase = partial.phases[last] + omegas[last] * hop;
+        self.add_segment(
+            output,
+            start + (last * self.hop_size) as i64,
+            (partial.amplitudes[last], 0.0),
+            (partial.phases[last], death_phase),
+            (omegas[last], omegas[last]),
+        );
+    }
+
+    /// Adds one hop of a partial starting at sample `start`, which may lie outside the signal.
+    fn add_segment(
+        &self,
+        output: &mut [f64],
+        start: i64,
+        amplitudes: (f64, f64),
+        phases: (f64, f64),
+        omegas: (f64, f64),
+    ) {
+        let hop = self.hop_size as f64;
+        let (theta0, theta1) = phases;
+        let (omega0, omega1) = omegas;
+
+        // The number of extra cycles that makes the phase curve the smoothest
+        let cycles =
+            ((theta0 + omega0 * hop - theta1) + (omega1 - omega0) * hop / 2.0) / (2.0 * PI);
+        let difference = theta1 - theta0 - omega0 * hop + 2.0 * PI * cycles.round();
+        let alpha = 3.0 / (hop * hop) * difference - (omega1 - omega0) / hop;
+        let beta = -2.0 / (hop * hop * hop) * difference + (omega1 - omega0) / (hop * hop);
+
+        for n in 0..self.hop_size {
+            let index = start + n as i64;
+            if index < 0 || index >= output.len() as i64 {
+                continue;
+            }
+            let t = n as f64;
+            let amplitude = amplitudes.0 + (amplitudes.1 - amplitudes.0) * t / hop;
+            let phase = theta0 + omega0 * t + alpha * t * t + beta * t * t * t;
+            output[index as usize] += amplitude * phase.cos();
+        }
+    }
+}
+
+/// Finds the spectral peaks of a frame.
+///
+/// # Arguments
+///
+/// * `frame` - The transform of the windowed frame.
+/// * `window` - The window coefficients the frame was multiplied by, which must be symmetric
+///   around the center of the frame.
+/// * `sample_rate` - The sample rate of the signal.
+/// * `threshold_db` - The smallest amplitude in dB of a peak.
+///
+/// # Returns
+///
+/// The peaks, ordered by frequency.
+pub fn find_spectral_peaks(
+    frame: &FftResult,
+    window: &[f64],
+    sample_rate: u32,
+    threshold_db: f64,
+) -> Vec<SpectralPeak> {
+    let size = frame.real.len();
+    let bins = size / 2 + 1;
+    // `fft` divides by the size, so a sinusoid of amplitude A peaks at A/2 times the mean
+    // of the window
+    let gain = window.iter().sum::<f64>() / (2.0 * size as f64);
+    let threshold = 10f64.powf(threshold_db / 20.0) * gain;
+    let magnitudes: Vec<f64> = (0..bins)
+        .map(|k| (frame.real[k] * frame.real[k] + frame.imag[k] * frame.imag[k]).sqrt())
+        .collect();
+
+    let mut peaks = Vec::new();
+    for k in 1..bins.saturating_sub(1) {
+        let (left, center, right) = (magnitudes[k - 1], magnitudes[k], magnitudes[k + 1]);
+        if center < threshold || center <= left || center < right {
+            continue;
+        }
+
+        // Parabolic interpolation on the log magnitudes of the peak and its neighbours
+        let (l, c, r) = (left.max(1e-20).ln(), center.ln(), right.max(1e-20).ln());
+        let denominator = l - 2.0 * c + r;
+        let offset = if denominator < 0.0 {
+            0.5 * (l - r) / denominator
+        } else {
+            0.0
+        };
+        let peak = c - 0.25 * (l - r) * offset;
+
+        // The window is symmetric around the center, so shifting the phase of bin k by half
+        // a frame gives the phase of the sinusoid at the center, whatever its exact frequency
+        let phase = frame.imag[k].atan2(frame.real[k]) + PI * k as f64;
+        peaks.push(SpectralPeak {
+            frequency: (k as f64 + offset) * sample_rate as f64 / size as f64,
+            amplitude: peak.exp() / gain,
+            phase: wrap_phase(phase),
+        });
+    }
+    peaks
+}
+
+/// Analyzes a signal into partials.
+///
+/// The signal is padded with half a frame of silence on both sides, so that frame `i` is
+/// centered on sample `i * hop_size` and the partials cover the whole signal.
+///
+/// # Arguments
+///
+/// * `samples` - The signal.
+/// * `sample_rate` - The sample rate of the signal.
+/// * `settings` - The analysis settings.
+///
+/// # Panics
+///
+/// If the frame size is not a power of 2 or the hop size is 0 or larger than the frame size.
+pub fn analyze(
+    samples: &[f64],
+    sample_rate: u32,
+    settings: &SinusoidalSettings,
+) -> SinusoidalModel {
+    let stft = Stft::new(settings.frame_size, settings.hop_size, settings.window);
+    let half = settings.frame_size / 2;
+    let frame_count = samples.len() / settings.hop_size + 1;
+    let mut padded = vec![0.0; half];
+    padded.extend_from_slice(samples);
+    padded.resize(
+        (frame_count - 1) * settings.hop_size + settings.frame_size,
+        0.0,
+    );
+
+    let mut tracker = PartialTracker::new(settings);
+    for frame in stft.analyze(&padded) {
+        let mut peaks =
+            find_spectral_peaks(&frame, stft.window(), sample_rate, settings.threshold_db);
+        if peaks.len() > settings.max_peaks {
+            peaks.sort_by(|a, b| b.amplitude.total_cmp(&a.amplitude));
+            peaks.truncate(settings.max_peaks);
+        }
+        tracker.push(&peaks);
+    }
+
+    SinusoidalModel {
+        partials: tracker.finish(),
+        frame_count,
+        hop_size: settings.hop_size,
+        length: samples.len(),
+        sample_rate,
+    }
+}
+
+/// Links the peaks of consecutive frames into partials.
+struct PartialTracker {
+    max_deviation: f64,
+    min_length: usize,
+    frame: usize,
+    active: Vec<Partial>,
+    finished: Vec<Partial>,
+}
+
+impl PartialTracker {
+    fn new(settings: &SinusoidalSettings) -> Self {
+        Self {
+            max_deviation: settings.max_deviation,
+            min_length: settings.min_length.max(1),
+            frame: 0,
+            active: Vec::new(),
+            finished: Vec::new(),
+        }
+    }
+
+    /// Continues the active partials with the peaks of the next frame. The closest pairs of
+    /// partial and peak are matched first. Partials left without a peak die and peaks left
+    /// without a partial give birth to new ones.
+    fn push(&mut self, peaks: &[SpectralPeak]) {
+        let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+        for (p, partial) in self.active.iter().enumerate() {
+            let frequency = partial.frequencies[partial.len() - 1];
+            for (k, peak) in peaks.iter().enumerate() {
+                let distance = (peak.frequency - frequency).abs();
+                if distance <= self.max_deviation {
+                    candidates.push((distance, p, k));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut continued = vec![false; self.active.len()];
+        let mut claimed = vec![false; peaks.len()];
+        for (_, p, k) in candidates {
+            if !continued[p] && !claimed[k] {
+                self.active[p].push(&peaks[k]);
+                continued[p] = true;
+                claimed[k] = true;
+            }
+        }
+
+        let mut active = Vec::with_capacity(self.active.len());
+        for (partial, continued) in self.active.drain(..).zip(continued) {
+            if continued {
+                active.push(partial);
+            } else if partial.len() >= self.min_length {
+                self.finished.push(partial);
+            }
+        }
+        for (peak, _) in peaks.iter().zip(claimed).filter(|(_, claimed)| !claimed) {
+            let mut partial = Partial {
+                start_frame: self.frame,
+                frequencies: Vec::new(),
+                amplitudes: Vec::new(),
+                phases: Vec::new(),
+            };
+            partial.push(peak);
+            active.push(partial);
+        }
+        self.active = active;
+        self.frame += 1;
+    }
+
+    /// Ends the partials that are still active and returns all of them, ordered by birth.
+    fn finish(mut self) -> Vec<Partial> {
+        let min_length = self.min_length;
+        self.finished
+            .extend(self.active.drain(..).filter(|p| p.len() >= min_length));
+        self.finished.sort_by_key(|p| p.start_frame);
+        self.finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fft;
+    use crate::window::apply_window;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    fn energy(samples: &[f64]) -> f64 {
+        samples.iter().map(|s| s * s).sum()
+    }
+
+    #[test]
+    fn peak_estimates_frequency_amplitude_and_phase() {
+        let window = Window::Hann.generate(1024);
+        let (frequency, amplitude, phase) = (1234.5, 0.3, 0.7);
+        let frame: Vec<f64> = (0..1024)
+            .map(|n| {
+                let t = (n as f64 - 512.0) / SAMPLE_RATE as f64;
+                amplitude * (2.0 * PI * frequency * t + phase).cos()
+            })
+            .collect();
+        let peaks = find_spectral_peaks(
+            &fft(&apply_window(&frame, &window)),
+            &window,
+            SAMPLE_RATE,
+            -30.0,
+        );
+
+        assert_eq!(peaks.len(), 1);
+        assert_float_absolute_eq!(peaks[0].frequency, frequency, 0.5);
+        assert_float_absolute_eq!(peaks[0].amplitude, amplitude, 0.005);
+        assert_float_absolute_eq!(peaks[0].phase, phase, 0.01);
+    }
+
+    #[test]
+    fn steady_partials_resynthesize_the_signal() {
+        let samples: Vec<f64> = (0..16000)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                0.5 * (2.0 * PI * 440.0 * t).sin() + 0.25 * (2.0 * PI * 1320.0 * t + 1.0).sin()
+            })
+            .collect();
+        let model = analyze(&samples, SAMPLE_RATE, &SinusoidalSettings::default());
+
+        assert_eq!(model.partials.len(), 2);
+        assert_eq!(model.frame_count, 16000 / 256 + 1);
+        for (partial, (frequency, amplitude)) in
+            model.partials.iter().zip([(440.0, 0.5), (1320.0, 0.25)])
+        {
+            let middle = partial.len() / 2;
+            assert_float_absolute_eq!(partial.frequencies[middle], frequency, 0.5);
+            assert_float_absolute_eq!(partial.amplitudes[middle], amplitude, 0.005);
+        }
+
+        // Away from the edges, where the padding lowers the amplitudes, the residual is tiny
+        let residual = model.residual(&samples);
+        assert!(energy(&residual[2048..14000]) < 1e-4 * energy(&samples[2048..14000]));
+    }
+
+    #[test]
+    fn glide_is_followed_by_one_partial() {
+        // From 500 Hz to 1500 Hz over a second
+        let samples: Vec<f64> = (0..16000)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                0.5 * (2.0 * PI * (500.0 * t + 500.0 * t * t)).sin()
+            })
+            .collect();
+        // A long frame smears the glide over many bins, which lowers the peak
+        let settings = SinusoidalSettings {
+            frame_size: 512,
+            hop_size: 128,
+            ..SinusoidalSettings::default()
+        };
+        let model = analyze(&samples, SAMPLE_RATE, &settings);
+
+        let partial = model.partials.iter().max_by_key(|p| p.len()).unwrap();
+        assert!(partial.len() >= model.frame_count - 2);
+        for frame in [32, 62, 94] {
+            let expected = 500.0 + 1000.0 * model.frame_time(frame);
+            assert_float_absolute_eq!(
+                partial.frequencies[frame - partial.start_frame],
+                expected,
+                2.0
+            );
+        }
+
+        let residual = model.residual(&samples);
+        assert!(energy(&residual[2048..14000]) < 1e-2 * energy(&samples[2048..14000]));
+    }
+
+    #[test]
+    fn partials_are_born_and_die_with_their_notes() {
+        // 600 Hz for the first half, 900 Hz for the second
+        let samples: Vec<f64> = (0..16000)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let frequency = if i < 8000 { 600.0 } else { 900.0 };
+                0.5 * (2.0 * PI * frequency * t).sin()
+            })
+            .collect();
+        let model = analyze(&samples, SAMPLE_RATE, &SinusoidalSettings::default());
+
+        let low = model
+            .partials
+            .iter()
+            .find(|p| (p.mean_frequency() - 600.0).abs() < 5.0)
+            .unwrap();
+        let high = model
+            .partials
+            .iter()
+            .find(|p| (p.mean_frequency() - 900.0).abs() < 5.0)
+            .unwrap();
+        // The switch is at frame 31.25, and the frames overlap it by four frames on each side
+        assert!(low.start_frame <= 1);
+        assert!((31..=36).contains(&low.end_frame()));
+        assert!((27..=31).contains(&high.start_frame));
+        assert!(high.end_frame() >= model.frame_count - 2);
+    }
+}
@@ -0,0 +1,262 @@
+//! Short-time Fourier transform (STFT) built on `fft`, and its inverse by overlap-add.
+
+use alloc::{vec, vec::Vec};
+
+use crate::window::{apply_window, Window};
+use crate::{fft, ifft, FftResult};
+
+/// Splits a signal into overlapping windowed frames and transforms each frame.
+///
+/// The same instance can be used offline with `analyze` or fed chunk by chunk with
+/// `process`, which buffers the samples that do not yet fill a frame.
+pub struct Stft {
+    frame_size: usize,
+    hop_size: usize,
+    window: Vec<f64>,
+    buffer: Vec<f64>,
+}
+
+impl Stft {
+    /// Creates a new STFT.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_size` - The length of each frame, must be a power of 2.
+    /// * `hop_size` - The number of samples between the starts of consecutive frames.
+    /// * `window` - The window applied to every frame.
+    ///
+    /// # Panics
+    ///
+    /// If the frame size is not a power of 2 or the hop size is 0 or larger than the frame size.
+    pub fn new(frame_size: usize, hop_size: usize, window: Window) -> Self {
+        if frame_size == 0 || (frame_size & (frame_size - 1)) != 0 {
+            panic!("Frame size must be a power of 2 and greater than 0.");
+        }
+        if hop_size == 0 || hop_size > frame_size {
+            panic!("Hop size must be greater than 0 and at most the frame size.");
+        }
+
+        Self {
+            frame_size,
+            hop_size,
+            window: window.generate(frame_size),
+            buffer: Vec::with_capacity(frame_size * 2),
+        }
+    }
+
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    pub fn hop_size(&self) -> usize {
+        self.hop_size
+    }
+
+    /// The window coefficients applied to each frame.
+    pub fn window(&self) -> &[f64] {
+        &self.window
+    }
+
+    /// Transforms every complete frame of a signal, starting at the first sample.
+    ///
+    /// Trailing samples that do not fill a whole frame are ignored.
+    pub fn analyze(&self, samples: &[f64]) -> Vec<FftResult> {
+        if samples.len() < self.frame_size {
+            return Vec::new();
+        }
+
+        (0..=samples.len() - self.frame_size)
+            .step_by(self.hop_size)
+            .map(|start| self.transform(&samples[start..start + self.frame_size]))
+            .collect()
+    }
+
+    /// Feeds a chunk of a stream and transforms every frame that became complete.
+    ///
+    /// Frames are aligned as if `analyze` had been called on the concatenation of all chunks.
+    pub fn process(&mut self, chunk: &[f64]) -> Vec<FftResult> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut frames = Vec::new();
+        while self.buffer.len() >= self.frame_size {
+            frames.push(self.transform(&self.buffer[..self.frame_size]));
+            self.buffer.drain(..self.hop_size);
+        }
+        frames
+    }
+
+    /// Drops any buffered samples so the next chunk starts a new stream.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// The time in seconds at the center of a frame.
+    pub fn frame_time(&self, index: usize, sample_rate: u32) -> f64 {
+        (index * self.hop_size + self.frame_size / 2) as f64 / sample_rate as f64
+    }
+
+    fn transform(&self, frame: &[f64]) -> FftResult {
+        fft(&apply_window(frame, &self.window))
+    }
+}
+
+/// Turns (possibly modified) STFT frames back into a signal by weighted overlap-add.
+///
+/// Each inverse-transformed frame is multiplied by the synthesis window and added to the
+/// output, which is then divided by the overlapping sum of the squared windows. With
+/// unmodified frames this reconstructs the input exactly, except for the first
+/// `frame_size - hop_size` samples which are only partially covered by frames.
+pub struct OverlapAdd {
+    frame_size: usize,
+    hop_size: usize,
+    window: Vec<f64>,
+    normalization: Vec<f64>,
+    buffer: Vec<f64>,
+}
+
+impl OverlapAdd {
+    /// Creates a new overlap-add synthesizer matching an `Stft` with the same parameters.
+    ///
+    /// # Panics
+    ///
+    /// If the frame size is not a power of 2 or the hop size is 0 or larger than the frame size.
+    pub fn new(frame_size: usize, hop_size: usize, window: Window) -> Self {
+        if frame_size == 0 || (frame_size & (frame_size - 1)) != 0 {
+            panic!("Frame size must be a power of 2 and greater than 0.");
+        }
+        if hop_size == 0 || hop_size > frame_size {
+            panic!("Hop size must be greater than 0 and at most the frame size.");
+        }
+
+        let window = window.generate(frame_size);
+        let mut normalization = vec![0.0; hop_size];
+        for (i, w) in window.iter().enumerate() {
+            normalization[i % hop_size] += w * w;
+        }
+
+        Self {
+            frame_size,
+            hop_size,
+            window,
+            normalization,
+            buffer: vec![0.0; frame_size],
+        }
+    }
+
+    /// Adds the next frame and returns the `hop_size` output samples that are now complete.
+    ///
+    /// # Panics
+    ///
+    /// If the frame does not have `frame_size` bins.
+    pub fn process(&mut self, frame: &FftResult) -> Vec<f64> {
+        assert_eq!(
+            frame.real.len(),
+            self.frame_size,
+            "Frame must have the same size as the synthesizer."
+        );
+
+        let signal = ifft(frame);
+        for (i, (sample, w)) in signal.real.iter().zip(self.window.iter()).enumerate() {
+            self.buffer[i] += sample * w;
+        }
+
+        let output: Vec<f64> = self.buffer[..self.hop_size]
+            .iter()
+            .enumerate()
+            .map(|(i, sample)| self.normalize(i, *sample))
+            .collect();
+
+        self.buffer.drain(..self.hop_size);
+        self.buffer.resize(self.frame_size, 0.0);
+        output
+    }
+
+    /// Returns the samples still held back after the last frame, and resets the synthesizer.
+    pub fn finish(&mut self) -> Vec<f64> {
+        let tail_length = self.frame_size - self.hop_size;
+        let output = self.buffer[..tail_length]
+            .iter()
+            .enumerate()
+            .map(|(i, sample)| self.normalize(i, *sample))
+            .collect();
+
+        self.buffer = vec![0.0; self.frame_size];
+        output
+    }
+
+    /// Synthesizes a complete signal from a sequence of frames.
+    pub fn synthesize(&mut self, frames: &[FftResult]) -> Vec<f64> {
+        let mut output: Vec<f64> = frames
+            .iter()
+            .flat_map(|frame| self.process(frame))
+            .collect();
+        output.extend(self.finish());
+        output
+    }
+
+    fn normalize(&self, index: usize, sample: f64) -> f64 {
+        let norm = self.normalization[index % self.hop_size];
+        if norm > 1e-12 {
+            sample / norm
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyze_counts_complete_frames() {
+        let stft = Stft::new(8, 4, Window::Hann);
+        assert_eq!(stft.analyze(&[0.0; 7]).len(), 0);
+        assert_eq!(stft.analyze(&[0.0; 8]).len(), 1);
+        assert_eq!(stft.analyze(&[0.0; 20]).len(), 4);
+    }
+
+    #[test]
+    fn streaming_matches_offline() {
+        let samples: Vec<f64> = (0..100).map(|i| (i as f64 * 0.3).sin()).collect();
+        let offline = Stft::new(16, 4, Window::Hann).analyze(&samples);
+
+        let mut stft = Stft::new(16, 4, Window::Hann);
+        let streamed: Vec<FftResult> = samples
+            .chunks(7)
+            .flat_map(|chunk| stft.process(chunk))
+            .collect();
+
+        assert_eq!(offline.len(), streamed.len());
+        for (a, b) in offline.iter().zip(streamed.iter()) {
+            assert_eq!(a.real, b.real);
+            assert_eq!(a.imag, b.imag);
+        }
+    }
+
+    #[test]
+    fn overlap_add_reconstructs_signal() {
+        let samples: Vec<f64> = (0..256)
+            .map(|i| (i as f64 * 0.1).sin() + 0.01 * i as f64)
+            .collect();
+        for hop in [4, 8] {
+            let frames = Stft::new(16, hop, Window::Hann).analyze(&samples);
+            let output = OverlapAdd::new(16, hop, Window::Hann).synthesize(&frames);
+
+            assert_eq!(output.len(), samples.len());
+            for i in 16..samples.len() - 16 {
+                assert!(
+                    (output[i] - samples[i]).abs() < 1e-9,
+                    "sample {} differs",
+                    i
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_hop_larger_than_frame() {
+        Stft::new(8, 9, Window::Hann);
+    }
+}
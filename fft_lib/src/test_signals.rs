@@ -0,0 +1,60 @@
+//! Deterministic signals shared by the tests.
+
+use alloc::vec::Vec;
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+/// A small linear congruential generator, so the tests need no dependencies.
+pub(crate) struct Random(pub(crate) u64);
+
+impl Random {
+    /// The next number, uniform in [0, 1).
+    pub(crate) fn uniform(&mut self) -> f64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// The next number, uniform in [-1, 1).
+    pub(crate) fn signed(&mut self) -> f64 {
+        self.uniform() * 2.0 - 1.0
+    }
+}
+
+/// Uniform white noise in [-1, 1) from a seed.
+pub(crate) fn noise(seed: u64, length: usize) -> Vec<f64> {
+    let mut random = Random(seed);
+    (0..length).map(|_| random.signed()).collect()
+}
+
+/// A sine at half of full scale.
+pub(crate) fn sine(frequency: f64, sample_rate: u32, length: usize) -> Vec<f64> {
+    (0..length)
+        .map(|i| 0.5 * (2.0 * PI * frequency * i as f64 / sample_rate as f64).sin())
+        .collect()
+}
+
+/// Random chords of three notes between 200 and 3000 Hz, a new chord every quarter second.
+pub(crate) fn music(seed: u64, seconds: usize, sample_rate: u32) -> Vec<f64> {
+    let mut random = Random(seed);
+    let chord_length = sample_rate as usize / 4;
+    let mut samples = Vec::with_capacity(seconds * sample_rate as usize);
+    for _ in 0..seconds * 4 {
+        let frequencies: Vec<f64> = (0..3).map(|_| 200.0 + 2800.0 * random.uniform()).collect();
+        for i in 0..chord_length {
+            let t = i as f64 / sample_rate as f64;
+            let envelope = (-4.0 * t).exp();
+            samples.push(
+                frequencies
+                    .iter()
+                    .map(|f| 0.2 * envelope * (2.0 * PI * f * t).sin())
+                    .sum(),
+            );
+        }
+    }
+    samples
+}
@@ -0,0 +1,364 @@
+//! Rough polyphonic note transcription from a log-frequency spectrogram.
+//!
+//! Every frame is reduced to one power per semitone. The pitches sounding in a frame are
+//! found one at a time: the pitch whose harmonics add up to the most power wins, and its
+//! harmonics are removed before looking for the next one. Runs of frames with the same
+//! pitch become notes, timed by where the power of the pitch rises and falls fastest, and
+//! split where it rises again while the note is sounding.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+use crate::get_frequencies;
+use crate::log_spectrum::{midi_to_frequency, LogFrequencySpectrum};
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::midi::Note;
+use crate::stft::Stft;
+use crate::window::Window;
+
+/// The lowest pitch that is transcribed (A0).
+pub const MIN_PITCH: u8 = 21;
+/// The highest pitch that is transcribed (C8).
+pub const MAX_PITCH: u8 = 108;
+
+/// The number of harmonics summed for the salience of a pitch.
+const HARMONICS: usize = 6;
+/// The largest number of simultaneous notes.
+const MAX_POLYPHONY: usize = 6;
+/// Pitches whose fundamental is quieter than this level in dBFS are ignored.
+const MIN_LEVEL_DB: f64 = -60.0;
+/// A pitch counts only if its salience is at least this fraction of the strongest pitch.
+const RELATIVE_SALIENCE: f64 = 0.005;
+/// The shortest note in seconds.
+const MIN_NOTE_DURATION: f64 = 0.06;
+/// The factor the power of a sounding pitch must rise by within a frame to start a new note.
+const ATTACK_RATIO: f64 = 2.5;
+/// The velocity range covers this many dB below full scale.
+const VELOCITY_RANGE_DB: f64 = 60.0;
+
+/// Converts a velocity to the amplitude of a note's fundamental, from -60 dBFS at 0 up to
+/// full scale at 127.
+pub fn velocity_to_amplitude(velocity: u8) -> f64 {
+    10f64.powf((velocity as f64 / 127.0 - 1.0) * VELOCITY_RANGE_DB / 20.0)
+}
+
+/// Converts the amplitude of a note's fundamental to a velocity, the inverse of
+/// `velocity_to_amplitude`.
+pub fn amplitude_to_velocity(amplitude: f64) -> u8 {
+    let db = 20.0 * amplitude.max(1e-12).log10();
+    (127.0 * (1.0 + db / VELOCITY_RANGE_DB))
+        .round()
+        .clamp(1.0, 127.0) as u8
+}
+
+/// Transcribes the notes of a recording.
+///
+/// # Arguments
+///
+/// * `samples` - The mono signal.
+/// * `sample_rate` - The sample rate of the signal.
+///
+/// # Returns
+///
+/// The notes, ordered by onset and then pitch. The velocities come from the loudest frame
+/// of each note, mapped with `amplitude_to_velocity`.
+pub fn transcribe(samples: &[f64], sample_rate: u32) -> Vec<Note> {
+    // About 90 ms frames resolve a semitone down to around A2
+    let frame_size = ((sample_rate as f64 * 0.09) as usize).next_power_of_two();
+    let hop_size = frame_size / 8;
+    let stft = Stft::new(frame_size, hop_size, Window::Hann);
+    // A sinusoid of amplitude A has a one-sided power of A^2 / 4 times the mean of w^2
+    let power_gain = stft.window().iter().map(|w| w * w).sum::<f64>() / (4.0 * frame_size as f64);
+
+    // The power of every semitone in every frame, up to the highest harmonic needed
+    let pitches = (MAX_PITCH - MIN_PITCH) as usize + 1 + harmonic_offset(HARMONICS);
+    let octaves = pitches.div_ceil(12);
+    // Frame t is centered on sample t * hop_size, and the frames reach past the end
+    let mut padded = vec![0.0; frame_size / 2];
+    padded.extend_from_slice(samples);
+    padded.resize(padded.len() + frame_size, 0.0);
+    let powers: Vec<Vec<f64>> = stft
+        .analyze(&padded)
+        .iter()
+        .map(|frame| {
+            let spectrum = LogFrequencySpectrum::from_frequencies(
+                &get_frequencies(frame, sample_rate),
+                midi_to_frequency(MIN_PITCH as f64),
+                12,
+                octaves,
+            );
+            spectrum.powers.iter().map(|p| p / power_gain).collect()
+        })
+        .collect();
+    // The Hann main lobe is four frequency bins wide, which covers more semitones at low pitches
+    let resolution = sample_rate as f64 / frame_size as f64;
+    let reaches: Vec<usize> = (0..octaves * 12)
+        .map(|k| {
+            let frequency = midi_to_frequency((MIN_PITCH as usize + k) as f64);
+            ((12.0 * (1.0 + 2.0 * resolution / frequency).log2()).ceil() as usize).max(1)
+        })
+        .collect();
+    let active: Vec<Vec<bool>> = powers
+        .iter()
+        .map(|frame| detect_pitches(frame, &reaches))
+        .collect();
+
+    let frame_time = |t: usize| (t * hop_size) as f64 / sample_rate as f64;
+    let hop = hop_size as f64 / sample_rate as f64;
+    let window_frames = frame_size / hop_size;
+    let min_frames = (MIN_NOTE_DURATION / hop).ceil() as usize;
+
+    let mut notes = Vec::new();
+    for p in 0..=(MAX_PITCH - MIN_PITCH) as usize {
+        let power: Vec<f64> = powers.iter().map(|frame| frame[p]).collect();
+        // The change in power between each frame and the previous one
+        let rise: Vec<f64> = (0..power.len())
+            .map(|t| {
+                if t == 0 {
+                    power[0]
+                } else {
+                    power[t] - power[t - 1]
+                }
+            })
+            .collect();
+
+        for (start, end) in segments(active.iter().map(|frame| frame[p]), min_frames) {
+            let last = power.len() - 1;
+            let onset_frame = (start.saturating_sub(window_frames)
+                ..=(start + window_frames).min(last))
+                .max_by(|&a, &b| rise[a].total_cmp(&rise[b]))
+                .unwrap();
+            let offset_frame = (end.saturating_sub(window_frames).max(1)
+                ..=(end + window_frames).min(last))
+                .min_by(|&a, &b| rise[a].total_cmp(&rise[b]))
+                .unwrap_or(end);
+
+            // The power rises or falls fastest when the change is at the center of the window
+            let mut onsets = vec![onset_frame];
+            for t in onset_frame + window_frames..offset_frame.saturating_sub(window_frames / 2) {
+                let is_steepest =
+                    (t - window_frames / 2..=t + window_frames / 2).all(|u| rise[u] <= rise[t]);
+                if is_steepest && power[t] > ATTACK_RATIO * power[t - window_frames] {
+                    onsets.push(t);
+                }
+            }
+            for (i, &onset) in onsets.iter().enumerate() {
+                let next = onsets.get(i + 1).copied().unwrap_or(offset_frame);
+                if next <= onset {
+                    continue;
+                }
+                let peak = power[onset..next].iter().fold(0.0_f64, |m, &v| m.max(v));
+                notes.push(Note {
+                    pitch: MIN_PITCH + p as u8,
+                    onset: (frame_time(onset) - hop / 2.0).max(0.0),
+                    offset: frame_time(next) - hop / 2.0,
+                    velocity: amplitude_to_velocity(peak.sqrt()),
+                });
+            }
+        }
+    }
+
+    notes.sort_by(|a, b| a.onset.total_cmp(&b.onset).then(a.pitch.cmp(&b.pitch)));
+    notes
+}
+
+/// Renders notes with a simple additive synthesizer, to listen to a transcription or to
+/// test it. Each note has `HARMONICS` harmonics falling off as 1/h, a 5 ms attack, a gentle
+/// decay and a 30 ms release after its offset.
+///
+/// # Returns
+///
+/// The signal, long enough for the release of the last note.
+pub fn synthesize(notes: &[Note], sample_rate: u32) -> Vec<f64> {
+    let rate = sample_rate as f64;
+    let (attack, release) = (0.005, 0.03);
+    let end = notes.iter().fold(0.0_f64, |end, n| end.max(n.offset));
+    let mut output = vec![0.0; ((end + release) * rate).ceil() as usize];
+
+    for note in notes {
+        let frequency = midi_to_frequency(note.pitch as f64);
+        let amplitude = velocity_to_amplitude(note.velocity);
+        let start = (note.onset * rate).round() as usize;
+        let stop = (((note.offset + release) * rate).round() as usize).min(output.len());
+        for (i, sample) in output[start.min(stop)..stop].iter_mut().enumerate() {
+            let t = i as f64 / rate;
+            let envelope = (t / attack).min(1.0)
+                * (-1.5 * t).exp()
+                * ((note.offset + release - note.onset - t) / release).clamp(0.0, 1.0);
+            *sample += amplitude
+                * envelope
+                * (1..=HARMONICS)
+                    .filter(|&h| frequency * (h as f64) < rate / 2.0)
+                    .map(|h| (2.0 * PI * frequency * h as f64 * t).sin() / h as f64)
+                    .sum::<f64>();
+        }
+    }
+    output
+}
+
+/// Finds the pitches sounding in a frame by iterative estimation and cancellation.
+fn detect_pitches(powers: &[f64], reaches: &[usize]) -> Vec<bool> {
+    let candidates = (MAX_PITCH - MIN_PITCH) as usize + 1;
+    let min_power = 10f64.powf(MIN_LEVEL_DB / 10.0);
+    let mut residual = powers.to_vec();
+    let mut active = vec![false; candidates];
+    let mut strongest: Option<f64> = None;
+
+    for _ in 0..MAX_POLYPHONY {
+        // The fundamental itself must be a peak, which rules out pitches below the played
+        // ones. Two partials in phase at most double their summed power, so a harmonic of
+        // the notes found so far is only a new note if most of its power is left.
+        let best = (0..candidates)
+            .filter(|&p| {
+                residual[p] >= min_power
+                    && (p == 0 || residual[p] >= residual[p - 1])
+                    && residual[p] >= residual[p + 1]
+                    && spread(&residual, p, reaches[p]) > 0.5 * spread(powers, p, reaches[p])
+            })
+            .map(|p| (p, salience(&residual, p)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        let Some((pitch, salience)) = best else {
+            break;
+        };
+        if salience < RELATIVE_SALIENCE * *strongest.get_or_insert(salience) {
+            break;
+        }
+        active[pitch] = true;
+
+        // Remove the fundamental and what a 1/h harmonic series would put on the harmonics
+        let fundamental = spread(&residual, pitch, reaches[pitch]);
+        for h in 1..=HARMONICS {
+            let index = pitch + harmonic_offset(h);
+            let power = spread(&residual, index, reaches[index]);
+            let share = if power > 0.0 {
+                (fundamental / (h * h) as f64 / power).min(1.0)
+            } else {
+                0.0
+            };
+            let high = (index + reaches[index]).min(residual.len() - 1);
+            for power in &mut residual[index.saturating_sub(reaches[index])..=high] {
+                *power *= 1.0 - share;
+            }
+        }
+    }
+    active
+}
+
+/// The power of a semitone and the `reach` semitones on each side of it. The window
+/// spreads each partial over neighbouring semitones, most of all while a note starts
+/// within the frame.
+fn spread(powers: &[f64], index: usize, reach: usize) -> f64 {
+    let high = (index + reach).min(powers.len() - 1);
+    powers[index.saturating_sub(reach)..=high].iter().sum()
+}
+
+/// The sum of the powers of the harmonics of a pitch, weighted by 1/h.
+fn salience(powers: &[f64], pitch: usize) -> f64 {
+    (1..=HARMONICS)
+        .map(|h| powers[pitch + harmonic_offset(h)] / h as f64)
+        .sum()
+}
+
+/// The number of semitones from a fundamental to its harmonic `h`.
+fn harmonic_offset(h: usize) -> usize {
+    (12.0 * (h as f64).log2()).round() as usize
+}
+
+/// The runs of true values of at least `min_length`, as (first, last) indices. Runs
+/// separated by a single false value are joined.
+fn segments(values: impl Iterator<Item = bool>, min_length: usize) -> Vec<(usize, usize)> {
+    let mut segments: Vec<(usize, usize)> = Vec::new();
+    for (t, value) in values.enumerate() {
+        if !value {
+            continue;
+        }
+        match segments.last_mut() {
+            Some((_, end)) if t <= *end + 2 => *end = t,
+            _ => segments.push((t, t)),
+        }
+    }
+    segments.retain(|(start, end)| end - start + 1 >= min_length);
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 22050;
+
+    fn note(pitch: u8, onset: f64, offset: f64, velocity: u8) -> Note {
+        Note {
+            pitch,
+            onset,
+            offset,
+            velocity,
+        }
+    }
+
+    /// Checks that every expected note was found with the right timing and loudness, and
+    /// that nothing else was.
+    fn assert_transcribed(found: &[Note], expected: &[Note]) {
+        assert_eq!(found.len(), expected.len(), "found {:?}", found);
+        for expected in expected {
+            let matched = found
+                .iter()
+                .find(|n| n.pitch == expected.pitch && (n.onset - expected.onset).abs() < 0.03)
+                .unwrap_or_else(|| panic!("{:?} not found in {:?}", expected, found));
+            assert!(
+                (matched.offset - expected.offset).abs() < 0.08,
+                "{:?}",
+                matched
+            );
+            assert!(
+                matched.velocity.abs_diff(expected.velocity) <= 8,
+                "{:?}",
+                matched
+            );
+        }
+    }
+
+    #[test]
+    fn velocity_mapping_is_invertible() {
+        for velocity in [1, 40, 100, 127] {
+            assert_eq!(
+                amplitude_to_velocity(velocity_to_amplitude(velocity)),
+                velocity
+            );
+        }
+        assert_eq!(amplitude_to_velocity(0.0), 1);
+    }
+
+    #[test]
+    fn transcribes_melody() {
+        let notes = [
+            note(60, 0.1, 0.5, 90),
+            note(64, 0.5, 0.9, 70),
+            note(67, 0.9, 1.6, 100),
+            note(72, 1.8, 2.2, 80),
+        ];
+        assert_transcribed(
+            &transcribe(&synthesize(&notes, SAMPLE_RATE), SAMPLE_RATE),
+            &notes,
+        );
+    }
+
+    #[test]
+    fn transcribes_simultaneous_and_repeated_notes() {
+        let notes = [
+            // A C major chord
+            note(60, 0.1, 0.9, 85),
+            note(64, 0.1, 0.9, 85),
+            note(67, 0.1, 0.9, 85),
+            // A repeated A3 under a held G5
+            note(57, 1.0, 1.5, 95),
+            note(57, 1.5, 2.0, 95),
+            note(79, 1.2, 2.0, 75),
+        ];
+        assert_transcribed(
+            &transcribe(&synthesize(&notes, SAMPLE_RATE), SAMPLE_RATE),
+            &notes,
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Transfer function and coherence estimation from a stimulus and the response it caused.
+//!
+//! Both signals are split into the same windowed frames, and the auto and cross spectra of
+//! the frames are summed. Averaging over many frames is what lets the estimate separate the
+//! part of the response caused by the stimulus from noise.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::stft::Stft;
+use crate::unwrap_phase;
+use crate::window::Window;
+
+/// How the transfer function is formed from the averaged spectra.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Estimator {
+    /// The cross spectrum over the stimulus auto spectrum. Unbiased by noise in the response.
+    H1,
+    /// The response auto spectrum over the cross spectrum. Unbiased by noise in the stimulus.
+    H2,
+}
+
+/// An estimated frequency response, per bin from DC up to the Nyquist frequency.
+#[derive(Debug, Clone)]
+pub struct TransferFunction {
+    /// The frequencies in Hz.
+    pub frequencies: Vec<f64>,
+    /// The gain in dB.
+    pub magnitude_db: Vec<f64>,
+    /// The unwrapped phase shift in radians.
+    pub phase: Vec<f64>,
+    /// The group delay in seconds, the negative slope of the phase.
+    pub group_delay: Vec<f64>,
+    /// The magnitude-squared coherence, from 0 (unrelated) to 1 (fully explained by the stimulus).
+    pub coherence: Vec<f64>,
+}
+
+/// Accumulates the auto and cross spectra of a stimulus and a response.
+///
+/// The two signals must be time-aligned: a delay between them shows up in the result as
+/// phase and group delay, but a delay that is a large part of a frame also lowers the
+/// coherence, since the response then contains parts of the stimulus from other frames.
+///
+/// # How to use:
+/// ```ignore
+/// let mut estimator = TransferFunctionEstimator::new(4096, 2048, Window::Hann);
+/// estimator.process(&stimulus, &recording);
+/// let response = estimator.estimate(Estimator::H1, sample_rate).unwrap();
+/// ```
+pub struct TransferFunctionEstimator {
+    stimulus: Stft,
+    response: Stft,
+    /// Auto spectrum of the stimulus.
+    gxx: Vec<f64>,
+    /// Auto spectrum of the response.
+    gyy: Vec<f64>,
+    /// Cross spectrum conj(X) * Y, real and imaginary parts.
+    gxy_real: Vec<f64>,
+    gxy_imag: Vec<f64>,
+    frame_count: usize,
+}
+
+impl TransferFunctionEstimator {
+    /// Creates a new estimator.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_size` - The length of each frame, must be a power of 2. Sets the resolution.
+    /// * `hop_size` - The number of samples between the starts of consecutive frames.
+    /// * `window` - The window applied to every frame of both signals.
+    ///
+    /// # Panics
+    ///
+    /// If the frame size is not a power of 2 or the hop size is 0 or larger than the frame size.
+    pub fn new(frame_size: usize, hop_size: usize, window: Window) -> Self {
+        let bins = frame_size / 2 + 1;
+        Self {
+            stimulus: Stft::new(frame_size, hop_size, window),
+            response: Stft::new(frame_size, hop_size, window),
+            gxx: vec![0.0; bins],
+            gyy: vec![0.0; bins],
+            gxy_real: vec![0.0; bins],
+            gxy_imag: vec![0.0; bins],
+            frame_count: 0,
+        }
+    }
+
+    /// The number of frames averaged since the last reset.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Adds a chunk of both signals to the averages.
+    ///
+    /// # Panics
+    ///
+    /// If the chunks do not have the same length.
+    pub fn process(&mut self, stimulus: &[f64], response: &[f64]) {
+        assert_eq!(
+            stimulus.len(),
+            response.len(),
+            "Stimulus and response chunks must have the same length."
+        );
+
+        let stimulus_frames = self.stimulus.process(stimulus);
+        let response_frames = self.response.process(response);
+        for (x, y) in stimulus_frames.iter().zip(response_frames.iter()) {
+            for k in 0..self.gxx.len() {
+                let (xr, xi) = (x.real[k], x.imag[k]);
+                let (yr, yi) = (y.real[k], y.imag[k]);
+                self.gxx[k] += xr * xr + xi * xi;
+                self.gyy[k] += yr * yr + yi * yi;
+                self.gxy_real[k] += xr * yr + xi * yi;
+                self.gxy_imag[k] += xr * yi - xi * yr;
+            }
+            self.frame_count += 1;
+        }
+    }
+
+    /// Calculates the transfer function from the frames averaged so far.
+    ///
+    /// Bins where the denominator of the estimator is zero, e.g. where the stimulus has no
+    /// energy, get a gain of zero (-inf dB) and zero coherence.
+    ///
+    /// # Returns
+    ///
+    /// The estimated `TransferFunction`, or `None` before the first complete frame.
+    pub fn estimate(&self, estimator: Estimator, sample_rate: u32) -> Option<TransferFunction> {
+        if self.frame_count == 0 {
+            return None;
+        }
+
+        let bins = self.gxx.len();
+        let bin_width = sample_rate as f64 / self.stimulus.frame_size() as f64;
+        let mut magnitude_db = Vec::with_capacity(bins);
+        let mut phases = Vec::with_capacity(bins);
+        let mut coherence = Vec::with_capacity(bins);
+
+        for k in 0..bins {
+            let (gxy_real, gxy_imag) = (self.gxy_real[k], self.gxy_imag[k]);
+            let cross_power = gxy_real * gxy_real + gxy_imag * gxy_imag;
+            let (h_real, h_imag) = match estimator {
+                Estimator::H1 if self.gxx[k] > 0.0 => {
+                    (gxy_real / self.gxx[k], gxy_imag / self.gxx[k])
+                }
+                // Gyy / Gyx, where Gyx is the conjugate of Gxy
+                Estimator::H2 if cross_power > 0.0 => (
+                    self.gyy[k] * gxy_real / cross_power,
+                    self.gyy[k] * gxy_imag / cross_power,
+                ),
+                _ => (0.0, 0.0),
+            };
+            let auto_power = self.gxx[k] * self.gyy[k];
+
+            magnitude_db.push(10.0 * (h_real * h_real + h_imag * h_imag).log10());
+            phases.push(h_imag.atan2(h_real));
+            coherence.push(if auto_power > 0.0 {
+                cross_power / auto_power
+            } else {
+                0.0
+            });
+        }
+
+        let phase = unwrap_phase(&phases);
+        Some(TransferFunction {
+            frequencies: (0..bins).map(|k| k as f64 * bin_width).collect(),
+            magnitude_db,
+            group_delay: group_delay(&phase, bin_width),
+            phase,
+            coherence,
+        })
+    }
+
+    /// Forgets all frames and buffered samples.
+    pub fn reset(&mut self) {
+        self.stimulus.reset();
+        self.response.reset();
+        self.gxx.fill(0.0);
+        self.gyy.fill(0.0);
+        self.gxy_real.fill(0.0);
+        self.gxy_imag.fill(0.0);
+        self.frame_count = 0;
+    }
+}
+
+/// The negative derivative of the phase with respect to angular frequency, using central
+/// differences inside and one-sided differences at the ends.
+fn group_delay(phase: &[f64], bin_width: f64) -> Vec<f64> {
+    let n = phase.len();
+    if n < 2 {
+        return vec![0.0; n];
+    }
+
+    let step = 2.0 * PI * bin_width;
+    (0..n)
+        .map(|k| {
+            let (before, after) = (k.saturating_sub(1), (k + 1).min(n - 1));
+            -(phase[after] - phase[before]) / ((after - before) as f64 * step)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::iir::Biquad;
+    use crate::filter::Filter;
+    use crate::test_signals::noise;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    #[test]
+    fn measures_gain_and_delay() {
+        let delay = 5;
+        let stimulus = noise(1, 65536);
+        let response: Vec<f64> = (0..stimulus.len())
+            .map(|i| {
+                if i >= delay {
+                    0.5 * stimulus[i - delay]
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        let mut estimator = TransferFunctionEstimator::new(1024, 512, Window::Hann);
+        estimator.process(&stimulus, &response);
+        let result = estimator.estimate(Estimator::H1, SAMPLE_RATE).unwrap();
+
+        assert_eq!(result.frequencies.len(), 513);
+        for k in 1..512 {
+            assert_float_absolute_eq!(result.magnitude_db[k], -6.02, 0.05);
+            assert_float_absolute_eq!(
+                result.phase[k],
+                -2.0 * PI * delay as f64 * k as f64 / 1024.0,
+                0.01
+            );
+            assert!(result.coherence[k] > 0.97);
+        }
+        // The slope between neighbouring bins is noisy, its mean is not
+        let mean_delay = result.group_delay[1..512].iter().sum::<f64>() / 511.0;
+        assert_float_absolute_eq!(mean_delay * SAMPLE_RATE as f64, delay as f64, 0.01);
+    }
+
+    #[test]
+    fn matches_filter_response() {
+        let mut filter = Biquad::peaking(2000.0, 1.0, 9.0, SAMPLE_RATE);
+        let stimulus = noise(2, 32768);
+        let response = filter.process(&stimulus);
+
+        let mut estimator = TransferFunctionEstimator::new(512, 256, Window::Hann);
+        // Fed in uneven chunks, like a live recording
+        for (x, y) in stimulus.chunks(1000).zip(response.chunks(1000)) {
+            estimator.process(x, y);
+        }
+        let measured = estimator.estimate(Estimator::H1, SAMPLE_RATE).unwrap();
+        let expected = filter.frequency_response(256, SAMPLE_RATE);
+
+        for k in 1..256 {
+            assert_float_absolute_eq!(measured.frequencies[k], expected.frequencies[k], 1e-9);
+            assert_float_absolute_eq!(measured.magnitude_db[k], expected.magnitude_db[k], 0.1);
+            assert_float_absolute_eq!(measured.phase[k], expected.phase[k], 0.02);
+        }
+    }
+
+    #[test]
+    fn estimators_differ_under_output_noise() {
+        // Equal power of stimulus and uncorrelated noise in the response, which H2 mistakes
+        // for a gain of 2
+        let stimulus = noise(3, 131072);
+        let response: Vec<f64> = stimulus
+            .iter()
+            .zip(noise(4, stimulus.len()))
+            .map(|(x, n)| x + n)
+            .collect();
+
+        let mut estimator = TransferFunctionEstimator::new(256, 128, Window::Hann);
+        estimator.process(&stimulus, &response);
+        let h1 = estimator.estimate(Estimator::H1, SAMPLE_RATE).unwrap();
+        let h2 = estimator.estimate(Estimator::H2, SAMPLE_RATE).unwrap();
+
+        let mean = |values: &[f64]| values[1..128].iter().sum::<f64>() / 127.0;
+        assert_float_absolute_eq!(mean(&h1.magnitude_db), 0.0, 0.2);
+        assert_float_absolute_eq!(mean(&h2.magnitude_db), 6.02, 0.2);
+        assert_float_absolute_eq!(mean(&h1.coherence), 0.5, 0.03);
+    }
+
+    #[test]
+    fn empty_until_first_frame() {
+        let mut estimator = TransferFunctionEstimator::new(64, 32, Window::Hann);
+        estimator.process(&[0.0; 63], &[0.0; 63]);
+        assert!(estimator.estimate(Estimator::H1, SAMPLE_RATE).is_none());
+
+        estimator.process(&[1.0], &[1.0]);
+        assert_eq!(estimator.frame_count(), 1);
+        estimator.reset();
+        assert_eq!(estimator.frame_count(), 0);
+    }
+}
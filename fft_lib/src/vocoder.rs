@@ -0,0 +1,512 @@
+//! Phase vocoder for changing the speed of a signal without changing its pitch, and the other
+//! way around.
+//!
+//! The signal is analyzed in STFT frames at one hop size and resynthesized by overlap-add at
+//! another. Between frames every bin's phase is advanced by its measured instantaneous
+//! frequency, so sinusoids continue smoothly at the new hop. Pitch shifting stretches the
+//! time by the pitch factor and then resamples the result back to the original duration.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::stft::OverlapAdd;
+use crate::window::{apply_window, Window};
+use crate::{fft, wrap_phase, FftResult};
+
+/// The number of zero crossings of the interpolation kernel on each side, at unity ratio.
+const INTERPOLATION_ZERO_CROSSINGS: f64 = 16.0;
+
+/// How the phases of the bins around a spectral peak follow the peak.
+///
+/// Without locking every bin gets its own phase, which loses the phase relations within a
+/// peak and makes the result sound reverberant ("phasy"). Locking the bins around each peak
+/// to its phase (Laroche and Dolson, 1999) keeps the peaks coherent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PhaseLocking {
+    /// Every bin is advanced by its own instantaneous frequency.
+    None,
+    /// The bins around a peak keep their analysis phase difference to the peak.
+    Identity,
+    /// Like `Identity`, with the phase differences scaled by `2/3 + stretch/3`, which suits
+    /// larger stretch factors better.
+    Scaled,
+}
+
+/// Streaming phase vocoder for time stretching and pitch shifting.
+///
+/// The output of a whole signal is `stretch` times as long as the input, and the output is
+/// aligned with the input (output sample `stretch * n` corresponds to input sample `n`).
+///
+/// # How to use:
+/// ```ignore
+/// // Half speed, same pitch
+/// let mut vocoder = PhaseVocoder::new(2048, 2.0, 1.0, PhaseLocking::Identity);
+/// let mut output = Vec::new();
+/// for chunk in input.chunks(1024) {
+///     output.extend(vocoder.process(chunk));
+/// }
+/// output.extend(vocoder.finish());
+/// ```
+pub struct PhaseVocoder {
+    frame_size: usize,
+    synthesis_hop: usize,
+    stretch: f64,
+    pitch: f64,
+    locking: PhaseLocking,
+    window: Vec<f64>,
+    /// Stretched samples to drop from the start of the synthesis, which aligns the output.
+    latency: usize,
+    /// Input samples not yet past, starting at padded index `buffer_start`.
+    buffer: Vec<f64>,
+    buffer_start: usize,
+    input_length: usize,
+    frame_index: usize,
+    previous_start: Option<usize>,
+    previous_phase: Vec<f64>,
+    synthesis_phase: Vec<f64>,
+    /// Instantaneous frequency of each bin, in radians per sample.
+    frequencies: Vec<f64>,
+    synthesis: OverlapAdd,
+    /// The number of stretched samples produced, including the dropped ones.
+    synthesized: usize,
+    interpolator: Option<Interpolator>,
+}
+
+impl PhaseVocoder {
+    /// Creates a new phase vocoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_size` - The STFT frame size, must be a power of 2. 2048 at 44.1 kHz is typical;
+    ///   larger frames smear transients, smaller ones blur low frequencies.
+    /// * `stretch` - The factor the duration is multiplied by, e.g. 2.0 for half speed.
+    /// * `pitch` - The factor the frequencies are multiplied by, e.g. 2.0 for an octave up.
+    /// * `locking` - How the phases around spectral peaks are locked.
+    ///
+    /// # Panics
+    ///
+    /// If the frame size is not a power of 2 of at least 4, or a factor is not positive.
+    pub fn new(frame_size: usize, stretch: f64, pitch: f64, locking: PhaseLocking) -> Self {
+        if frame_size < 4 || (frame_size & (frame_size - 1)) != 0 {
+            panic!("Frame size must be a power of 2 and at least 4.");
+        }
+        assert!(
+            stretch > 0.0 && pitch > 0.0,
+            "Stretch and pitch factors must be positive."
+        );
+
+        let synthesis_hop = frame_size / 4;
+        let time_factor = stretch * pitch;
+        // Zeros in front of the input, so the first output sample is covered by all the frames
+        // that overlap it
+        let padding = frame_size / 2 + (synthesis_hop as f64 / time_factor).ceil() as usize;
+        let latency = (frame_size as f64 / 2.0
+            + time_factor * (padding as f64 - frame_size as f64 / 2.0))
+            .round() as usize;
+        let bins = frame_size / 2 + 1;
+
+        Self {
+            frame_size,
+            synthesis_hop,
+            stretch,
+            pitch,
+            locking,
+            window: Window::Hann.generate(frame_size),
+            latency,
+            buffer: vec![0.0; padding],
+            buffer_start: 0,
+            input_length: 0,
+            frame_index: 0,
+            previous_start: None,
+            previous_phase: vec![0.0; bins],
+            synthesis_phase: vec![0.0; bins],
+            frequencies: vec![0.0; bins],
+            synthesis: OverlapAdd::new(frame_size, synthesis_hop, Window::Hann),
+            synthesized: 0,
+            interpolator: if pitch != 1.0 {
+                Some(Interpolator::new(pitch))
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn stretch(&self) -> f64 {
+        self.stretch
+    }
+
+    pub fn pitch(&self) -> f64 {
+        self.pitch
+    }
+
+    /// The number of output samples a whole signal of `input_length` samples gives.
+    pub fn output_length(&self, input_length: usize) -> usize {
+        let stretched = self.stretched_length(input_length);
+        if self.interpolator.is_some() {
+            (stretched as f64 / self.pitch).ceil() as usize
+        } else {
+            stretched
+        }
+    }
+
+    /// Feeds a chunk of the input and returns the output that is complete.
+    pub fn process(&mut self, input: &[f64]) -> Vec<f64> {
+        self.input_length += input.len();
+        self.buffer.extend_from_slice(input);
+
+        let mut stretched = Vec::new();
+        while let Some(start) = self.next_frame_start() {
+            stretched.extend(self.synthesize_frame(start));
+        }
+        self.resample(stretched)
+    }
+
+    /// Returns the rest of the output after the last chunk, and resets the vocoder.
+    pub fn finish(&mut self) -> Vec<f64> {
+        let target = self.stretched_length(self.input_length) + self.latency;
+        let mut stretched = Vec::new();
+        while self.synthesized < target {
+            // Past the end the input is silent
+            let start = self.frame_start(self.frame_index);
+            let missing = (start + self.frame_size).saturating_sub(self.buffer_end());
+            self.buffer.resize(self.buffer.len() + missing, 0.0);
+            stretched.extend(self.synthesize_frame(start));
+        }
+        let excess = self.synthesized - target;
+        stretched.truncate(stretched.len() - excess);
+
+        let mut output = self.resample(stretched);
+        if let Some(interpolator) = &mut self.interpolator {
+            output.extend(interpolator.finish());
+        }
+        self.reset();
+        output
+    }
+
+    /// Forgets all input, as if the vocoder had just been created.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.frame_size, self.stretch, self.pitch, self.locking);
+    }
+
+    fn stretched_length(&self, input_length: usize) -> usize {
+        (input_length as f64 * self.stretch * self.pitch).round() as usize
+    }
+
+    /// The padded input index where analysis frame `index` starts.
+    fn frame_start(&self, index: usize) -> usize {
+        let analysis_hop = self.synthesis_hop as f64 / (self.stretch * self.pitch);
+        (index as f64 * analysis_hop).round() as usize
+    }
+
+    fn buffer_end(&self) -> usize {
+        self.buffer_start + self.buffer.len()
+    }
+
+    fn next_frame_start(&self) -> Option<usize> {
+        let start = self.frame_start(self.frame_index);
+        (start + self.frame_size <= self.buffer_end()).then_some(start)
+    }
+
+    /// Analyzes the frame at `start`, advances the phases and returns the finished output.
+    fn synthesize_frame(&mut self, start: usize) -> Vec<f64> {
+        let offset = start - self.buffer_start;
+        let frame = &self.buffer[offset..offset + self.frame_size];
+        let spectrum = fft(&apply_window(frame, &self.window));
+
+        let bins = self.frame_size / 2 + 1;
+        let magnitudes: Vec<f64> = (0..bins)
+            .map(|k| (spectrum.real[k].powi(2) + spectrum.imag[k].powi(2)).sqrt())
+            .collect();
+        let phases: Vec<f64> = (0..bins)
+            .map(|k| spectrum.imag[k].atan2(spectrum.real[k]))
+            .collect();
+
+        match self.previous_start {
+            None => self.synthesis_phase.copy_from_slice(&phases),
+            Some(previous_start) => {
+                self.advance_phases(&magnitudes, &phases, start - previous_start)
+            }
+        }
+        self.previous_phase = phases;
+        self.previous_start = Some(start);
+        self.frame_index += 1;
+
+        // Drop the input that no later frame needs
+        let next_offset =
+            self.frame_start(self.frame_index).min(self.buffer_end()) - self.buffer_start;
+        self.buffer.drain(..next_offset);
+        self.buffer_start += next_offset;
+
+        let mut frame = FftResult {
+            real: vec![0.0; self.frame_size],
+            imag: vec![0.0; self.frame_size],
+        };
+        for (k, (magnitude, phase)) in magnitudes.iter().zip(&self.synthesis_phase).enumerate() {
+            let (sin, cos) = phase.sin_cos();
+            frame.real[k] = magnitude * cos;
+            frame.imag[k] = magnitude * sin;
+            if k > 0 && k < self.frame_size / 2 {
+                frame.real[self.frame_size - k] = frame.real[k];
+                frame.imag[self.frame_size - k] = -frame.imag[k];
+            }
+        }
+        // DC and Nyquist must be real for a real signal
+        frame.imag[0] = 0.0;
+        frame.imag[self.frame_size / 2] = 0.0;
+
+        let output = self.synthesis.process(&frame);
+        let skip = self
+            .latency
+            .saturating_sub(self.synthesized)
+            .min(output.len());
+        self.synthesized += output.len();
+        output[skip..].to_vec()
+    }
+
+    fn advance_phases(&mut self, magnitudes: &[f64], phases: &[f64], analysis_hop: usize) {
+        let bins = phases.len();
+        let synthesis_hop = self.synthesis_hop as f64;
+        if analysis_hop > 0 {
+            for (k, phase) in phases.iter().enumerate() {
+                let bin_frequency = 2.0 * PI * k as f64 / self.frame_size as f64;
+                let expected = self.previous_phase[k] + bin_frequency * analysis_hop as f64;
+                let deviation = wrap_phase(phase - expected);
+                self.frequencies[k] = bin_frequency + deviation / analysis_hop as f64;
+            }
+        }
+
+        let peaks = match self.locking {
+            PhaseLocking::None => Vec::new(),
+            PhaseLocking::Identity | PhaseLocking::Scaled => find_peaks(magnitudes),
+        };
+        if peaks.is_empty() {
+            for k in 0..bins {
+                self.synthesis_phase[k] += synthesis_hop * self.frequencies[k];
+            }
+            return;
+        }
+
+        let scale = match self.locking {
+            PhaseLocking::Scaled => 2.0 / 3.0 + self.stretch * self.pitch / 3.0,
+            _ => 1.0,
+        };
+        for (i, &peak) in peaks.iter().enumerate() {
+            // Each peak rules the bins up to halfway to its neighbours
+            let low = if i == 0 {
+                0
+            } else {
+                (peaks[i - 1] + peak) / 2 + 1
+            };
+            let high = if i + 1 == peaks.len() {
+                bins - 1
+            } else {
+                (peak + peaks[i + 1]) / 2
+            };
+
+            let peak_phase = self.synthesis_phase[peak] + synthesis_hop * self.frequencies[peak];
+            for k in low..=high {
+                self.synthesis_phase[k] = peak_phase + scale * (phases[k] - phases[peak]);
+            }
+        }
+    }
+
+    fn resample(&mut self, stretched: Vec<f64>) -> Vec<f64> {
+        match &mut self.interpolator {
+            Some(interpolator) => interpolator.process(&stretched),
+            None => stretched,
+        }
+    }
+}
+
+/// Local maxima of the magnitude that are larger than their two neighbours on each side.
+fn find_peaks(magnitudes: &[f64]) -> Vec<usize> {
+    (0..magnitudes.len())
+        .filter(|&k| {
+            let low = k.saturating_sub(2);
+            let high = (k + 2).min(magnitudes.len() - 1);
+            magnitudes[k] > 0.0 && (low..=high).all(|j| j == k || magnitudes[j] < magnitudes[k])
+        })
+        .collect()
+}
+
+/// Streaming windowed-sinc interpolation at a fixed step through the input.
+///
+/// Steps above 1 lower the cutoff so the shortened signal does not alias.
+struct Interpolator {
+    step: f64,
+    cutoff: f64,
+    half_width: usize,
+    /// Input samples, starting at input index `buffer_start` (negative for the zeros in front).
+    buffer: Vec<f64>,
+    buffer_start: i64,
+    received: usize,
+    /// The input position of the next output sample.
+    position: f64,
+}
+
+impl Interpolator {
+    fn new(step: f64) -> Self {
+        let cutoff = (1.0 / step).min(1.0);
+        let half_width = (INTERPOLATION_ZERO_CROSSINGS / cutoff).ceil() as usize;
+        Self {
+            step,
+            cutoff,
+            half_width,
+            buffer: vec![0.0; half_width],
+            buffer_start: -(half_width as i64),
+            received: 0,
+            position: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: &[f64]) -> Vec<f64> {
+        self.received += input.len();
+        self.buffer.extend_from_slice(input);
+
+        let mut output = Vec::new();
+        let end = self.buffer_start + self.buffer.len() as i64;
+        while self.position.floor() as i64 + (self.half_width as i64) < end {
+            output.push(self.sample());
+            self.position += self.step;
+        }
+
+        let keep_from = self.position.floor() as i64 + 1 - self.half_width as i64;
+        let drop = (keep_from - self.buffer_start).clamp(0, self.buffer.len() as i64);
+        self.buffer.drain(..drop as usize);
+        self.buffer_start += drop;
+        output
+    }
+
+    fn finish(&mut self) -> Vec<f64> {
+        self.buffer.resize(self.buffer.len() + self.half_width, 0.0);
+        let mut output = Vec::new();
+        while self.position < self.received as f64 {
+            output.push(self.sample());
+            self.position += self.step;
+        }
+        output
+    }
+
+    fn sample(&self) -> f64 {
+        let base = self.position.floor() as i64;
+        let first = base + 1 - self.half_width as i64;
+        (first..=base + self.half_width as i64)
+            .map(|index| {
+                let sample = self.buffer[(index - self.buffer_start) as usize];
+                sample * self.kernel(self.position - index as f64)
+            })
+            .sum()
+    }
+
+    /// Hann-windowed sinc lowpass with the cutoff relative to the input Nyquist frequency.
+    fn kernel(&self, distance: f64) -> f64 {
+        let ratio = distance / self.half_width as f64;
+        if ratio.abs() >= 1.0 {
+            return 0.0;
+        }
+        let x = PI * self.cutoff * distance;
+        let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+        self.cutoff * sinc * (0.5 + 0.5 * (PI * ratio).cos())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_signals::sine;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    const SAMPLE_RATE: f64 = 16000.0;
+
+    /// The frequency from the number of upward zero crossings.
+    fn measure_frequency(samples: &[f64]) -> f64 {
+        let crossings: Vec<usize> = (1..samples.len())
+            .filter(|&i| samples[i - 1] < 0.0 && samples[i] >= 0.0)
+            .collect();
+        let periods = (crossings.len() - 1) as f64;
+        periods * SAMPLE_RATE / (crossings[crossings.len() - 1] - crossings[0]) as f64
+    }
+
+    fn rms(samples: &[f64]) -> f64 {
+        (samples.iter().map(|s| s * s).sum::<f64>() / samples.len() as f64).sqrt()
+    }
+
+    fn run(vocoder: &mut PhaseVocoder, input: &[f64], chunk_size: usize) -> Vec<f64> {
+        let mut output: Vec<f64> = input
+            .chunks(chunk_size)
+            .flat_map(|chunk| vocoder.process(chunk))
+            .collect();
+        output.extend(vocoder.finish());
+        output
+    }
+
+    #[test]
+    fn unity_factors_restore_input() {
+        let input: Vec<f64> = (0..5000)
+            .map(|i| (i as f64 * 0.05).sin() + 0.3 * (i as f64 * 0.31).cos())
+            .collect();
+        let mut vocoder = PhaseVocoder::new(512, 1.0, 1.0, PhaseLocking::Identity);
+        let output = run(&mut vocoder, &input, 700);
+
+        assert_eq!(output.len(), input.len());
+        for (output, input) in output.iter().zip(input.iter()) {
+            assert_float_absolute_eq!(*output, *input, 1e-9);
+        }
+    }
+
+    #[test]
+    fn time_stretch_keeps_pitch() {
+        let input = sine(440.0, 16000, 16000);
+        for locking in [
+            PhaseLocking::None,
+            PhaseLocking::Identity,
+            PhaseLocking::Scaled,
+        ] {
+            for stretch in [0.7, 1.5, 2.3] {
+                let mut vocoder = PhaseVocoder::new(1024, stretch, 1.0, locking);
+                let output = run(&mut vocoder, &input, 1000);
+
+                assert_eq!(output.len(), (16000.0 * stretch).round() as usize);
+                assert_eq!(output.len(), vocoder.output_length(16000));
+                let middle = &output[2048..output.len() - 2048];
+                assert_float_absolute_eq!(measure_frequency(middle), 440.0, 1.0);
+                // Without locking the bins keep the phase relations of the first frame, where
+                // the tone starts, which costs some level for good
+                if locking == PhaseLocking::Identity {
+                    assert_float_absolute_eq!(rms(middle), rms(&input), 0.05 * rms(&input));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pitch_shift_keeps_duration() {
+        let input = sine(440.0, 16000, 16000);
+        for pitch in [0.75, 1.5] {
+            let mut vocoder = PhaseVocoder::new(1024, 1.0, pitch, PhaseLocking::Identity);
+            let output = run(&mut vocoder, &input, 1000);
+
+            assert!((output.len() as i64 - 16000).abs() <= 1);
+            assert_eq!(output.len(), vocoder.output_length(16000));
+            let middle = &output[2048..output.len() - 2048];
+            assert_float_absolute_eq!(measure_frequency(middle), 440.0 * pitch, 1.5);
+            assert_float_absolute_eq!(rms(middle), rms(&input), 0.05 * rms(&input));
+        }
+    }
+
+    #[test]
+    fn chunk_size_does_not_change_output() {
+        let input = sine(300.0, 16000, 6000);
+        let mut whole = PhaseVocoder::new(512, 1.3, 1.2, PhaseLocking::Scaled);
+        let mut chunked = PhaseVocoder::new(512, 1.3, 1.2, PhaseLocking::Scaled);
+        let expected = run(&mut whole, &input, input.len());
+        let output = run(&mut chunked, &input, 37);
+
+        assert_eq!(output.len(), expected.len());
+        for (output, expected) in output.iter().zip(expected.iter()) {
+            assert_float_absolute_eq!(*output, *expected, 1e-12);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Continuous wavelet transform, computed in the frequency domain with `fft`.
+//!
+//! The normalization follows Torrence and Compo, "A Practical Guide to Wavelet Analysis"
+//! (1998): every scale has unit energy, so the scalogram of white noise is flat.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+use crate::{fft, ifft, FftResult};
+
+/// Gamma(2.5), which normalizes the Mexican hat wavelet.
+const GAMMA_5_2: f64 = 1.329_340_388_179_137;
+
+/// The mother wavelets available for the continuous transform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wavelet {
+    /// A complex sinusoid in a Gaussian envelope. `omega0` is the number of radians per
+    /// envelope width, 6.0 is the usual choice.
+    Morlet { omega0: f64 },
+    /// The negative second derivative of a Gaussian. Real valued and short, so it is good at
+    /// locating transients.
+    MexicanHat,
+}
+
+impl Wavelet {
+    /// The ratio of the Fourier period to the scale, used to convert between them.
+    pub fn fourier_factor(&self) -> f64 {
+        match self {
+            Wavelet::Morlet { omega0 } => 4.0 * PI / (omega0 + (2.0 + omega0 * omega0).sqrt()),
+            Wavelet::MexicanHat => 2.0 * PI / 2.5f64.sqrt(),
+        }
+    }
+
+    /// The scale in seconds whose wavelet responds most to `frequency`.
+    pub fn scale_for_frequency(&self, frequency: f64) -> f64 {
+        1.0 / (frequency * self.fourier_factor())
+    }
+
+    /// The Fourier transform of the wavelet at the scaled angular frequency `scale * omega`.
+    fn transform(&self, scaled_omega: f64) -> f64 {
+        match self {
+            Wavelet::Morlet { omega0 } => {
+                if scaled_omega > 0.0 {
+                    PI.powf(-0.25) * (-(scaled_omega - omega0).powi(2) / 2.0).exp()
+                } else {
+                    0.0
+                }
+            }
+            Wavelet::MexicanHat => {
+                scaled_omega * scaled_omega * (-scaled_omega * scaled_omega / 2.0).exp()
+                    / GAMMA_5_2.sqrt()
+            }
+        }
+    }
+}
+
+/// The wavelet coefficients of a signal at a set of frequencies.
+#[derive(Debug, Clone)]
+pub struct Scalogram {
+    /// The frequencies in Hz that were analyzed, one per row.
+    pub frequencies: Vec<f64>,
+    /// The wavelet scale in seconds of each row.
+    pub scales: Vec<f64>,
+    /// The complex coefficients of each row, one per input sample. The imaginary parts are
+    /// zero for real wavelets.
+    pub coefficients: Vec<FftResult>,
+    pub sample_rate: u32,
+}
+
+impl Scalogram {
+    /// The wavelet power (squared magnitude) of every coefficient, one row per frequency.
+    pub fn power(&self) -> Vec<Vec<f64>> {
+        self.coefficients
+            .iter()
+            .map(|row| {
+                row.real
+                    .iter()
+                    .zip(row.imag.iter())
+                    .map(|(re, im)| re * re + im * im)
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Calculates the continuous wavelet transform of a signal.
+///
+/// The signal is zero padded to at least twice its length before transforming, so the end
+/// does not wrap around into the start. Coefficients within about one scale of either end
+/// are still affected by the padding.
+///
+/// # Arguments
+///
+/// * `samples` - The signal, of any length.
+/// * `sample_rate` - The sample rate of the signal.
+/// * `wavelet` - The mother wavelet.
+/// * `frequencies` - The frequencies in Hz to analyze, e.g. logarithmically spaced.
+///
+/// # Returns
+///
+/// A `Scalogram` with one row per frequency and one column per sample.
+pub fn cwt(samples: &[f64], sample_rate: u32, wavelet: Wavelet, frequencies: &[f64]) -> Scalogram {
+    let length = samples.len();
+    let padded_length = (2 * length).max(1).next_power_of_two();
+    let mut padded = samples.to_vec();
+    padded.resize(padded_length, 0.0);
+    let spectrum = fft(&padded);
+
+    let dt = 1.0 / sample_rate as f64;
+    let omegas: Vec<f64> = (0..padded_length)
+        .map(|k| {
+            let k = if k <= padded_length / 2 {
+                k as f64
+            } else {
+                k as f64 - padded_length as f64
+            };
+            2.0 * PI * k / (padded_length as f64 * dt)
+        })
+        .collect();
+
+    let scales: Vec<f64> = frequencies
+        .iter()
+        .map(|&f| wavelet.scale_for_frequency(f))
+        .collect();
+    let coefficients = scales
+        .iter()
+        .map(|&scale| {
+            // The wavelet transforms are real, so conjugating them changes nothing
+            let normalization = (2.0 * PI * scale / dt).sqrt();
+            let mut product = FftResult {
+                real: vec![0.0; padded_length],
+                imag: vec![0.0; padded_length],
+            };
+            for (k, omega) in omegas.iter().enumerate() {
+                let daughter = normalization * wavelet.transform(scale * omega);
+                product.real[k] = spectrum.real[k] * daughter;
+                product.imag[k] = spectrum.imag[k] * daughter;
+            }
+
+            let mut row = ifft(&product);
+            row.real.truncate(length);
+            row.imag.truncate(length);
+            row
+        })
+        .collect();
+
+    Scalogram {
+        frequencies: frequencies.to_vec(),
+        scales,
+        coefficients,
+        sample_rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    const SAMPLE_RATE: u32 = 1000;
+
+    fn log_frequencies(min: f64, max: f64, count: usize) -> Vec<f64> {
+        (0..count)
+            .map(|i| min * (max / min).powf(i as f64 / (count - 1) as f64))
+            .collect()
+    }
+
+    #[test]
+    fn morlet_ridge_follows_tone() {
+        // 40 Hz for the first half second, 120 Hz for the second
+        let samples: Vec<f64> = (0..1000)
+            .map(|i| {
+                let t = i as f64 / SAMPLE_RATE as f64;
+                let frequency = if i < 500 { 40.0 } else { 120.0 };
+                (2.0 * PI * frequency * t).sin()
+            })
+            .collect();
+        let frequencies = log_frequencies(10.0, 400.0, 65);
+        let scalogram = cwt(
+            &samples,
+            SAMPLE_RATE,
+            Wavelet::Morlet { omega0: 6.0 },
+            &frequencies,
+        );
+        let power = scalogram.power();
+
+        let ridge = |time: usize| {
+            let row = (0..frequencies.len())
+                .max_by(|&a, &b| power[a][time].total_cmp(&power[b][time]))
+                .unwrap();
+            frequencies[row]
+        };
+        assert!((ridge(250) / 40.0).log2().abs() < 0.1);
+        assert!((ridge(750) / 120.0).log2().abs() < 0.1);
+    }
+
+    #[test]
+    fn morlet_magnitude_is_steady_for_a_tone() {
+        let frequency = 50.0;
+        let samples: Vec<f64> = (0..2000)
+            .map(|i| (2.0 * PI * frequency * i as f64 / SAMPLE_RATE as f64).cos())
+            .collect();
+        let scalogram = cwt(
+            &samples,
+            SAMPLE_RATE,
+            Wavelet::Morlet { omega0: 6.0 },
+            &[frequency],
+        );
+
+        // Away from the edges, the analytic wavelet gives an envelope without ripple
+        let row = &scalogram.power()[0];
+        let reference = row[1000];
+        for power in &row[500..1500] {
+            assert_float_absolute_eq!(*power / reference, 1.0, 1e-3);
+        }
+    }
+
+    #[test]
+    fn mexican_hat_locates_impulse() {
+        let mut samples = vec![0.0; 512];
+        samples[300] = 1.0;
+        let frequencies = [20.0, 100.0];
+        let scalogram = cwt(&samples, SAMPLE_RATE, Wavelet::MexicanHat, &frequencies);
+
+        for row in &scalogram.coefficients {
+            let peak = (0..512)
+                .max_by(|&a, &b| row.real[a].abs().total_cmp(&row.real[b].abs()))
+                .unwrap();
+            assert_eq!(peak, 300);
+            assert!(row.imag.iter().all(|i| i.abs() < 1e-12));
+        }
+
+        // The shorter wavelet at the higher frequency is more concentrated
+        let width = |row: &FftResult| {
+            row.real
+                .iter()
+                .filter(|r| r.abs() > 0.1 * row.real[300].abs())
+                .count()
+        };
+        assert!(width(&scalogram.coefficients[1]) < width(&scalogram.coefficients[0]));
+    }
+}
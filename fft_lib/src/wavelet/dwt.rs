@@ -0,0 +1,268 @@
+//! Discrete wavelet transform with orthogonal Haar and Daubechies filters.
+//!
+//! The signal is extended periodically, so every level halves the length exactly and the
+//! inverse restores the signal without boundary errors.
+
+use alloc::{vec, vec::Vec};
+
+/// The orthogonal wavelets available for the discrete transform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiscreteWavelet {
+    /// The Haar wavelet, identical to Daubechies 1.
+    Haar,
+    /// Daubechies with 2 vanishing moments (4 taps).
+    Daubechies2,
+    /// Daubechies with 3 vanishing moments (6 taps).
+    Daubechies3,
+    /// Daubechies with 4 vanishing moments (8 taps).
+    Daubechies4,
+}
+
+impl DiscreteWavelet {
+    /// The lowpass (scaling) filter. Its taps sum to the square root of 2.
+    pub fn lowpass(&self) -> &'static [f64] {
+        match self {
+            DiscreteWavelet::Haar => &[
+                core::f64::consts::FRAC_1_SQRT_2,
+                core::f64::consts::FRAC_1_SQRT_2,
+            ],
+            DiscreteWavelet::Daubechies2 => &[
+                0.482_962_913_144_534_16,
+                0.836_516_303_737_807_9,
+                0.224_143_868_042_013_4,
+                -0.129_409_522_551_260_37,
+            ],
+            DiscreteWavelet::Daubechies3 => &[
+                0.332_670_552_950_082_6,
+                0.806_891_509_311_092_5,
+                0.459_877_502_118_491_54,
+                -0.135_011_020_010_254_58,
+                -0.085_441_273_882_026_66,
+                0.035_226_291_885_709_53,
+            ],
+            DiscreteWavelet::Daubechies4 => &[
+                0.230_377_813_308_896_53,
+                0.714_846_570_552_915_6,
+                0.630_880_767_929_858_9,
+                -0.027_983_769_416_859_854,
+                -0.187_034_811_719_093_09,
+                0.030_841_381_835_560_764,
+                0.032_883_011_666_885_2,
+                -0.010_597_401_785_069_032,
+            ],
+        }
+    }
+
+    /// The highpass (wavelet) filter, the alternating flip of the lowpass filter.
+    pub fn highpass(&self) -> Vec<f64> {
+        let lowpass = self.lowpass();
+        let length = lowpass.len();
+        (0..length)
+            .map(|n| {
+                let tap = lowpass[length - 1 - n];
+                if n % 2 == 0 {
+                    tap
+                } else {
+                    -tap
+                }
+            })
+            .collect()
+    }
+}
+
+/// A signal split into one approximation and a detail band per level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaveletDecomposition {
+    pub wavelet: DiscreteWavelet,
+    /// The coarsest approximation, covering the lowest frequencies.
+    pub approximation: Vec<f64>,
+    /// The detail coefficients of each level, the finest (highest frequencies) first.
+    pub details: Vec<Vec<f64>>,
+}
+
+impl WaveletDecomposition {
+    /// Runs the inverse transform over all levels to restore the signal.
+    pub fn reconstruct(&self) -> Vec<f64> {
+        self.details
+            .iter()
+            .rev()
+            .fold(self.approximation.clone(), |approximation, detail| {
+                idwt(&approximation, detail, self.wavelet)
+            })
+    }
+}
+
+/// Runs one level of the discrete wavelet transform.
+///
+/// # Arguments
+///
+/// * `samples` - The signal, its length must be even.
+/// * `wavelet` - The wavelet to transform with.
+///
+/// # Returns
+///
+/// The approximation and the detail coefficients, each half as long as the signal.
+///
+/// # Panics
+///
+/// If the length of the signal is odd.
+pub fn dwt(samples: &[f64], wavelet: DiscreteWavelet) -> (Vec<f64>, Vec<f64>) {
+    let length = samples.len();
+    assert!(length.is_multiple_of(2), "The signal length must be even.");
+
+    let lowpass = wavelet.lowpass();
+    let highpass = wavelet.highpass();
+    let mut approximation = vec![0.0; length / 2];
+    let mut detail = vec![0.0; length / 2];
+    for k in 0..length / 2 {
+        for (n, (low, high)) in lowpass.iter().zip(highpass.iter()).enumerate() {
+            let sample = samples[(2 * k + n) % length];
+            approximation[k] += low * sample;
+            detail[k] += high * sample;
+        }
+    }
+    (approximation, detail)
+}
+
+/// Runs one level of the inverse discrete wavelet transform.
+///
+/// # Returns
+///
+/// The signal, twice as long as the coefficients.
+///
+/// # Panics
+///
+/// If the approximation and the detail do not have the same length.
+pub fn idwt(approximation: &[f64], detail: &[f64], wavelet: DiscreteWavelet) -> Vec<f64> {
+    assert_eq!(
+        approximation.len(),
+        detail.len(),
+        "Approximation and detail must have the same length."
+    );
+
+    let length = 2 * approximation.len();
+    let lowpass = wavelet.lowpass();
+    let highpass = wavelet.highpass();
+    let mut samples = vec![0.0; length];
+    for k in 0..approximation.len() {
+        for (n, (low, high)) in lowpass.iter().zip(highpass.iter()).enumerate() {
+            samples[(2 * k + n) % length] += low * approximation[k] + high * detail[k];
+        }
+    }
+    samples
+}
+
+/// Decomposes a signal over several levels, transforming the approximation again each time.
+///
+/// # Arguments
+///
+/// * `samples` - The signal, its length must be divisible by `2^levels`.
+/// * `wavelet` - The wavelet to transform with.
+/// * `levels` - The number of levels.
+///
+/// # Panics
+///
+/// If the length of the signal is not divisible by `2^levels`.
+pub fn decompose(samples: &[f64], wavelet: DiscreteWavelet, levels: usize) -> WaveletDecomposition {
+    assert!(
+        samples.len().is_multiple_of(1 << levels),
+        "The signal length must be divisible by 2^levels."
+    );
+
+    let mut approximation = samples.to_vec();
+    let mut details = Vec::with_capacity(levels);
+    for _ in 0..levels {
+        let (next, detail) = dwt(&approximation, wavelet);
+        details.push(detail);
+        approximation = next;
+    }
+    WaveletDecomposition {
+        wavelet,
+        approximation,
+        details,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    const WAVELETS: [DiscreteWavelet; 4] = [
+        DiscreteWavelet::Haar,
+        DiscreteWavelet::Daubechies2,
+        DiscreteWavelet::Daubechies3,
+        DiscreteWavelet::Daubechies4,
+    ];
+
+    #[test]
+    fn haar_averages_and_differences() {
+        let (approximation, detail) = dwt(&[1.0, 3.0, 5.0, 5.0], DiscreteWavelet::Haar);
+        let scale = core::f64::consts::FRAC_1_SQRT_2;
+        assert_float_absolute_eq!(approximation[0], 4.0 * scale, 1e-12);
+        assert_float_absolute_eq!(approximation[1], 10.0 * scale, 1e-12);
+        assert_float_absolute_eq!(detail[0], -2.0 * scale, 1e-12);
+        assert_float_absolute_eq!(detail[1], 0.0, 1e-12);
+    }
+
+    #[test]
+    fn multi_level_reconstruction_is_perfect() {
+        let samples: Vec<f64> = (0..256)
+            .map(|i| ((i * 37) % 101) as f64 / 50.0 - 1.0 + (i as f64 * 0.05).sin())
+            .collect();
+        let energy: f64 = samples.iter().map(|s| s * s).sum();
+
+        for wavelet in WAVELETS {
+            let decomposition = decompose(&samples, wavelet, 5);
+            assert_eq!(decomposition.approximation.len(), 8);
+            assert_eq!(decomposition.details[0].len(), 128);
+            assert_eq!(decomposition.details[4].len(), 8);
+
+            // Orthogonal wavelets keep the energy
+            let coefficient_energy: f64 = decomposition
+                .details
+                .iter()
+                .flatten()
+                .chain(decomposition.approximation.iter())
+                .map(|c| c * c)
+                .sum();
+            assert_float_absolute_eq!(coefficient_energy, energy, 1e-9);
+
+            let restored = decomposition.reconstruct();
+            for (restored, original) in restored.iter().zip(samples.iter()) {
+                assert_float_absolute_eq!(*restored, *original, 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn daubechies_details_vanish_on_polynomials() {
+        // Daubechies N has N vanishing moments, so polynomials of degree N - 1 give no detail
+        // away from where the periodic extension wraps around
+        let cases = [
+            (DiscreteWavelet::Daubechies2, 1),
+            (DiscreteWavelet::Daubechies3, 2),
+            (DiscreteWavelet::Daubechies4, 3),
+        ];
+        for (wavelet, degree) in cases {
+            let samples: Vec<f64> = (0..64).map(|i| (i as f64 / 8.0).powi(degree)).collect();
+            let (_, detail) = dwt(&samples, wavelet);
+            let taps = wavelet.lowpass().len();
+            for value in &detail[..32 - taps / 2] {
+                assert_float_absolute_eq!(*value, 0.0, 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn filters_are_orthonormal() {
+        for wavelet in WAVELETS {
+            let lowpass = wavelet.lowpass();
+            let sum: f64 = lowpass.iter().sum();
+            let energy: f64 = lowpass.iter().map(|h| h * h).sum();
+            assert_float_absolute_eq!(sum, 2f64.sqrt(), 1e-12);
+            assert_float_absolute_eq!(energy, 1.0, 1e-12);
+            assert_float_absolute_eq!(wavelet.highpass().iter().sum::<f64>(), 0.0, 1e-12);
+        }
+    }
+}
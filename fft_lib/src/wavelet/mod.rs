@@ -0,0 +1,8 @@
+//! Wavelet transforms: a continuous transform for scalograms and a discrete transform for
+//! multi-level decomposition.
+//!
+//! Unlike the STFT, wavelets get shorter as the frequency rises, so transients stay sharp
+//! in time at high frequencies while low frequencies keep a fine frequency resolution.
+
+pub mod cwt;
+pub mod dwt;
@@ -0,0 +1,131 @@
+//! Window functions applied to frames before transforming them.
+
+use alloc::{vec, vec::Vec};
+use core::f64::consts::PI;
+
+#[cfg(not(feature = "std"))]
+use crate::math::Float;
+
+/// The window functions available for framing a signal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Window {
+    /// No tapering at all.
+    Rectangular,
+    /// Raised cosine, the default choice for STFT analysis.
+    Hann,
+    /// Raised cosine that does not reach zero at the edges.
+    Hamming,
+    /// Three-term cosine window with lower side lobes than Hann.
+    Blackman,
+    /// Four-term cosine window with side lobes below -92 dB, for measurements that need a
+    /// large dynamic range.
+    BlackmanHarris,
+}
+
+impl Window {
+    /// Generates the window coefficients.
+    ///
+    /// The windows are periodic (the first sample of the next frame would be the missing
+    /// endpoint), which is what overlapping STFT frames need to add up to a constant.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The number of coefficients to generate.
+    ///
+    /// # Returns
+    ///
+    /// A vector of `size` window coefficients.
+    pub fn generate(&self, size: usize) -> Vec<f64> {
+        (0..size)
+            .map(|i| self.coefficient(i as f64 / size as f64))
+            .collect()
+    }
+
+    /// Generates symmetric window coefficients, where the last coefficient equals the first.
+    ///
+    /// Symmetric windows are what FIR filter design needs for a linear phase response.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The number of coefficients to generate.
+    ///
+    /// # Returns
+    ///
+    /// A vector of `size` window coefficients.
+    pub fn generate_symmetric(&self, size: usize) -> Vec<f64> {
+        if size == 1 {
+            return vec![1.0];
+        }
+        (0..size)
+            .map(|i| self.coefficient(i as f64 / (size - 1) as f64))
+            .collect()
+    }
+
+    /// The window value at `position`, running from 0.0 to 1.0 over one period.
+    fn coefficient(&self, position: f64) -> f64 {
+        let x = 2.0 * PI * position;
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5 * x.cos(),
+            Window::Hamming => 0.54 - 0.46 * x.cos(),
+            Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+            Window::BlackmanHarris => {
+                0.35875 - 0.48829 * x.cos() + 0.14128 * (2.0 * x).cos() - 0.01168 * (3.0 * x).cos()
+            }
+        }
+    }
+}
+
+/// Multiplies the samples with the window coefficients.
+///
+/// # Panics
+///
+/// If `samples` and `window` do not have the same length.
+pub fn apply_window(samples: &[f64], window: &[f64]) -> Vec<f64> {
+    assert_eq!(
+        samples.len(),
+        window.len(),
+        "Samples and window must have the same length."
+    );
+    samples
+        .iter()
+        .zip(window.iter())
+        .map(|(s, w)| s * w)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::assert_float_absolute_eq;
+
+    #[test]
+    fn hann_is_periodic() {
+        let w = Window::Hann.generate(8);
+        assert_float_absolute_eq!(w[0], 0.0, 1e-12);
+        assert_float_absolute_eq!(w[4], 1.0, 1e-12);
+        assert_float_absolute_eq!(w[2], w[6], 1e-12);
+    }
+
+    #[test]
+    fn hann_overlap_adds_to_constant() {
+        let w = Window::Hann.generate(16);
+        for i in 0..8 {
+            assert_float_absolute_eq!(w[i] + w[i + 8], 1.0, 1e-12);
+        }
+    }
+
+    #[test]
+    fn symmetric_window_mirrors() {
+        let w = Window::Blackman.generate_symmetric(9);
+        assert_float_absolute_eq!(w[0], w[8], 1e-12);
+        assert_float_absolute_eq!(w[4], 1.0, 1e-12);
+    }
+
+    #[test]
+    fn rectangular_leaves_samples_unchanged() {
+        let samples = [1.0, -2.0, 3.0];
+        let windowed = apply_window(&samples, &Window::Rectangular.generate(3));
+        assert_eq!(windowed, samples);
+    }
+}
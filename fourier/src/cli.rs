@@ -0,0 +1,150 @@
+use audio_lib::{
+    list_hosts, list_input_devices, InputDevice, MicrophoneConfig, MicrophoneSampleFormat,
+};
+
+pub const USAGE: &str = "Usage: fourier [OPTIONS]
+
+Options:
+    --list-devices            List the audio hosts and input devices, then exit
+    --device <NAME|INDEX>     Record from this input device instead of the default one
+    --sample-format <FORMAT>  Request this sample format from the device, e.g. i16 or f32
+    --sample-rate <HZ>        Request this sample rate from the device
+    --buffer-size <FRAMES>    Request this buffer size from the device
+    --help                    Show this message";
+
+/// The command-line options of the visualizer.
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    pub help: bool,
+    pub list_devices: bool,
+    pub device: InputDevice,
+    pub config: MicrophoneConfig,
+}
+
+impl Options {
+    /// Parses the arguments after the program name.
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {}", name))
+            };
+            match arg.as_str() {
+                "--help" | "-h" => options.help = true,
+                "--list-devices" => options.list_devices = true,
+                "--device" => options.device = InputDevice::parse(&value("--device")?),
+                "--sample-format" => {
+                    options.config.sample_format =
+                        Some(parse_sample_format(&value("--sample-format")?)?)
+                }
+                "--sample-rate" => {
+                    options.config.sample_rate = Some(parse_number(&value("--sample-rate")?)?)
+                }
+                "--buffer-size" => {
+                    options.config.buffer_size = Some(parse_number(&value("--buffer-size")?)?)
+                }
+                _ => return Err(format!("Unknown argument: {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The sample formats cpal can record in.
+const SAMPLE_FORMATS: [MicrophoneSampleFormat; 10] = [
+    MicrophoneSampleFormat::I8,
+    MicrophoneSampleFormat::I16,
+    MicrophoneSampleFormat::I32,
+    MicrophoneSampleFormat::I64,
+    MicrophoneSampleFormat::U8,
+    MicrophoneSampleFormat::U16,
+    MicrophoneSampleFormat::U32,
+    MicrophoneSampleFormat::U64,
+    MicrophoneSampleFormat::F32,
+    MicrophoneSampleFormat::F64,
+];
+
+fn parse_number(value: &str) -> Result<u32, String> {
+    match value.parse() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!("Expected a positive number, got {}", value)),
+    }
+}
+
+/// Parses a sample format by the name cpal displays it with, e.g. `i16` or `f32`.
+fn parse_sample_format(value: &str) -> Result<MicrophoneSampleFormat, String> {
+    SAMPLE_FORMATS
+        .into_iter()
+        .find(|format| format.to_string() == value)
+        .ok_or_else(|| {
+            let names: Vec<String> = SAMPLE_FORMATS
+                .iter()
+                .map(|format| format.to_string())
+                .collect();
+            format!(
+                "Unknown sample format {}, expected one of {}",
+                value,
+                names.join(", ")
+            )
+        })
+}
+
+/// Prints the audio hosts and every input device with the configs it supports.
+pub fn print_devices() {
+    println!("Hosts: {}", list_hosts().join(", "));
+    println!("Input devices:");
+    for device in list_input_devices() {
+        println!("{}", device);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_use_the_default_device() {
+        assert_eq!(parse(&[]), Ok(Options::default()));
+    }
+
+    #[test]
+    fn device_and_config_are_parsed() {
+        let options = parse(&[
+            "--device",
+            "1",
+            "--sample-rate",
+            "48000",
+            "--buffer-size",
+            "256",
+            "--sample-format",
+            "f32",
+        ])
+        .unwrap();
+        assert_eq!(options.device, InputDevice::Index(1));
+        assert_eq!(
+            options.config.sample_format,
+            Some(MicrophoneSampleFormat::F32)
+        );
+        assert_eq!(options.config.sample_rate, Some(48000));
+        assert_eq!(options.config.buffer_size, Some(256));
+
+        let options = parse(&["--list-devices", "--device", "USB Audio"]).unwrap();
+        assert!(options.list_devices);
+        assert_eq!(options.device, InputDevice::Name("USB Audio".to_string()));
+    }
+
+    #[test]
+    fn bad_arguments_are_reported() {
+        assert!(parse(&["--device"]).is_err());
+        assert!(parse(&["--sample-rate", "fast"]).is_err());
+        assert!(parse(&["--sample-rate", "0"]).is_err());
+        assert!(parse(&["--buffer-size", "0"]).is_err());
+        assert!(parse(&["--sample-format", "i24"]).is_err());
+        assert!(parse(&["--loud"]).is_err());
+    }
+}
@@ -1,9 +1,12 @@
 #![allow(unused)]
 
+mod cli;
 mod plot;
 mod utils;
 
 use audio_lib::{AudioSource, AudioStreamer, MicrophoneSource, WavFileSource};
+use cli::Options;
+use fft_lib::averaging::{AveragingMode, SpectrumAverager};
 use fft_lib::{fft, get_frequencies};
 use plot::bar_visualizer::{BarVisualizer, Rotation};
 
@@ -23,13 +26,30 @@ pub const MAX_LOUDNESS: f64 = 0.05; // TODO: Adjust this for microphone sensitiv
 const CHUNK_SIZE: usize = 256; // Chunk size for audio processing
                                // const FILE_PATH: &'static str = "./audio/pigstep.wav"; // Path to the audio file
 const NUM_BARS: usize = 32; // Fixed number of bars for visualization
+const SMOOTHING_TIME: f64 = 0.1; // Time constant in seconds for averaging the spectrum
 
 fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    if options.list_devices {
+        cli::print_devices();
+        return;
+    }
+
     // Initialize the visualization components
     let (mut window, mut visualizers) = initialize_visualization(NUM_BARS);
 
     // Set up audio processing
-    let (audio_rx, audio_thread_handle, sample_rate) = setup_audio_streaming();
+    let (audio_rx, audio_thread_handle, sample_rate) = setup_audio_streaming(&options);
 
     // // Set up audio playback
     // let (_stream, stream_handle) = OutputStream::try_default().unwrap();
@@ -80,11 +100,17 @@ fn initialize_visualization(num_bars: usize) -> (PistonWindow, Vec<Box<dyn Visua
 }
 
 /// Set up the audio streaming and playback
-fn setup_audio_streaming() -> (Receiver<Vec<f32>>, JoinHandle<()>, u32) {
+fn setup_audio_streaming(options: &Options) -> (Receiver<Vec<f32>>, JoinHandle<()>, u32) {
     let (audio_tx, audio_rx) = mpsc::channel::<Vec<f32>>();
 
-    // Initialize MicrophoneSource
-    let mic_source = MicrophoneSource::new().expect("Failed to create MicrophoneSource");
+    // Initialize MicrophoneSource on the selected device
+    let mic_source = match MicrophoneSource::with_device(&options.device, options.config) {
+        Ok(mic_source) => mic_source,
+        Err(e) => {
+            eprintln!("Failed to create MicrophoneSource: {}", e);
+            std::process::exit(1);
+        }
+    };
     let sample_rate = mic_source.get_sample_rate();
 
     // Set up audio streamer with the correct chunk size
@@ -113,6 +139,7 @@ fn setup_audio_streaming() -> (Receiver<Vec<f32>>, JoinHandle<()>, u32) {
 fn process_audio_data(
     audio_rx: &Receiver<Vec<f32>>,
     sample_rate: u32,
+    averager: &mut SpectrumAverager,
     latest_fft_data: &mut Option<fft_lib::Frequencies>,
     audio_stream_ended: &mut bool,
 ) {
@@ -129,7 +156,8 @@ fn process_audio_data(
                     .collect::<Vec<f64>>()
                     .as_slice());
                 let frequencies_data = get_frequencies(&fft_result, sample_rate);
-                *latest_fft_data = Some(frequencies_data);
+                // Every chunk goes into the average, even if it is not drawn
+                *latest_fft_data = Some(averager.process(&frequencies_data));
             }
             Err(TryRecvError::Empty) => {
                 break;
@@ -187,6 +215,9 @@ fn run_event_loop(
 ) {
     let mut audio_stream_ended = false;
     let mut latest_fft_data: Option<fft_lib::Frequencies> = None;
+    let mut averager = SpectrumAverager::new(AveragingMode::Exponential {
+        time_constant: SMOOTHING_TIME,
+    });
 
     // Create a glyph cache for text rendering
     let mut glyph_cache = window.load_font("assets/Roboto-Regular.ttf").unwrap();
@@ -198,6 +229,7 @@ fn run_event_loop(
         process_audio_data(
             &audio_rx,
             sample_rate,
+            &mut averager,
             &mut latest_fft_data,
             &mut audio_stream_ended,
         );
@@ -44,15 +44,13 @@ impl Visualizer for BarVisualizer {
         assert!(data.len() >= self.bars.len());
         let data_per_bar = data.len() / self.bars.len();
 
-        // Apply easing to smooth transitions
-        const EASE_FACTOR: f64 = 0.95;
+        // The data is already averaged over time, see `SpectrumAverager` in main.rs
         for i in 0..self.bars.len() {
             let start_index = i * data_per_bar;
             let end_index = (start_index + data_per_bar).min(data.len());
             let sum = data[start_index..end_index].iter().sum::<f64>();
 
-            let bar = &mut self.bars[i];
-            *bar = *bar * EASE_FACTOR + sum / data_per_bar as f64 * (1.0 - EASE_FACTOR);
+            self.bars[i] = sum / data_per_bar as f64;
         }
     }
 